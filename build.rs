@@ -7,15 +7,69 @@ use {
 
 const CODE: &str = include_str!("gen/codes.txt");
 
+/// Vendor numerics: name of the `numerics-*` feature, enum variant, version prefixes and table.
+///
+/// The tables in `gen/numerics/` have the format of `gen/codes.txt`, and are
+/// merged into it when the feature is enabled. A line starting with `~` gives
+/// a known numeric the meaning it has on this server: it is not used when
+/// parsing, but applied with the dialect of the vendor, like `Vendor::InspIRCd`.
+/// See `Vendor::merge` for the conflicts.
+const VENDORS: &[(&str, &str, &[&str], &str)] = &[
+    (
+        "inspircd",
+        "InspIRCd",
+        &["inspircd"],
+        include_str!("gen/numerics/inspircd.txt"),
+    ),
+    (
+        "unreal",
+        "Unreal",
+        &["unreal"],
+        include_str!("gen/numerics/unreal.txt"),
+    ),
+    (
+        "solanum",
+        "Solanum",
+        &["solanum", "charybdis"],
+        include_str!("gen/numerics/solanum.txt"),
+    ),
+    (
+        "ergo",
+        "Ergo",
+        &["ergo", "oragono"],
+        include_str!("gen/numerics/ergo.txt"),
+    ),
+    (
+        "ngircd",
+        "Ngircd",
+        &["ngircd"],
+        include_str!("gen/numerics/ngircd.txt"),
+    ),
+];
+
 fn main() -> io::Result<()> {
     let out_dir = env::var("OUT_DIR").unwrap();
-    let dest_path = Path::new(&out_dir).join("code.rs");
+    let mut codes = Code::from_iter(CODE.lines());
+    let mut aliases = Alias::from_iter(CODE.lines());
+    let categories = Category::from_iter(CODE.lines());
 
-    let mut file = File::create(&dest_path)?;
+    let mut vendors = Vec::new();
+    for &(name, format_name, versions, table) in VENDORS {
+        let mut vendor = Vendor::new(name, format_name, versions);
+        let feature = format!("CARGO_FEATURE_NUMERICS_{}", name.to_ascii_uppercase());
+        if env::var_os(feature).is_some() {
+            vendor.merge(&mut codes, Code::from_iter(table.lines()));
+            aliases.extend(Alias::from_iter(table.lines()));
+        }
+        vendors.push(vendor);
+    }
+    check(&codes, &aliases, &categories);
 
-    let codes = Code::from_iter(CODE.split('\n'));
+    let mut file = File::create(Path::new(&out_dir).join("code.rs"))?;
+    gen_code(&codes, &aliases, &categories, &vendors, &mut file)?;
 
-    gen_code(&codes, &mut file)?;
+    let mut file = File::create(Path::new(&out_dir).join("reply.rs"))?;
+    gen_replies(&codes, &aliases, &mut file)?;
 
     Ok(())
 }
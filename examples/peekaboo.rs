@@ -7,9 +7,9 @@ use {
 };
 
 macro_rules! write_irc {
-    ($writer:expr, $($arg:tt)*) => {
-        let msg = format!($($arg)*);
-        $writer.raw_wait(msg)?;
+    ($writer:expr, $code:expr, $($arg:expr),*) => {
+        let msg = Message::build($code)$(.arg($arg))*.finish();
+        $writer.send_wait(&msg)?;
     }
 }
 
@@ -24,7 +24,7 @@ fn for_each_message(
             match msg.code {
                 Code::RplWelcome => {
                     // join channel, no password
-                    write_irc!(writer, "JOIN {}\n", channel);
+                    write_irc!(writer, Code::Join, channel);
                 }
                 // JOIN is sent when you join a channel.
                 Code::Join => {
@@ -32,15 +32,15 @@ fn for_each_message(
                     if let Some(Prefix::User(user)) = msg.prefix {
                         // And that user's nick is peekaboo, we've joined the channel!
                         if user.nickname == "peekaboo" {
-                            write_irc!(writer, "PRIVMSG {} :{}\n", channel, "peekaboo");
+                            write_irc!(writer, Code::Privmsg, channel, "peekaboo");
                             // Note that if the reconnection settings said to reconnect,
                             // it would. Close would "really" stop it.
-                            write_irc!(writer, "QUIT :{}\n", "peekaboo");
+                            write_irc!(writer, Code::Quit, "peekaboo");
                         }
                     }
                 }
                 Code::Ping => {
-                    write_irc!(writer, "PONG {}\n", msg.args.join(" "));
+                    write_irc!(writer, Code::Pong, msg.args.join(" "));
                 }
                 _ => {}
             }
@@ -71,8 +71,8 @@ fn main() -> Fallible<()> {
     let irc_stream = IrcStream::from_std(stream, UTF_8);
     let writer = irc_stream.writer();
 
-    write_irc!(writer, "USER {} 8 * :{}\n", "peekaboo", "peekaboo");
-    write_irc!(writer, "NICK {}\n", "peekaboo");
+    write_irc!(writer, Code::User, "peekaboo", "8", "*", "peekaboo");
+    write_irc!(writer, Code::Nick, "peekaboo");
 
     for msg in irc_stream {
         for_each_message(&writer, &channel, msg)?;
@@ -3,47 +3,31 @@ use {
     encoding::all::UTF_8,
     failure::Fallible,
     futures::prelude::*,
-    std::env,
-    yaircc::{Code, IrcStream, Message, Prefix, StreamError, Writer},
+    std::{env, time::Duration},
+    yaircc::{Code, IrcStream, Keepalive, Message, Prefix, Registration, StreamError, Writer},
 };
 
-macro_rules! write_irc {
-    ($writer:expr, $($arg:tt)*) => {
-        let msg = format!($($arg)*);
-        $writer.raw(msg).await?;
-    }
-}
-
 async fn for_each_message(
     writer: &Writer<TcpStream>,
+    nickname: &str,
     channel: &str,
     msg: Result<Message, StreamError>,
 ) -> Fallible<()> {
     match msg {
         Ok(msg) => {
             println!("{:?}", msg);
-            match msg.code {
-                Code::RplWelcome => {
-                    // join channel, no password
-                    write_irc!(writer, "JOIN {}\n", channel);
-                }
-                // JOIN is sent when you join a channel.
-                Code::Join => {
-                    // If there is a prefix and the prefix is a user...
-                    if let Some(Prefix::User(user)) = msg.prefix {
-                        // And that user's nick is peekaboo, we've joined the channel!
-                        if user.nickname == "peekaboo" {
-                            write_irc!(writer, "PRIVMSG {} :{}\n", channel, "peekaboo");
-                            // Note that if the reconnection settings said to reconnect,
-                            // it would. Close would "really" stop it.
-                            write_irc!(writer, "QUIT :{}\n", "peekaboo");
-                        }
+            // JOIN is sent when you join a channel.
+            if msg.code == Code::Join {
+                // If there is a prefix and the prefix is a user...
+                if let Some(Prefix::User(user)) = msg.prefix {
+                    // And that user's nick is ours, we've joined the channel!
+                    if user.nickname == nickname {
+                        writer.privmsg(channel, "peekaboo").await?;
+                        // Note that if the reconnection settings said to reconnect,
+                        // it would. Close would "really" stop it.
+                        writer.quit(Some("peekaboo")).await?;
                     }
                 }
-                Code::Ping => {
-                    write_irc!(writer, "PONG {}\n", msg.args.join(" "));
-                }
-                _ => {}
             }
         }
         Err(e) => eprintln!("{}", e),
@@ -69,14 +53,19 @@ async fn async_main() -> Fallible<()> {
     let (server, channel) = get_args();
 
     let stream = TcpStream::connect(server).await?;
-    let irc_stream = IrcStream::new(stream, UTF_8);
+    let mut irc_stream = IrcStream::new(stream, UTF_8);
     let writer = irc_stream.writer();
 
-    write_irc!(writer, "USER {} 8 * :{}\n", "peekaboo", "peekaboo");
-    write_irc!(writer, "NICK {}\n", "peekaboo");
+    // Falls back to peekaboo_, peekaboo__... if the nick is taken.
+    let registered = Registration::new("peekaboo", "peekaboo", "peekaboo")
+        .register(&mut irc_stream, &writer)
+        .await?;
+    // join channel, no password
+    writer.join(&channel, None).await?;
 
-    irc_stream
-        .then(|msg| for_each_message(&writer, &channel, msg))
+    // Answers PINGs, and gives up if the server stops answering ours.
+    Keepalive::new(irc_stream, Duration::from_secs(60), Duration::from_secs(30))
+        .then(|msg| for_each_message(&writer, &registered.nickname, &channel, msg))
         .try_collect::<()>()
         .err_into()
         .await
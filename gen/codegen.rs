@@ -23,42 +23,220 @@ fn gen_enum(file: &mut File, codes: &[Code]) -> io::Result<()> {
         file,
         "/// Representation of IRC commands, replies and errors."
     )?;
+    writeln!(file, "///")?;
+    writeln!(
+        file,
+        "/// The `numerics-*` features add variants, so matches need a wildcard arm."
+    )?;
     writeln!(file, "#[derive(Clone, Debug, Eq, PartialEq)]")?;
+    writeln!(file, "#[non_exhaustive]")?;
     writeln!(file, "pub enum Code {{")?;
     for code in codes {
         writeln!(file, "    /// {} = \"{}\"", code.code, code.value)?;
         writeln!(file, "    {},", code.format_code)?;
     }
-    writeln!(file, "    /// Codes that are unknown end up in here.")?;
-    writeln!(file, "    Unknown(String),")?;
+    writeln!(file, "    /// Numerics that are unknown end up in here.")?;
+    writeln!(file, "    UnknownNumeric(u16),")?;
+    writeln!(file, "    /// Commands that are unknown end up in here.")?;
+    writeln!(file, "    UnknownCommand(String),")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+fn gen_pattern<'a>(file: &mut File, codes: impl Iterator<Item = &'a Code>) -> io::Result<()> {
+    for (i, code) in codes.enumerate() {
+        let sep = if i == 0 { "" } else { "| " };
+        writeln!(file, "            {}Code::{}", sep, code.format_code)?;
+    }
+
+    Ok(())
+}
+
+fn gen_category(file: &mut File, categories: &[Category]) -> io::Result<()> {
+    writeln!(file, "/// Group of related replies and errors.")?;
+    writeln!(file, "#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]")?;
+    writeln!(file, "pub enum Category {{")?;
+    for category in categories {
+        writeln!(file, "    /// {}", category.name)?;
+        writeln!(file, "    {},", category.format_name)?;
+    }
     writeln!(file, "}}")?;
 
     Ok(())
 }
 
-fn gen_methods(file: &mut File, codes: &[Code]) -> io::Result<()> {
+fn gen_methods(file: &mut File, codes: &[Code], categories: &[Category]) -> io::Result<()> {
     writeln!(file, "impl Code {{")?;
     writeln!(file, "    /// Checks if the code is a reply.")?;
     writeln!(file, "    pub fn is_reply(&self) -> bool {{")?;
-    writeln!(file, "        match *self {{")?;
-    for code in codes {
-        if code.is_reply {
-            writeln!(file, "            Code::{} => true,", code.format_code)?;
-        }
-    }
-    writeln!(file, "            _  => false,")?;
-    writeln!(file, "        }}")?;
+    writeln!(file, "        matches!(")?;
+    writeln!(file, "            *self,")?;
+    gen_pattern(file, codes.iter().filter(|code| code.is_reply))?;
+    writeln!(file, "        )")?;
     writeln!(file, "    }}")?;
     writeln!(file)?;
     writeln!(file, "    /// Check if the code is en error.")?;
     writeln!(file, "    pub fn is_error(&self) -> bool {{")?;
-    writeln!(file, "        match *self {{")?;
+    writeln!(file, "        matches!(")?;
+    writeln!(file, "            *self,")?;
+    gen_pattern(file, codes.iter().filter(|code| code.is_error))?;
+    writeln!(file, "        )")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(
+        file,
+        "    /// Checks if the code is a command, rather than a numeric."
+    )?;
+    writeln!(file, "    pub fn is_command(&self) -> bool {{")?;
+    writeln!(file, "        matches!(")?;
+    writeln!(file, "            *self,")?;
+    gen_pattern(file, codes.iter().filter(|code| code.numeric().is_none()))?;
+    writeln!(file, "            | Code::UnknownCommand(_)")?;
+    writeln!(file, "        )")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Value of the numeric, if the code is one.")?;
+    writeln!(file, "    pub fn numeric(&self) -> Option<u16> {{")?;
+    writeln!(file, "        let numeric = match *self {{")?;
     for code in codes {
-        if code.is_error {
-            writeln!(file, "            Code::{} => true,", code.format_code)?;
+        if let Some(numeric) = code.numeric() {
+            writeln!(
+                file,
+                "            Code::{} => {},",
+                code.format_code, numeric
+            )?;
+        }
+    }
+    writeln!(
+        file,
+        "            Code::UnknownNumeric(numeric) => numeric,"
+    )?;
+    writeln!(file, "            _ => return None,")?;
+    writeln!(file, "        }};")?;
+    writeln!(file, "        Some(numeric)")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(
+        file,
+        "    /// Code of the numeric, or `None` if it has more than three digits."
+    )?;
+    writeln!(
+        file,
+        "    pub fn from_numeric(numeric: u16) -> Option<Self> {{"
+    )?;
+    writeln!(file, "        let code = match numeric {{")?;
+    for code in codes.iter().filter(|code| !code.dialect) {
+        if let Some(numeric) = code.numeric() {
+            writeln!(
+                file,
+                "            {} => Code::{},",
+                numeric, code.format_code
+            )?;
+        }
+    }
+    writeln!(
+        file,
+        "            0..=999 => Code::UnknownNumeric(numeric),"
+    )?;
+    writeln!(file, "            _ => return None,")?;
+    writeln!(file, "        }};")?;
+    writeln!(file, "        Some(code)")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Category of the code, if any.")?;
+    writeln!(file, "    pub fn category(&self) -> Option<Category> {{")?;
+    writeln!(file, "        let category = match *self {{")?;
+    for category in categories {
+        let codes = codes
+            .iter()
+            .filter(|code| category.codes.contains(&code.code));
+        gen_pattern(file, codes)?;
+        writeln!(file, "            => Category::{},", category.format_name)?;
+    }
+    writeln!(file, "            _ => return None,")?;
+    writeln!(file, "        }};")?;
+    writeln!(file, "        Some(category)")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+fn gen_aliases(file: &mut File, aliases: &[Alias]) -> io::Result<()> {
+    writeln!(file, "#[allow(non_upper_case_globals)]")?;
+    writeln!(file, "impl Code {{")?;
+    for alias in aliases {
+        writeln!(file, "    /// {} = {}", alias.code, alias.target)?;
+        writeln!(
+            file,
+            "    pub const {}: Code = Code::{};",
+            alias.format_code, alias.format_target
+        )?;
+    }
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+fn gen_vendors(file: &mut File, vendors: &[Vendor]) -> io::Result<()> {
+    writeln!(file, "/// Server software with its own numerics.")?;
+    writeln!(file, "///")?;
+    writeln!(
+        file,
+        "/// Numerics are only reinterpreted for the vendors enabled with a `numerics-*` feature."
+    )?;
+    writeln!(file, "#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]")?;
+    writeln!(file, "pub enum Vendor {{")?;
+    for vendor in vendors {
+        writeln!(file, "    /// `numerics-{}` feature", vendor.name)?;
+        writeln!(file, "    {},", vendor.format_name)?;
+    }
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "impl Vendor {{")?;
+    writeln!(
+        file,
+        "    /// Detects the vendor from the version in `RPL_MYINFO`, like `solanum-1.0`."
+    )?;
+    writeln!(file, "    pub fn detect(version: &str) -> Option<Self> {{")?;
+    writeln!(file, "        let version = version.to_ascii_lowercase();")?;
+    for vendor in vendors {
+        let checks = vendor
+            .versions
+            .iter()
+            .map(|v| format!("version.starts_with({:?})", v))
+            .collect::<Vec<_>>();
+        writeln!(file, "        if {} {{", checks.join(" || "))?;
+        writeln!(
+            file,
+            "            return Some(Vendor::{});",
+            vendor.format_name
+        )?;
+        writeln!(file, "        }}")?;
+    }
+    writeln!(file, "        None")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "impl Dialect for Vendor {{")?;
+    // Without vendor features, only the wildcard arm is left.
+    writeln!(file, "    #[allow(clippy::match_single_binding)]")?;
+    writeln!(
+        file,
+        "    fn reinterpret(&self, code: &Code) -> Option<Code> {{"
+    )?;
+    writeln!(file, "        match (*self, code.numeric()?) {{")?;
+    for vendor in vendors {
+        for (numeric, format_code) in &vendor.dialect {
+            writeln!(
+                file,
+                "            (Vendor::{}, {}) => Some(Code::{}),",
+                vendor.format_name, numeric, format_code
+            )?;
         }
     }
-    writeln!(file, "            _  => false,")?;
+    writeln!(file, "            _ => None,")?;
     writeln!(file, "        }}")?;
     writeln!(file, "    }}")?;
     writeln!(file, "}}")?;
@@ -83,7 +261,11 @@ fn gen_display(file: &mut File, codes: &[Code]) -> io::Result<()> {
     }
     writeln!(
         file,
-        "            Code::Unknown(ref text) => write!(f, \"{{}}\", text),"
+        "            Code::UnknownNumeric(numeric) => write!(f, \"{{:03}}\", numeric),"
+    )?;
+    writeln!(
+        file,
+        "            Code::UnknownCommand(ref text) => write!(f, \"{{}}\", text),"
     )?;
     writeln!(file, "        }}")?;
     writeln!(file, "    }}")?;
@@ -101,14 +283,26 @@ fn gen_fromstr(file: &mut File, codes: &[Code]) -> io::Result<()> {
         "    fn from_str(s: &str) -> Result<Self, Self::Err> {{"
     )?;
     writeln!(file, "        let code = match s {{")?;
-    for code in codes {
+    for code in codes.iter().filter(|code| !code.dialect) {
         writeln!(
             file,
             "            {} => Code::{},",
             code.format_value, code.format_code
         )?;
     }
-    writeln!(file, "            _ => Code::Unknown(s.to_string()),")?;
+    writeln!(
+        file,
+        "            _ if s.len() == 3 && s.bytes().all(|b| b.is_ascii_digit()) => {{"
+    )?;
+    writeln!(
+        file,
+        "                Code::UnknownNumeric(s.parse().unwrap())"
+    )?;
+    writeln!(file, "            }}")?;
+    writeln!(
+        file,
+        "            _ => Code::UnknownCommand(s.to_string()),"
+    )?;
     writeln!(file, "        }};")?;
     writeln!(file, "        Ok(code)")?;
     writeln!(file, "    }}")?;
@@ -117,12 +311,24 @@ fn gen_fromstr(file: &mut File, codes: &[Code]) -> io::Result<()> {
     Ok(())
 }
 
-pub fn gen_code(codes: &[Code], file: &mut File) -> io::Result<()> {
+pub fn gen_code(
+    codes: &[Code],
+    aliases: &[Alias],
+    categories: &[Category],
+    vendors: &[Vendor],
+    file: &mut File,
+) -> io::Result<()> {
     gen_header(file)?;
     writeln!(file)?;
     gen_enum(file, codes)?;
     writeln!(file)?;
-    gen_methods(file, codes)?;
+    gen_category(file, categories)?;
+    writeln!(file)?;
+    gen_methods(file, codes, categories)?;
+    writeln!(file)?;
+    gen_aliases(file, aliases)?;
+    writeln!(file)?;
+    gen_vendors(file, vendors)?;
     writeln!(file)?;
     gen_display(file, codes)?;
     writeln!(file)?;
@@ -130,3 +336,171 @@ pub fn gen_code(codes: &[Code], file: &mut File) -> io::Result<()> {
 
     Ok(())
 }
+
+fn gen_reply(file: &mut File, code: &Code) -> io::Result<()> {
+    // Fixed text at the end, like `:End of /WHOIS list`, may be omitted by
+    // the server, so it is not required.
+    let trailing = code
+        .params
+        .iter()
+        .rev()
+        .take_while(|param| param.is_fixed())
+        .count();
+    let params = &code.params[..code.params.len() - trailing];
+    let head = params
+        .iter()
+        .take_while(|param| param.is_positional())
+        .count();
+    let tail = params
+        .iter()
+        .rev()
+        .take_while(|param| param.is_positional())
+        .count()
+        .min(params.len() - head);
+    let middle = &params[head..params.len() - tail];
+    if middle.iter().any(Param::is_positional)
+        || (middle.len() > 1 && middle.iter().any(|p| matches!(p, Param::Variadic(_))))
+    {
+        panic!("unsupported parameters for {}: {}", code.code, code.schema);
+    }
+
+    writeln!(file, "/// `{} {}`", code.code, code.schema)?;
+    writeln!(file, "#[derive(Clone, Copy, Debug, Eq, PartialEq)]")?;
+    writeln!(file, "pub struct {}<'a> {{", code.format_code)?;
+    for param in params {
+        match *param {
+            Param::Required(ref name) => {
+                writeln!(file, "    /// `<{}>`", name)?;
+                writeln!(file, "    pub {}: &'a str,", name)?;
+            }
+            Param::Optional(ref name) => {
+                writeln!(file, "    /// `[<{}>]`", name)?;
+                writeln!(file, "    pub {}: Option<&'a str>,", name)?;
+            }
+            Param::Variadic(ref name) => {
+                writeln!(file, "    /// `<{}>...`", name)?;
+                writeln!(file, "    pub {}: &'a [String],", name)?;
+            }
+            Param::Fixed(_) => {}
+        }
+    }
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+
+    writeln!(file, "impl Message {{")?;
+    writeln!(file, "    /// Interprets the message as `{}`.", code.code)?;
+    writeln!(
+        file,
+        "    pub fn {}(&self) -> Result<{}<'_>, ReplyError> {{",
+        code.accessor(),
+        code.format_code
+    )?;
+    let names = params[..head]
+        .iter()
+        .chain(&params[params.len() - tail..])
+        .map(|param| format!("{:?}", param.name()))
+        .collect::<Vec<_>>();
+    let from_end = tail > 0 || !middle.is_empty();
+    let binding = if from_end { "let len = " } else { "" };
+    writeln!(
+        file,
+        "        {}check(self, &Code::{}, &[{}])?;",
+        binding,
+        code.format_code,
+        names.join(", ")
+    )?;
+    if from_end && trailing > 0 {
+        // The trailing text is taken as present when there are enough arguments.
+        writeln!(
+            file,
+            "        let len = if len >= {} {{ len - {} }} else {{ len }};",
+            names.len() + trailing,
+            trailing
+        )?;
+    }
+    if !middle.is_empty() && tail > 0 {
+        writeln!(
+            file,
+            "        let middle = &self.args[{}..len - {}];",
+            head, tail
+        )?;
+    } else if !middle.is_empty() {
+        writeln!(file, "        let middle = &self.args[{}..len];", head)?;
+    }
+    writeln!(file, "        Ok({} {{", code.format_code)?;
+    for (i, param) in params.iter().enumerate() {
+        match *param {
+            Param::Required(ref name) if i < head => {
+                writeln!(file, "            {}: &self.args[{}],", name, i)?
+            }
+            Param::Required(ref name) => writeln!(
+                file,
+                "            {}: &self.args[len - {}],",
+                name,
+                params.len() - i
+            )?,
+            Param::Optional(ref name) if i == head => writeln!(
+                file,
+                "            {}: middle.first().map(String::as_str),",
+                name
+            )?,
+            Param::Optional(ref name) => writeln!(
+                file,
+                "            {}: middle.get({}).map(String::as_str),",
+                name,
+                i - head
+            )?,
+            Param::Variadic(ref name) => writeln!(file, "            {}: middle,", name)?,
+            Param::Fixed(_) => {}
+        }
+    }
+    writeln!(file, "        }})")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+fn gen_alias_reply(file: &mut File, alias: &Alias, target: &Code) -> io::Result<()> {
+    writeln!(file, "/// `{}`, alias of `{}`.", alias.code, alias.target)?;
+    writeln!(
+        file,
+        "pub type {}<'a> = {}<'a>;",
+        alias.format_code, alias.format_target
+    )?;
+    writeln!(file)?;
+    writeln!(file, "impl Message {{")?;
+    writeln!(file, "    /// Interprets the message as `{}`.", alias.code)?;
+    writeln!(
+        file,
+        "    pub fn {}(&self) -> Result<{}<'_>, ReplyError> {{",
+        alias.accessor(),
+        alias.format_target
+    )?;
+    writeln!(file, "        self.{}()", target.accessor())?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+pub fn gen_replies(codes: &[Code], aliases: &[Alias], file: &mut File) -> io::Result<()> {
+    writeln!(file, "// This file was generated automatically.")?;
+    writeln!(
+        file,
+        "// See `gen/` folder and `build.rs` at the project root."
+    )?;
+    for code in codes.iter().filter(|code| !code.params.is_empty()) {
+        writeln!(file)?;
+        gen_reply(file, code)?;
+    }
+    for alias in aliases {
+        let target = codes.iter().find(|code| code.code == alias.target).unwrap();
+        if !target.params.is_empty() {
+            writeln!(file)?;
+            gen_alias_reply(file, alias, target)?;
+        }
+    }
+
+    Ok(())
+}
@@ -2,61 +2,352 @@ fn capfirst(s: &str) -> String {
     s[..1].to_ascii_uppercase() + &s[1..].to_ascii_lowercase()
 }
 
+/// Argument of a reply or an error, as described in `codes.txt`.
+pub enum Param {
+    /// `<name>` or `:<name>`
+    Required(String),
+    /// `[<name>]`
+    Optional(String),
+    /// `<name>...`
+    Variadic(String),
+    /// Fixed argument that is not exposed.
+    Fixed(String),
+}
+
+impl Param {
+    fn parse(word: &str) -> Self {
+        let word = word.strip_prefix(':').unwrap_or(word);
+        if let Some(name) = word
+            .strip_prefix("[<")
+            .and_then(|word| word.strip_suffix(">]"))
+        {
+            Param::Optional(name.to_string())
+        } else if let Some(name) = word
+            .strip_prefix('<')
+            .and_then(|word| word.strip_suffix(">..."))
+        {
+            Param::Variadic(name.to_string())
+        } else if let Some(name) = word
+            .strip_prefix('<')
+            .and_then(|word| word.strip_suffix('>'))
+        {
+            Param::Required(name.to_string())
+        } else {
+            Param::Fixed(word.to_string())
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            Param::Required(ref name)
+            | Param::Optional(ref name)
+            | Param::Variadic(ref name)
+            | Param::Fixed(ref name) => name,
+        }
+    }
+
+    /// Checks if the argument is always at the same position, i.e. neither
+    /// optional nor variadic.
+    pub fn is_positional(&self) -> bool {
+        match *self {
+            Param::Required(_) | Param::Fixed(_) => true,
+            Param::Optional(_) | Param::Variadic(_) => false,
+        }
+    }
+
+    /// Checks if the argument is fixed text.
+    pub fn is_fixed(&self) -> bool {
+        matches!(*self, Param::Fixed(_))
+    }
+}
+
 pub struct Code {
     pub code: String,
     pub value: String,
     pub is_reply: bool,
     pub is_error: bool,
+    /// Meaning specific to a dialect, not used when parsing.
+    pub dialect: bool,
     pub format_code: String,
     pub format_value: String,
+    pub schema: String,
+    pub params: Vec<Param>,
 }
 
 impl Code {
-    pub fn from_iter<'a>(mut iter: impl Iterator<Item = &'a str>) -> Vec<Self> {
+    pub fn from_iter<'a>(iter: impl Iterator<Item = &'a str>) -> Vec<Self> {
         let mut codes = Vec::new();
-        loop {
-            let code = if let Some(code) = iter.next() {
-                code.to_string()
-            } else {
-                break;
-            };
-            let value = if let Some(value) = iter.next() {
-                value.to_string()
-            } else {
-                break;
+        for line in iter {
+            let line = line.trim();
+            if line.is_empty()
+                || line.starts_with('#')
+                || Alias::parse(line).is_some()
+                || Category::parse(line).is_some()
+            {
+                continue;
+            }
+
+            let (line, dialect) = match line.strip_prefix('~') {
+                Some(line) => (line, true),
+                None => (line, false),
             };
+            let mut words = line.splitn(3, ' ');
+            let code = words.next().unwrap().to_string();
+            let value = words
+                .next()
+                .unwrap_or_else(|| panic!("missing value for {}", code))
+                .to_string();
+            let schema = words.next().unwrap_or("").to_string();
 
             let is_reply = code.starts_with("RPL_");
             let is_error = code.starts_with("ERR_");
 
             let format_code = Self::format_code(&code, is_reply, is_error);
             let format_value = Self::format_value(&value);
+            let params = Self::parse_params(&schema);
 
             codes.push(Code {
                 code,
                 value,
                 is_reply,
                 is_error,
+                dialect,
                 format_code,
                 format_value,
+                schema,
+                params,
             });
         }
 
         codes
     }
 
-    fn format_code(code: &str, is_reply: bool, is_error: bool) -> String {
+    pub fn format_code(code: &str, is_reply: bool, is_error: bool) -> String {
         if is_reply || is_error {
             let mut parts = code.split('_');
             let left = parts.next().unwrap();
             let right = parts.next().unwrap();
-            capfirst(&left) + &capfirst(right)
+            capfirst(left) + &capfirst(right)
         } else {
-            capfirst(&code)
+            capfirst(code)
         }
     }
 
     fn format_value(value: &str) -> String {
         format!("\"{}\"", value.to_ascii_uppercase())
     }
+
+    fn parse_params(schema: &str) -> Vec<Param> {
+        let mut params = Vec::new();
+        let mut rest = schema;
+        while !rest.is_empty() {
+            if rest.starts_with(':') {
+                params.push(Param::parse(rest));
+                break;
+            }
+            let (word, next) = match rest.find(' ') {
+                Some(idx) => (&rest[..idx], &rest[idx + 1..]),
+                None => (rest, ""),
+            };
+            params.push(Param::parse(word));
+            rest = next;
+        }
+        params
+    }
+
+    /// Value of the numeric, if it is one.
+    pub fn numeric(&self) -> Option<u16> {
+        if self.value.bytes().all(|b| b.is_ascii_digit()) {
+            self.value.parse().ok()
+        } else {
+            None
+        }
+    }
+
+    /// Name of the accessor method generated for the reply or error.
+    pub fn accessor(&self) -> String {
+        if self.is_error {
+            format!("as_{}", self.code.to_ascii_lowercase())
+        } else {
+            format!("as_{}", self.code[4..].to_ascii_lowercase())
+        }
+    }
+}
+
+/// Other name of a code, as `ALIAS = CODE` in `codes.txt`.
+pub struct Alias {
+    pub code: String,
+    pub target: String,
+    pub format_code: String,
+    pub format_target: String,
+}
+
+impl Alias {
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let code = words.next()?;
+        if words.next()? != "=" {
+            return None;
+        }
+        let target = words
+            .next()
+            .unwrap_or_else(|| panic!("missing target for alias {}", code));
+        let format = |code: &str| {
+            Code::format_code(code, code.starts_with("RPL_"), code.starts_with("ERR_"))
+        };
+        Some(Alias {
+            code: code.to_string(),
+            target: target.to_string(),
+            format_code: format(code),
+            format_target: format(target),
+        })
+    }
+
+    pub fn from_iter<'a>(iter: impl Iterator<Item = &'a str>) -> Vec<Self> {
+        iter.map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(Alias::parse)
+            .collect()
+    }
+
+    /// Name of the accessor method generated for the alias.
+    pub fn accessor(&self) -> String {
+        if self.code.starts_with("ERR_") {
+            format!("as_{}", self.code.to_ascii_lowercase())
+        } else {
+            format!("as_{}", self.code[4..].to_ascii_lowercase())
+        }
+    }
+}
+
+/// Group of codes, as `name: CODE...` in `codes.txt`.
+pub struct Category {
+    pub name: String,
+    pub format_name: String,
+    pub codes: Vec<String>,
+}
+
+impl Category {
+    fn parse(line: &str) -> Option<Self> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?.strip_suffix(':')?;
+        let format_name = name.split('-').map(capfirst).collect();
+        Some(Category {
+            name: name.to_string(),
+            format_name,
+            codes: words.map(str::to_string).collect(),
+        })
+    }
+
+    pub fn from_iter<'a>(iter: impl Iterator<Item = &'a str>) -> Vec<Self> {
+        iter.map(str::trim)
+            .filter(|line| !line.starts_with('#'))
+            .filter_map(Category::parse)
+            .collect()
+    }
+}
+
+/// Checks that the names and values are unique, that the aliases are of known
+/// codes, and that codes are in one category at most.
+pub fn check(codes: &[Code], aliases: &[Alias], categories: &[Category]) {
+    let mut names = std::collections::HashSet::new();
+    let mut values = std::collections::HashMap::new();
+    for code in codes {
+        if !names.insert(code.code.as_str()) {
+            panic!("duplicate code {}", code.code);
+        }
+        if code.dialect {
+            continue;
+        }
+        if let Some(other) = values.insert(code.value.as_str(), code.code.as_str()) {
+            panic!(
+                "{} and {} are both {}, make one an alias of the other",
+                other, code.code, code.value
+            );
+        }
+    }
+    for alias in aliases {
+        if !codes.iter().any(|code| code.code == alias.target) {
+            panic!("alias {} of unknown code {}", alias.code, alias.target);
+        }
+        if !names.insert(alias.code.as_str()) {
+            panic!("duplicate code {}", alias.code);
+        }
+    }
+    let mut categorized = std::collections::HashMap::new();
+    for category in categories {
+        for code in &category.codes {
+            if !codes.iter().any(|c| c.code == *code) {
+                panic!("unknown code {} in category {}", code, category.name);
+            }
+            if let Some(other) = categorized.insert(code.as_str(), category.name.as_str()) {
+                panic!(
+                    "{} is in both categories {} and {}",
+                    code, other, category.name
+                );
+            }
+        }
+    }
+}
+
+/// Server software with its own numerics, in `gen/numerics/`.
+pub struct Vendor {
+    pub name: String,
+    pub format_name: String,
+    /// Version prefixes identifying the server, in lowercase.
+    pub versions: Vec<String>,
+    /// Numerics reinterpreted by the dialect, with the code they mean.
+    pub dialect: Vec<(u16, String)>,
+}
+
+impl Vendor {
+    pub fn new(name: &str, format_name: &str, versions: &[&str]) -> Self {
+        Vendor {
+            name: name.to_string(),
+            format_name: format_name.to_string(),
+            versions: versions.iter().map(|v| v.to_string()).collect(),
+            dialect: Vec::new(),
+        }
+    }
+
+    /// Adds the codes of the vendor table, panicking on conflicts.
+    ///
+    /// A code with the name and value of a known one is the same code.
+    /// Otherwise, a code whose value is known must be marked with `~`,
+    /// making it a meaning specific to the dialect.
+    pub fn merge(&mut self, codes: &mut Vec<Code>, table: Vec<Code>) {
+        for code in table {
+            if let Some(known) = codes.iter_mut().find(|known| known.code == code.code) {
+                if known.value != code.value {
+                    panic!(
+                        "{} is {} but {} in the {} numerics",
+                        code.code, known.value, code.value, self.name
+                    );
+                }
+                if code.dialect {
+                    self.dialect
+                        .push((code.numeric().unwrap(), code.format_code));
+                } else if known.dialect {
+                    // Becomes the meaning used when parsing.
+                    known.dialect = false;
+                }
+                continue;
+            }
+            if code.dialect {
+                let numeric = code
+                    .numeric()
+                    .unwrap_or_else(|| panic!("{} is not a numeric", code.code));
+                self.dialect.push((numeric, code.format_code.clone()));
+            } else if let Some(known) = codes
+                .iter()
+                .find(|known| !known.dialect && known.value == code.value)
+            {
+                panic!(
+                    "{} is {} but {} in the {} numerics, mark it with `~` to keep it to the dialect",
+                    code.value, known.code, code.code, self.name
+                );
+            }
+            codes.push(code);
+        }
+    }
 }
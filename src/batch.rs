@@ -0,0 +1,361 @@
+//! IRCv3 batches, grouping the messages between `BATCH +id` and `BATCH -id`.
+
+use {
+    crate::{code::Code, message::Message, tags::Tags},
+    futures::{
+        prelude::*,
+        ready,
+        task::{Context, Poll},
+    },
+    std::{collections::VecDeque, pin::Pin},
+};
+
+/// Batch of messages, like a `netsplit` or a `labeled-response`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Batch {
+    /// Reference tag, unique among the open batches.
+    pub id: String,
+    /// Type, like `netsplit` or `chathistory`.
+    pub kind: String,
+    /// Parameters following the type, like the servers of a `netsplit`.
+    pub params: Vec<String>,
+    /// Tags of the `BATCH +id` message, like `label`.
+    pub tags: Tags,
+    /// Messages and nested batches, in the order received.
+    pub items: Vec<BatchItem>,
+}
+
+/// Message outside of any batch, or a complete batch.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum BatchItem {
+    /// Single message.
+    Message(Message),
+    /// Batch, from `BATCH +id` to `BATCH -id`.
+    Batch(Batch),
+}
+
+impl Batch {
+    /// Id of the batch opened by a `BATCH +<id> <type> [<params>...]`.
+    fn start_id(msg: &Message) -> Option<&str> {
+        match (&msg.code, msg.args.first(), msg.args.get(1)) {
+            (Code::Batch, Some(id), Some(_)) => id.strip_prefix('+').filter(|id| !id.is_empty()),
+            _ => None,
+        }
+    }
+
+    /// Opens a batch from a `BATCH +<id> <type> [<params>...]`.
+    fn start(id: String, msg: Message) -> Self {
+        let mut args = msg.args.into_iter().skip(1);
+        Batch {
+            id,
+            kind: args.next().unwrap_or_default(),
+            params: args.collect(),
+            tags: msg.tags,
+            items: Vec::new(),
+        }
+    }
+
+    /// `label` of the batch, if it answers a labeled message.
+    pub fn label(&self) -> Option<&str> {
+        self.tags.get("label")
+    }
+
+    /// Messages of the batch, including the ones of nested batches.
+    pub fn messages(&self) -> Vec<&Message> {
+        let mut msgs = Vec::new();
+        for item in &self.items {
+            match item {
+                BatchItem::Message(msg) => msgs.push(msg),
+                BatchItem::Batch(batch) => msgs.extend(batch.messages()),
+            }
+        }
+        msgs
+    }
+}
+
+impl BatchItem {
+    /// `label` of the message or batch, if it answers a labeled message.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            BatchItem::Message(msg) => msg.tags.get("label"),
+            BatchItem::Batch(batch) => batch.label(),
+        }
+    }
+}
+
+/// Buffers the messages of batches until they end.
+///
+/// Messages tagged with an unknown batch, like one started before, are
+/// passed through, and so is a `BATCH +id` reusing the id of an open batch.
+#[derive(Clone, Debug, Default)]
+pub struct BatchAssembler {
+    /// Open batches, with the id of their parent if nested.
+    open: Vec<(Option<String>, Batch)>,
+}
+
+impl BatchAssembler {
+    /// Creates an assembler without any open batch.
+    pub fn new() -> Self {
+        BatchAssembler::default()
+    }
+
+    /// Checks if no batch is open.
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    fn position(&self, id: &str) -> Option<usize> {
+        self.open.iter().position(|(_, batch)| batch.id == id)
+    }
+
+    /// Adds the batch to its parent, or returns it if it has none.
+    fn close(&mut self, parent: Option<String>, batch: Batch) -> Option<BatchItem> {
+        match parent.and_then(|parent| self.position(&parent)) {
+            Some(index) => {
+                self.open[index].1.items.push(BatchItem::Batch(batch));
+                None
+            }
+            None => Some(BatchItem::Batch(batch)),
+        }
+    }
+
+    /// Handles a message, returning it if outside of any batch, or the
+    /// batch it ended.
+    pub fn handle(&mut self, msg: Message) -> Option<BatchItem> {
+        let parent = msg
+            .tags
+            .get("batch")
+            .filter(|id| self.position(id).is_some())
+            .map(String::from);
+
+        if msg.code == Code::Batch {
+            let end = msg
+                .args
+                .first()
+                .and_then(|arg| arg.strip_prefix('-'))
+                .and_then(|id| self.position(id));
+            if let Some(index) = end {
+                let (parent, batch) = self.open.remove(index);
+                return self.close(parent, batch);
+            }
+        }
+        let start = Batch::start_id(&msg).filter(|id| self.position(id).is_none());
+        if let Some(id) = start.map(String::from) {
+            self.open.push((parent, Batch::start(id, msg)));
+            return None;
+        }
+        match parent.and_then(|parent| self.position(&parent)) {
+            Some(index) => {
+                self.open[index].1.items.push(BatchItem::Message(msg));
+                None
+            }
+            None => Some(BatchItem::Message(msg)),
+        }
+    }
+
+    /// Ends the open batches, like when the stream ends, returning the
+    /// outermost ones.
+    pub fn finish(&mut self) -> Vec<BatchItem> {
+        let mut items = Vec::new();
+        // Nested batches are opened after their parent.
+        while let Some((parent, batch)) = self.open.pop() {
+            items.extend(self.close(parent, batch));
+        }
+        items.reverse();
+        items
+    }
+}
+
+/// Stream wrapping a stream of messages, yielding batches as a whole.
+///
+/// Batches still open when the stream ends are yielded as they are.
+pub struct Batched<St> {
+    stream: St,
+    assembler: BatchAssembler,
+    finished: VecDeque<BatchItem>,
+    done: bool,
+}
+
+impl<St> Batched<St> {
+    /// Wraps the stream of messages.
+    pub fn new(stream: St) -> Self {
+        Batched {
+            stream,
+            assembler: BatchAssembler::new(),
+            finished: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Unwraps the inner stream, dropping the open batches.
+    pub fn into_inner(self) -> St {
+        self.stream
+    }
+}
+
+impl<St, E> Stream for Batched<St>
+where
+    St: Stream<Item = Result<Message, E>> + Unpin,
+{
+    type Item = Result<BatchItem, E>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        loop {
+            if this.done {
+                return Poll::Ready(this.finished.pop_front().map(Ok));
+            }
+            match ready!(Pin::new(&mut this.stream).poll_next(cx)) {
+                Some(Ok(msg)) => {
+                    if let Some(item) = this.assembler.handle(msg) {
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                }
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => {
+                    this.done = true;
+                    this.finished.extend(this.assembler.finish());
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn handle_lines(assembler: &mut BatchAssembler, lines: &[&str]) -> Vec<BatchItem> {
+    lines
+        .iter()
+        .filter_map(|line| assembler.handle(Message::parse(line).unwrap()))
+        .collect()
+}
+
+#[test]
+fn test_batch() {
+    let mut assembler = BatchAssembler::new();
+    let items = handle_lines(
+        &mut assembler,
+        &[
+            ":irc.example.com BATCH +yXNAbvnRHTRBv netsplit irc.hub other.host",
+            "@batch=yXNAbvnRHTRBv :aji!a@a QUIT :irc.hub other.host",
+            ":bob!b@b PRIVMSG #chan :hello",
+            "@batch=yXNAbvnRHTRBv :nenolod!a@a QUIT :irc.hub other.host",
+            "@batch=unknown :carol!c@c PRIVMSG #chan :hi",
+            ":irc.example.com BATCH -yXNAbvnRHTRBv",
+        ],
+    );
+    assert_eq!(items.len(), 3);
+    assert!(matches!(items[0], BatchItem::Message(ref msg) if msg.code == Code::Privmsg));
+    assert!(matches!(items[1], BatchItem::Message(ref msg) if msg.tags.contains("batch")));
+    let batch = match items[2] {
+        BatchItem::Batch(ref batch) => batch,
+        _ => panic!("not a batch"),
+    };
+    assert_eq!(batch.id, "yXNAbvnRHTRBv");
+    assert_eq!(batch.kind, "netsplit");
+    assert_eq!(batch.params, vec!["irc.hub", "other.host"]);
+    assert_eq!(batch.messages().len(), 2);
+    assert!(assembler.is_empty());
+}
+
+#[test]
+fn test_nested() {
+    let mut assembler = BatchAssembler::new();
+    let items = handle_lines(
+        &mut assembler,
+        &[
+            "@label=abc :irc.example.com BATCH +outer labeled-response",
+            "@batch=outer :irc.example.com BATCH +inner chathistory #chan",
+            "@batch=inner :bob!b@b PRIVMSG #chan :one",
+            "@batch=inner :bob!b@b PRIVMSG #chan :two",
+            "@batch=outer :irc.example.com BATCH -inner",
+            "@batch=outer :irc.example.com NOTICE me :done",
+            ":irc.example.com BATCH -outer",
+        ],
+    );
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].label(), Some("abc"));
+    let outer = match items[0] {
+        BatchItem::Batch(ref batch) => batch,
+        _ => panic!("not a batch"),
+    };
+    assert_eq!(outer.items.len(), 2);
+    match outer.items[0] {
+        BatchItem::Batch(ref inner) => {
+            assert_eq!(inner.kind, "chathistory");
+            assert_eq!(inner.params, vec!["#chan"]);
+            assert_eq!(inner.items.len(), 2);
+        }
+        _ => panic!("not a batch"),
+    }
+    assert_eq!(outer.messages().len(), 3);
+
+    let items = handle_lines(
+        &mut assembler,
+        &[
+            ":irc.example.com BATCH +a netjoin",
+            "@batch=a :irc.example.com BATCH +b netjoin",
+            "@batch=b :alice!a@a JOIN #chan",
+        ],
+    );
+    assert!(items.is_empty());
+    let items = assembler.finish();
+    assert_eq!(items.len(), 1);
+    assert_eq!(
+        match items[0] {
+            BatchItem::Batch(ref batch) => batch.messages().len(),
+            _ => 0,
+        },
+        1
+    );
+    assert!(assembler.is_empty());
+}
+
+#[test]
+fn test_duplicate_id() {
+    let mut assembler = BatchAssembler::new();
+    let items = handle_lines(
+        &mut assembler,
+        &[
+            ":irc.example.com BATCH +1 netjoin",
+            "@batch=1 :alice!a@a JOIN #chan",
+            ":irc.example.com BATCH +1 netsplit",
+            "@batch=1 :bob!b@b JOIN #chan",
+            ":irc.example.com BATCH -1",
+        ],
+    );
+    assert_eq!(items.len(), 2);
+    // Passed through, not opening another batch.
+    assert!(matches!(items[0], BatchItem::Message(ref msg) if msg.code == Code::Batch));
+    let batch = match items[1] {
+        BatchItem::Batch(ref batch) => batch,
+        _ => panic!("not a batch"),
+    };
+    assert_eq!(batch.kind, "netjoin");
+    assert_eq!(batch.messages().len(), 2);
+    assert!(assembler.is_empty());
+}
+
+#[test]
+fn test_batched() {
+    use futures::{executor::block_on_stream, stream};
+
+    let lines = [
+        ":irc.example.com BATCH +1 netjoin",
+        "@batch=1 :alice!a@a JOIN #chan",
+        ":bob!b@b PRIVMSG #chan :hello",
+        ":irc.example.com BATCH -1",
+        ":irc.example.com BATCH +2 netjoin",
+        "@batch=2 :carol!c@c JOIN #chan",
+    ];
+    let msgs = lines
+        .iter()
+        .map(|line| Message::parse(line))
+        .collect::<Vec<_>>();
+    let items = block_on_stream(Batched::new(stream::iter(msgs)))
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(items.len(), 3);
+    assert!(matches!(items[0], BatchItem::Message(_)));
+    assert!(matches!(items[1], BatchItem::Batch(ref batch) if batch.id == "1"));
+    assert!(matches!(items[2], BatchItem::Batch(ref batch) if batch.id == "2"));
+}
@@ -0,0 +1,410 @@
+//! IRCv3 client capability negotiation.
+
+use {
+    crate::{
+        code::Code,
+        message::Message,
+        stream::{WriteError, Writer},
+    },
+    futures::io::AsyncWrite,
+    std::collections::{BTreeMap, BTreeSet},
+};
+
+/// Maximum length of the capability list in a single `CAP REQ`.
+const MAX_REQ_LEN: usize = 400;
+
+/// State of the capability negotiation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CapState {
+    /// `CAP LS` was not sent yet.
+    Idle,
+    /// Waiting for the `CAP LS` replies.
+    Listing,
+    /// Waiting for the `CAP ACK` or `CAP NAK` replies.
+    Requesting,
+    /// Negotiation is over, but `CAP END` is held back. See `CapNegotiator::hold_end`.
+    Negotiated,
+    /// `CAP END` was sent, or the server does not support capabilities.
+    Ended,
+}
+
+/// Capability negotiation state machine.
+///
+/// Feed every message received from the server to `handle` and send the
+/// messages it returns. Capabilities advertised later with `CAP NEW` are
+/// requested as well, and `CAP DEL` disables them.
+#[derive(Clone, Debug)]
+pub struct CapNegotiator {
+    requested: BTreeSet<String>,
+    available: BTreeMap<String, String>,
+    enabled: BTreeSet<String>,
+    /// Capabilities requested, waiting for `CAP ACK` or `CAP NAK`.
+    requesting: BTreeSet<String>,
+    state: CapState,
+    pending: usize,
+    hold_end: bool,
+}
+
+impl CapNegotiator {
+    /// Creates a negotiator requesting the given capabilities, when available.
+    pub fn new<I>(requested: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        CapNegotiator {
+            requested: requested.into_iter().map(Into::into).collect(),
+            available: BTreeMap::new(),
+            enabled: BTreeSet::new(),
+            requesting: BTreeSet::new(),
+            state: CapState::Idle,
+            pending: 0,
+            hold_end: false,
+        }
+    }
+
+    /// Holds `CAP END` back until `end` is called, e.g. to authenticate first.
+    pub fn hold_end(mut self) -> Self {
+        self.hold_end = true;
+        self
+    }
+
+    /// Current state.
+    pub fn state(&self) -> CapState {
+        self.state
+    }
+
+    /// Checks if the negotiation is over, whether `CAP END` was sent or not.
+    pub fn is_negotiated(&self) -> bool {
+        matches!(self.state, CapState::Negotiated | CapState::Ended)
+    }
+
+    /// Capabilities advertised by the server, with their values.
+    ///
+    /// Capabilities without a value have an empty one.
+    pub fn available(&self) -> &BTreeMap<String, String> {
+        &self.available
+    }
+
+    /// Capabilities enabled for this connection.
+    pub fn enabled(&self) -> &BTreeSet<String> {
+        &self.enabled
+    }
+
+    /// Checks if the capability is enabled.
+    pub fn is_enabled(&self, cap: &str) -> bool {
+        self.enabled.contains(cap)
+    }
+
+    /// Returns the value advertised for the capability, e.g. `PLAIN,EXTERNAL` for `sasl`.
+    pub fn value(&self, cap: &str) -> Option<&str> {
+        self.available.get(cap).map(String::as_str)
+    }
+
+    /// Starts the negotiation, returning `CAP LS 302`.
+    pub fn start(&mut self) -> Message {
+        self.state = CapState::Listing;
+        Message::build(Code::Cap).arg("LS").arg("302").finish()
+    }
+
+    /// Ends a negotiation held back by `hold_end`, returning `CAP END`.
+    pub fn end(&mut self) -> Option<Message> {
+        if self.state == CapState::Negotiated {
+            self.state = CapState::Ended;
+            Some(Message::build(Code::Cap).arg("END").finish())
+        } else {
+            None
+        }
+    }
+
+    /// Handles a message from the server, returning the messages to send.
+    pub fn handle(&mut self, msg: &Message) -> Vec<Message> {
+        let mut out = Vec::new();
+        match msg.code {
+            Code::Cap => {}
+            Code::ErrUnknowncommand if msg.args.get(1).map(String::as_str) == Some("CAP") => {
+                // Server does not support capabilities at all, so there is
+                // no negotiation to end.
+                if !self.is_negotiated() && self.state != CapState::Idle {
+                    self.state = CapState::Ended;
+                }
+                return out;
+            }
+            _ => return out,
+        }
+
+        let subcommand = match msg.args.get(1) {
+            Some(subcommand) => subcommand.to_ascii_uppercase(),
+            None => return out,
+        };
+        let caps = msg.args.last().map(String::as_str).unwrap_or("");
+        let caps = caps.split(' ').filter(|cap| !cap.is_empty());
+
+        match subcommand.as_str() {
+            "LS" => {
+                for cap in caps {
+                    let (name, value) = split_cap(cap);
+                    self.available.insert(name.into(), value.into());
+                }
+                let more = msg.args.len() > 3 && msg.args[2] == "*";
+                if !more && self.state == CapState::Listing {
+                    self.request(&mut out);
+                }
+            }
+            "NEW" => {
+                for cap in caps {
+                    let (name, value) = split_cap(cap);
+                    self.available.insert(name.into(), value.into());
+                }
+                if self.state != CapState::Listing {
+                    self.request(&mut out);
+                }
+            }
+            "DEL" => {
+                for cap in caps {
+                    self.available.remove(cap);
+                    self.enabled.remove(cap);
+                }
+            }
+            "ACK" | "NAK" => {
+                for cap in caps {
+                    let (name, disabled) = match cap.strip_prefix('-') {
+                        Some(name) => (name, true),
+                        None => (cap, false),
+                    };
+                    self.requesting.remove(name);
+                    if subcommand == "ACK" {
+                        if disabled {
+                            self.enabled.remove(name);
+                        } else {
+                            self.enabled.insert(name.into());
+                        }
+                    }
+                }
+                self.pending = self.pending.saturating_sub(1);
+                if self.pending == 0 && self.state == CapState::Requesting {
+                    self.finish(&mut out);
+                }
+            }
+            _ => {}
+        }
+        out
+    }
+
+    /// Requests the wanted capabilities that are available, but neither
+    /// enabled nor already requested.
+    fn request(&mut self, out: &mut Vec<Message>) {
+        let wanted = self
+            .requested
+            .iter()
+            .filter(|cap| {
+                self.available.contains_key(*cap)
+                    && !self.enabled.contains(*cap)
+                    && !self.requesting.contains(*cap)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        self.requesting.extend(wanted.iter().cloned());
+
+        let mut line = String::new();
+        for cap in wanted {
+            if !line.is_empty() && line.len() + 1 + cap.len() > MAX_REQ_LEN {
+                out.push(req(&line));
+                self.pending += 1;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&cap);
+        }
+        if !line.is_empty() {
+            out.push(req(&line));
+            self.pending += 1;
+        }
+
+        if self.state == CapState::Listing {
+            if self.pending > 0 {
+                self.state = CapState::Requesting;
+            } else {
+                self.finish(out);
+            }
+        }
+    }
+
+    fn finish(&mut self, out: &mut Vec<Message>) {
+        self.state = CapState::Negotiated;
+        if !self.hold_end {
+            out.extend(self.end());
+        }
+    }
+
+    /// Starts the negotiation, writing `CAP LS 302` through the writer.
+    pub async fn start_with<S>(&mut self, writer: &Writer<S>) -> Result<(), WriteError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        writer.send(&self.start()).await
+    }
+
+    /// Handles a message from the server, writing the replies through the writer.
+    pub async fn handle_with<S>(
+        &mut self,
+        msg: &Message,
+        writer: &Writer<S>,
+    ) -> Result<(), WriteError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        for msg in self.handle(msg) {
+            writer.send(&msg).await?;
+        }
+        Ok(())
+    }
+}
+
+fn split_cap(cap: &str) -> (&str, &str) {
+    match cap.find('=') {
+        Some(idx) => (&cap[..idx], &cap[idx + 1..]),
+        None => (cap, ""),
+    }
+}
+
+fn req(caps: &str) -> Message {
+    Message::build(Code::Cap).arg("REQ").trailing(caps)
+}
+
+#[cfg(test)]
+fn handle_line(cap: &mut CapNegotiator, line: &str) -> Vec<String> {
+    cap.handle(&Message::parse(line).unwrap())
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn test_negotiate() {
+    let mut cap = CapNegotiator::new(vec!["multi-prefix", "sasl", "batch"]);
+    assert_eq!(cap.start().to_string(), "CAP LS 302");
+    assert!(handle_line(
+        &mut cap,
+        ":irc.example.com CAP * LS * :multi-prefix sasl=PLAIN,EXTERNAL"
+    )
+    .is_empty());
+    assert_eq!(
+        handle_line(&mut cap, ":irc.example.com CAP * LS :away-notify batch"),
+        vec!["CAP REQ :batch multi-prefix sasl"]
+    );
+    assert_eq!(cap.state(), CapState::Requesting);
+    assert_eq!(cap.value("sasl"), Some("PLAIN,EXTERNAL"));
+    assert_eq!(
+        handle_line(
+            &mut cap,
+            ":irc.example.com CAP * ACK :batch multi-prefix sasl"
+        ),
+        vec!["CAP END"]
+    );
+    assert_eq!(cap.state(), CapState::Ended);
+    assert!(cap.is_enabled("sasl"));
+    assert!(!cap.is_enabled("away-notify"));
+}
+
+#[test]
+fn test_nak() {
+    let mut cap = CapNegotiator::new(vec!["sasl"]);
+    cap.start();
+    handle_line(&mut cap, ":irc.example.com CAP * LS :sasl");
+    assert_eq!(
+        handle_line(&mut cap, ":irc.example.com CAP * NAK :sasl"),
+        vec!["CAP END"]
+    );
+    assert!(cap.enabled().is_empty());
+}
+
+#[test]
+fn test_nothing_to_request() {
+    let mut cap = CapNegotiator::new(vec!["sasl"]).hold_end();
+    cap.start();
+    assert!(handle_line(&mut cap, ":irc.example.com CAP * LS :multi-prefix").is_empty());
+    assert_eq!(cap.state(), CapState::Negotiated);
+    assert_eq!(cap.end().unwrap().to_string(), "CAP END");
+    assert_eq!(cap.end(), None);
+}
+
+#[test]
+fn test_unsupported() {
+    let mut cap = CapNegotiator::new(vec!["sasl"]);
+    cap.start();
+    assert!(handle_line(&mut cap, ":irc.example.com 421 * CAP :Unknown command").is_empty());
+    assert_eq!(cap.state(), CapState::Ended);
+    assert_eq!(cap.end(), None);
+}
+
+#[test]
+fn test_new_del() {
+    let mut cap = CapNegotiator::new(vec!["away-notify", "sasl"]);
+    cap.start();
+    handle_line(&mut cap, ":irc.example.com CAP * LS :cap-notify sasl");
+    handle_line(&mut cap, ":irc.example.com CAP * ACK :sasl");
+    assert_eq!(
+        handle_line(
+            &mut cap,
+            ":irc.example.com CAP bob NEW :away-notify extended-join"
+        ),
+        vec!["CAP REQ away-notify"]
+    );
+    assert!(handle_line(&mut cap, ":irc.example.com CAP bob ACK :away-notify").is_empty());
+    assert!(cap.is_enabled("away-notify"));
+    handle_line(&mut cap, ":irc.example.com CAP bob DEL :sasl");
+    assert!(!cap.is_enabled("sasl"));
+    assert_eq!(cap.value("sasl"), None);
+}
+
+#[test]
+fn test_new_while_requesting() {
+    let mut cap = CapNegotiator::new(vec!["away-notify", "batch", "sasl"]);
+    cap.start();
+    assert_eq!(
+        handle_line(&mut cap, ":irc.example.com CAP * LS :cap-notify sasl"),
+        vec!["CAP REQ sasl"]
+    );
+    // sasl is still waiting for its ACK, only batch is new.
+    assert_eq!(
+        handle_line(&mut cap, ":irc.example.com CAP * NEW :batch sasl"),
+        vec!["CAP REQ batch"]
+    );
+    assert!(handle_line(&mut cap, ":irc.example.com CAP * ACK :sasl").is_empty());
+    assert_eq!(
+        handle_line(&mut cap, ":irc.example.com CAP * NAK :batch"),
+        vec!["CAP END"]
+    );
+    assert!(cap.is_enabled("sasl"));
+    assert!(!cap.is_enabled("batch"));
+}
+
+#[test]
+fn test_drive_stream() {
+    use {
+        crate::{mock::MockStream, stream::IrcStream},
+        futures::{executor::block_on, prelude::*},
+    };
+
+    let (mock, output) = MockStream::new(
+        ":irc.example.com CAP * LS :multi-prefix\r\n\
+         :irc.example.com CAP * ACK :multi-prefix\r\n",
+    );
+    let mut stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    let mut cap = CapNegotiator::new(vec!["multi-prefix"]);
+    block_on(async {
+        cap.start_with(&writer).await.unwrap();
+        while let Some(msg) = stream.next().await {
+            cap.handle_with(&msg.unwrap(), &writer).await.unwrap();
+        }
+    });
+    assert!(cap.is_enabled("multi-prefix"));
+    assert_eq!(
+        output.get(),
+        "CAP LS 302\r\nCAP REQ multi-prefix\r\nCAP END\r\n"
+    );
+}
@@ -0,0 +1,215 @@
+//! Case-insensitive comparison of nicknames and channel names.
+
+use std::{
+    cmp::Ordering,
+    fmt,
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+/// Casemapping, as advertised by `CASEMAPPING` in `RPL_ISUPPORT`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum CaseMapping {
+    /// Only `A-Z` are folded to `a-z`.
+    Ascii,
+    /// `A-Z[]\~` are folded to `a-z{}|^`. The default.
+    #[default]
+    Rfc1459,
+    /// `A-Z[]\` are folded to `a-z{}|`.
+    StrictRfc1459,
+}
+
+impl CaseMapping {
+    /// Parses the value of `CASEMAPPING`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ascii" => Some(CaseMapping::Ascii),
+            "rfc1459" => Some(CaseMapping::Rfc1459),
+            "strict-rfc1459" => Some(CaseMapping::StrictRfc1459),
+            _ => None,
+        }
+    }
+
+    /// Name of the casemapping, as in `CASEMAPPING`.
+    pub fn name(self) -> &'static str {
+        match self {
+            CaseMapping::Ascii => "ascii",
+            CaseMapping::Rfc1459 => "rfc1459",
+            CaseMapping::StrictRfc1459 => "strict-rfc1459",
+        }
+    }
+
+    /// Folds the character to lowercase.
+    pub fn fold_char(self, c: char) -> char {
+        match (self, c) {
+            (_, 'A'..='Z') => c.to_ascii_lowercase(),
+            (CaseMapping::Ascii, _) => c,
+            (_, '[') => '{',
+            (_, ']') => '}',
+            (_, '\\') => '|',
+            (CaseMapping::Rfc1459, '~') => '^',
+            _ => c,
+        }
+    }
+
+    /// Folds the string to lowercase.
+    pub fn fold(self, s: &str) -> String {
+        s.chars().map(|c| self.fold_char(c)).collect()
+    }
+
+    /// Checks if the strings are equal once folded.
+    pub fn equals(self, a: &str, b: &str) -> bool {
+        a.len() == b.len() && self.compare(a, b) == Ordering::Equal
+    }
+
+    /// Compares the strings once folded.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        a.chars()
+            .map(|c| self.fold_char(c))
+            .cmp(b.chars().map(|c| self.fold_char(c)))
+    }
+}
+
+impl fmt::Display for CaseMapping {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// String compared, hashed and ordered according to a casemapping.
+///
+/// Use it as the key of maps of nicknames or channels. Strings with
+/// different casemappings should not be compared. It dereferences to `str`,
+/// and the default one is empty with the default casemapping.
+#[derive(Clone, Default)]
+pub struct IrcString {
+    string: String,
+    mapping: CaseMapping,
+}
+
+impl IrcString {
+    /// Wraps the string, to be compared with the given casemapping.
+    pub fn new(string: impl Into<String>, mapping: CaseMapping) -> Self {
+        IrcString {
+            string: string.into(),
+            mapping,
+        }
+    }
+
+    /// String as given, without folding.
+    pub fn as_str(&self) -> &str {
+        &self.string
+    }
+
+    /// Casemapping used to compare the string.
+    pub fn mapping(&self) -> CaseMapping {
+        self.mapping
+    }
+
+    /// Unwraps the string as given.
+    pub fn into_string(self) -> String {
+        self.string
+    }
+
+    /// Same string with another casemapping.
+    pub fn with_mapping(self, mapping: CaseMapping) -> Self {
+        IrcString::new(self.string, mapping)
+    }
+}
+
+impl Deref for IrcString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.string
+    }
+}
+
+impl PartialEq for IrcString {
+    fn eq(&self, other: &Self) -> bool {
+        self.mapping.equals(&self.string, &other.string)
+    }
+}
+
+impl Eq for IrcString {}
+
+impl PartialEq<str> for IrcString {
+    fn eq(&self, other: &str) -> bool {
+        self.mapping.equals(&self.string, other)
+    }
+}
+
+impl PartialEq<&str> for IrcString {
+    fn eq(&self, other: &&str) -> bool {
+        self.mapping.equals(&self.string, other)
+    }
+}
+
+impl PartialOrd for IrcString {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for IrcString {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.mapping.compare(&self.string, &other.string)
+    }
+}
+
+impl Hash for IrcString {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for c in self.string.chars() {
+            self.mapping.fold_char(c).hash(state);
+        }
+        state.write_u8(0xff);
+    }
+}
+
+impl fmt::Display for IrcString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.string)
+    }
+}
+
+impl fmt::Debug for IrcString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.string, f)
+    }
+}
+
+#[test]
+fn test_fold() {
+    assert_eq!(CaseMapping::Ascii.fold("Nick[]\\~"), "nick[]\\~");
+    assert_eq!(CaseMapping::Rfc1459.fold("Nick[]\\~"), "nick{}|^");
+    assert_eq!(CaseMapping::StrictRfc1459.fold("Nick[]\\~"), "nick{}|~");
+    assert!(CaseMapping::Rfc1459.equals("FOO[a]", "foo{A}"));
+    assert!(!CaseMapping::Ascii.equals("FOO[a]", "foo{A}"));
+    assert_eq!(
+        CaseMapping::from_name("strict-rfc1459"),
+        Some(CaseMapping::StrictRfc1459)
+    );
+    assert_eq!(CaseMapping::from_name("rfc7613"), None);
+}
+
+#[test]
+fn test_irc_string() {
+    use std::collections::{BTreeMap, HashMap};
+
+    let key = |s: &str| IrcString::new(s, CaseMapping::Rfc1459);
+    assert_eq!(key("Peek[a]boo"), key("peek{A}BOO"));
+    assert_eq!(key("Peekaboo"), "peekaboo");
+    assert!(key("alice") < key("Bob"));
+
+    let mut hash = HashMap::new();
+    hash.insert(key("Nick^"), 1);
+    assert_eq!(hash.get(&key("nick~")), Some(&1));
+
+    let mut btree = BTreeMap::new();
+    btree.insert(key("#Chan[1]"), 1);
+    assert_eq!(btree.get(&key("#chan{1}")), Some(&1));
+    assert_eq!(btree.keys().next().unwrap().as_str(), "#Chan[1]");
+
+    assert_eq!(IrcString::default().as_str(), "");
+    assert_eq!(IrcString::default().mapping(), CaseMapping::default());
+}
@@ -1 +1,157 @@
 include!(concat!(env!("OUT_DIR"), "/code.rs"));
+
+/// Parser of the code generator, to test the vendor tables whatever the features.
+#[cfg(test)]
+#[allow(dead_code)]
+#[path = "../gen/parser.rs"]
+mod parser;
+
+/// Meaning of numerics for a kind of server.
+///
+/// Numerics are parsed with their most common meaning. Servers giving some of
+/// them another meaning can be handled with `Message::reinterpret`.
+pub trait Dialect {
+    /// Returns the meaning of the code for this server, if it differs.
+    fn reinterpret(&self, code: &Code) -> Option<Code>;
+}
+
+#[test]
+fn test_numeric() {
+    assert_eq!(Code::RplWelcome.numeric(), Some(1));
+    assert_eq!(Code::ErrNicknameinuse.numeric(), Some(433));
+    assert_eq!(Code::Privmsg.numeric(), None);
+    assert_eq!(Code::from_numeric(5), Some(Code::RplIsupport));
+    assert_eq!(Code::from_numeric(999), Some(Code::UnknownNumeric(999)));
+    assert_eq!(Code::from_numeric(42).unwrap().to_string(), "042");
+    assert_eq!(Code::from_numeric(1000), None);
+    for numeric in 0..=999 {
+        let code = Code::from_numeric(numeric).unwrap();
+        assert_eq!(code.numeric(), Some(numeric));
+        assert_eq!(code.to_string().parse(), Ok(code));
+    }
+}
+
+#[test]
+fn test_unknown() {
+    assert_eq!("042".parse(), Ok(Code::UnknownNumeric(42)));
+    assert_eq!("999".parse(), Ok(Code::UnknownNumeric(999)));
+    assert_eq!("9999".parse(), Ok(Code::UnknownCommand("9999".into())));
+    assert_eq!(
+        "WHATEVER".parse(),
+        Ok(Code::UnknownCommand("WHATEVER".into()))
+    );
+    assert!(Code::Privmsg.is_command());
+    assert!(Code::UnknownCommand("WHATEVER".into()).is_command());
+    assert!(!Code::RplWelcome.is_command());
+    assert!(!Code::UnknownNumeric(999).is_command());
+}
+
+#[test]
+fn test_category() {
+    assert_eq!(Code::RplWhoisaccount.category(), Some(Category::Whois));
+    assert_eq!(Code::RplList.category(), Some(Category::ChannelList));
+    assert_eq!(
+        Code::ErrNicknameinuse.category(),
+        Some(Category::Registration)
+    );
+    assert_eq!(Code::RplLocalusers.category(), Some(Category::Lusers));
+    assert_eq!(Code::Privmsg.category(), None);
+}
+
+#[test]
+fn test_dialect() {
+    struct Custom;
+
+    impl Dialect for Custom {
+        fn reinterpret(&self, code: &Code) -> Option<Code> {
+            match code.numeric()? {
+                477 => Some(Code::ErrNeedmoreparams),
+                _ => None,
+            }
+        }
+    }
+
+    assert_eq!(Vendor::detect("solanum-1.0-dev"), Some(Vendor::Solanum));
+    assert_eq!(Vendor::detect("UnrealIRCd-6.1.0"), Some(Vendor::Unreal));
+    assert_eq!(Vendor::detect("oragono-2.0.0"), Some(Vendor::Ergo));
+    assert_eq!(Vendor::detect("hybrid-8.2"), None);
+    assert_eq!(
+        Custom.reinterpret(&Code::ErrNochanmodes),
+        Some(Code::ErrNeedmoreparams)
+    );
+    assert_eq!(Custom.reinterpret(&Code::Privmsg), None);
+    assert_eq!(Vendor::Unreal.reinterpret(&Code::Privmsg), None);
+}
+
+#[cfg(feature = "numerics-unreal")]
+#[test]
+fn test_vendor_numerics() {
+    assert_eq!("489".parse(), Ok(Code::ErrSecureonlychan));
+    assert_eq!("477".parse(), Ok(Code::ErrNochanmodes));
+    assert_eq!(
+        Vendor::Unreal.reinterpret(&Code::ErrNochanmodes),
+        Some(Code::ErrNeedreggednick)
+    );
+    assert_eq!(Code::ErrNeedreggednick.numeric(), Some(477));
+    assert_eq!(Code::ErrNeedreggednick.to_string(), "477");
+}
+
+#[cfg(test)]
+const VENDOR_TABLES: &[(&str, &str)] = &[
+    ("inspircd", include_str!("../gen/numerics/inspircd.txt")),
+    ("unreal", include_str!("../gen/numerics/unreal.txt")),
+    ("solanum", include_str!("../gen/numerics/solanum.txt")),
+    ("ergo", include_str!("../gen/numerics/ergo.txt")),
+    ("ngircd", include_str!("../gen/numerics/ngircd.txt")),
+];
+
+#[cfg(test)]
+fn merge_table(codes: &mut Vec<parser::Code>, name: &str, table: &str) -> parser::Vendor {
+    let mut vendor = parser::Vendor::new(name, name, &[name]);
+    vendor.merge(codes, parser::Code::from_iter(table.lines()));
+    vendor
+}
+
+#[test]
+fn test_vendor_merge() {
+    let table = include_str!("../gen/codes.txt");
+    let mut codes = parser::Code::from_iter(table.lines());
+    let mut aliases = parser::Alias::from_iter(table.lines());
+    let categories = parser::Category::from_iter(table.lines());
+    for (name, table) in VENDOR_TABLES {
+        let vendor = merge_table(&mut codes, name, table);
+        aliases.extend(parser::Alias::from_iter(table.lines()));
+        if *name == "inspircd" {
+            assert!(vendor
+                .dialect
+                .iter()
+                .any(|(numeric, code)| *numeric == 477 && code == "ErrNeedreggednick"));
+        }
+    }
+    parser::check(&codes, &aliases, &categories);
+
+    // The same code in two tables is merged once.
+    let count = |code: &str| codes.iter().filter(|c| c.code == code).count();
+    assert_eq!(count("ERR_BADCHANNAME"), 1);
+    assert_eq!(count("ERR_SECUREONLYCHAN"), 1);
+    // A dialect meaning does not replace the one used when parsing.
+    let known = codes
+        .iter()
+        .find(|c| c.value == "477" && !c.dialect)
+        .unwrap();
+    assert_eq!(known.code, "ERR_NOCHANMODES");
+}
+
+#[test]
+#[should_panic(expected = "mark it with `~`")]
+fn test_vendor_conflict_value() {
+    let mut codes = parser::Code::from_iter(include_str!("../gen/codes.txt").lines());
+    merge_table(&mut codes, "test", "ERR_OTHER 433 <client> :<text>");
+}
+
+#[test]
+#[should_panic(expected = "in the test numerics")]
+fn test_vendor_conflict_name() {
+    let mut codes = parser::Code::from_iter(include_str!("../gen/codes.txt").lines());
+    merge_table(&mut codes, "test", "ERR_NICKNAMEINUSE 434 <client> :<text>");
+}
@@ -0,0 +1,1314 @@
+//! Typed representation of IRC commands.
+
+use {
+    crate::{code::Code, message::Message, tags::Tags},
+    std::{convert::TryFrom, fmt},
+};
+
+/// Error generated when converting a `Message` into a `Command`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CommandError {
+    /// Code of the message is not a command.
+    NotCommand(Code),
+    /// Required argument is missing.
+    MissingArgument {
+        /// Code of the message
+        code: Code,
+        /// Position of the missing argument
+        index: usize,
+        /// Name of the missing argument
+        name: &'static str,
+    },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CommandError::NotCommand(ref code) => write!(f, "{} is not a command", code),
+            CommandError::MissingArgument {
+                ref code,
+                index,
+                name,
+            } => write!(f, "{} is missing argument {} <{}>", code, index, name),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// IRC command with typed arguments, as described in RFC 2812 and IRCv3.
+///
+/// Arguments holding comma separated lists are split into `Vec`s. An omitted
+/// optional argument followed by a present one is sent as `*`, and a `*` in
+/// such a position is parsed back as omitted.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Command {
+    /// `PASS <password>`
+    Pass {
+        /// Connection password
+        password: String,
+    },
+    /// `NICK <nickname>`
+    Nick {
+        /// New nickname
+        nickname: String,
+    },
+    /// `USER <user> <mode> <unused> <realname>`
+    User {
+        /// Username
+        username: String,
+        /// Initial user mode, as a bitmask
+        mode: String,
+        /// Real name
+        realname: String,
+    },
+    /// `OPER <name> <password>`
+    Oper {
+        /// Operator name
+        name: String,
+        /// Operator password
+        password: String,
+    },
+    /// `MODE <target> [<modestring> [<mode arguments>...]]`
+    Mode {
+        /// Channel or nickname
+        target: String,
+        /// Mode string followed by its arguments
+        changes: Vec<String>,
+    },
+    /// `SERVICE <nickname> <reserved> <distribution> <type> <reserved> <info>`
+    Service {
+        /// Nickname of the service
+        nickname: String,
+        /// Mask of the servers the service is visible to
+        distribution: String,
+        /// Type of the service
+        service_type: String,
+        /// Description of the service
+        info: String,
+    },
+    /// `QUIT [<message>]`
+    Quit {
+        /// Quit message
+        message: Option<String>,
+    },
+    /// `SQUIT <server> <comment>`
+    Squit {
+        /// Server to disconnect
+        server: String,
+        /// Reason
+        comment: String,
+    },
+    /// `JOIN <channel>{,<channel>} [<key>{,<key>}]`
+    Join {
+        /// Channels to join
+        channels: Vec<String>,
+        /// Keys of the first channels
+        keys: Vec<String>,
+    },
+    /// `PART <channel>{,<channel>} [<message>]`
+    Part {
+        /// Channels to leave
+        channels: Vec<String>,
+        /// Part message
+        message: Option<String>,
+    },
+    /// `TOPIC <channel> [<topic>]`
+    Topic {
+        /// Channel
+        channel: String,
+        /// New topic, or `None` to query it
+        topic: Option<String>,
+    },
+    /// `NAMES [<channel>{,<channel>} [<target>]]`
+    Names {
+        /// Channels, or all if empty
+        channels: Vec<String>,
+        /// Server to forward the query to
+        target: Option<String>,
+    },
+    /// `LIST [<channel>{,<channel>} [<target>]]`
+    List {
+        /// Channels, or all if empty
+        channels: Vec<String>,
+        /// Server to forward the query to
+        target: Option<String>,
+    },
+    /// `INVITE <nickname> <channel>`
+    Invite {
+        /// User to invite
+        nickname: String,
+        /// Channel
+        channel: String,
+    },
+    /// `KICK <channel> <user>{,<user>} [<comment>]`
+    Kick {
+        /// Channel
+        channel: String,
+        /// Users to kick
+        users: Vec<String>,
+        /// Reason
+        reason: Option<String>,
+    },
+    /// `PRIVMSG <target> <text>`
+    Privmsg {
+        /// Channel or nickname
+        target: String,
+        /// Text of the message
+        text: String,
+    },
+    /// `NOTICE <target> <text>`
+    Notice {
+        /// Channel or nickname
+        target: String,
+        /// Text of the notice
+        text: String,
+    },
+    /// `MOTD [<target>]`
+    Motd {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `LUSERS [<mask> [<target>]]`
+    Lusers {
+        /// Mask of the servers to count
+        mask: Option<String>,
+        /// Server to forward the query to
+        target: Option<String>,
+    },
+    /// `VERSION [<target>]`
+    Version {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `STATS [<query> [<target>]]`
+    Stats {
+        /// Letter of the statistics to query
+        query: Option<String>,
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `LINKS [[<remote server>] <server mask>]`
+    Links {
+        /// Server to forward the query to
+        remote_server: Option<String>,
+        /// Mask of the servers to list
+        server_mask: Option<String>,
+    },
+    /// `TIME [<target>]`
+    Time {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `CONNECT <target server> <port> [<remote server>]`
+    Connect {
+        /// Server to connect to
+        target_server: String,
+        /// Port to connect to
+        port: String,
+        /// Server to connect from
+        remote_server: Option<String>,
+    },
+    /// `TRACE [<target>]`
+    Trace {
+        /// Server or nickname to trace
+        target: Option<String>,
+    },
+    /// `ADMIN [<target>]`
+    Admin {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `INFO [<target>]`
+    Info {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `SERVLIST [<mask> [<type>]]`
+    Servlist {
+        /// Mask of the services to list
+        mask: Option<String>,
+        /// Type of the services to list
+        service_type: Option<String>,
+    },
+    /// `SQUERY <servicename> <text>`
+    Squery {
+        /// Service
+        service: String,
+        /// Text of the message
+        text: String,
+    },
+    /// `WHO [<mask> ["o"]]`
+    Who {
+        /// Mask of the users, or channel
+        mask: Option<String>,
+        /// Only lists operators
+        operators: bool,
+    },
+    /// `WHOIS [<target>] <mask>{,<mask>}`
+    Whois {
+        /// Server to forward the query to
+        target: Option<String>,
+        /// Masks of the users
+        masks: Vec<String>,
+    },
+    /// `WHOWAS <nickname>{,<nickname>} [<count> [<target>]]`
+    Whowas {
+        /// Nicknames
+        nicknames: Vec<String>,
+        /// Maximum number of entries per nickname
+        count: Option<String>,
+        /// Server to forward the query to
+        target: Option<String>,
+    },
+    /// `KILL <nickname> <comment>`
+    Kill {
+        /// User to disconnect
+        nickname: String,
+        /// Reason
+        comment: String,
+    },
+    /// `PING <token> [<target>]`
+    Ping {
+        /// Token echoed in the `PONG`
+        token: String,
+        /// Server to forward the ping to
+        target: Option<String>,
+    },
+    /// `PONG <server> [<token>]`
+    Pong {
+        /// Server answering, or the token when alone
+        server: String,
+        /// Token of the `PING`
+        token: Option<String>,
+    },
+    /// `ERROR <message>`
+    Error {
+        /// Error message
+        message: String,
+    },
+    /// `AWAY [<text>]`
+    Away {
+        /// Away message, or `None` to come back
+        text: Option<String>,
+    },
+    /// `REHASH`
+    Rehash,
+    /// `DIE`
+    Die,
+    /// `RESTART`
+    Restart,
+    /// `SUMMON <user> [<target> [<channel>]]`
+    Summon {
+        /// User to summon
+        user: String,
+        /// Server of the user
+        target: Option<String>,
+        /// Channel to summon the user to
+        channel: Option<String>,
+    },
+    /// `USERS [<target>]`
+    Users {
+        /// Server to query
+        target: Option<String>,
+    },
+    /// `WALLOPS <text>`
+    Wallops {
+        /// Text of the message
+        text: String,
+    },
+    /// `USERHOST <nickname>{ <nickname>}`
+    Userhost {
+        /// Nicknames
+        nicknames: Vec<String>,
+    },
+    /// `ISON <nickname>{ <nickname>}`
+    Ison {
+        /// Nicknames
+        nicknames: Vec<String>,
+    },
+    /// `CAP [<target>] <subcommand> [<params>...]`
+    Cap {
+        /// Nickname of the client, only present in messages sent by the server
+        target: Option<String>,
+        /// Subcommand, like `LS` or `REQ`
+        subcommand: String,
+        /// Parameters of the subcommand
+        params: Vec<String>,
+    },
+    /// `AUTHENTICATE <data>`
+    Authenticate {
+        /// Mechanism, or base64 encoded data
+        data: String,
+    },
+    /// `ACCOUNT <accountname>`
+    Account {
+        /// Account name, or `None` when logged out
+        account: Option<String>,
+    },
+    /// `CHGHOST <new_user> <new_host>`
+    Chghost {
+        /// New username
+        username: String,
+        /// New hostname
+        hostname: String,
+    },
+    /// `SETNAME <realname>`
+    Setname {
+        /// New real name
+        realname: String,
+    },
+    /// `TAGMSG <target>`
+    Tagmsg {
+        /// Channel or nickname
+        target: String,
+    },
+    /// `BATCH (+|-)<reference> [<type> [<params>...]]`
+    Batch {
+        /// Reference tag, prefixed with `+` to open or `-` to close the batch
+        reference: String,
+        /// Type of the batch, only present when opening it
+        batch_type: Option<String>,
+        /// Parameters of the batch type
+        params: Vec<String>,
+    },
+    /// `ACK`
+    Ack,
+    /// `MONITOR <subcommand> [<target>{,<target>}]`
+    Monitor {
+        /// Subcommand, one of `+`, `-`, `C`, `L` or `S`
+        subcommand: String,
+        /// Nicknames to add or remove
+        targets: Vec<String>,
+    },
+    /// `FAIL <command> <code> [<context>...] <description>`
+    Fail {
+        /// Command failing, or `*`
+        command: String,
+        /// Machine readable code
+        code: String,
+        /// Context of the failure
+        context: Vec<String>,
+        /// Human readable description
+        description: String,
+    },
+    /// `WARN <command> <code> [<context>...] <description>`
+    Warn {
+        /// Command the warning is about, or `*`
+        command: String,
+        /// Machine readable code
+        code: String,
+        /// Context of the warning
+        context: Vec<String>,
+        /// Human readable description
+        description: String,
+    },
+    /// `NOTE <command> <code> [<context>...] <description>`
+    Note {
+        /// Command the note is about, or `*`
+        command: String,
+        /// Machine readable code
+        code: String,
+        /// Context of the note
+        context: Vec<String>,
+        /// Human readable description
+        description: String,
+    },
+}
+
+/// Stands for an omitted optional argument followed by a present one.
+const PLACEHOLDER: &str = "*";
+
+impl Command {
+    /// Returns the code of the command.
+    pub fn code(&self) -> Code {
+        match *self {
+            Command::Pass { .. } => Code::Pass,
+            Command::Nick { .. } => Code::Nick,
+            Command::User { .. } => Code::User,
+            Command::Oper { .. } => Code::Oper,
+            Command::Mode { .. } => Code::Mode,
+            Command::Service { .. } => Code::Service,
+            Command::Quit { .. } => Code::Quit,
+            Command::Squit { .. } => Code::Squit,
+            Command::Join { .. } => Code::Join,
+            Command::Part { .. } => Code::Part,
+            Command::Topic { .. } => Code::Topic,
+            Command::Names { .. } => Code::Names,
+            Command::List { .. } => Code::List,
+            Command::Invite { .. } => Code::Invite,
+            Command::Kick { .. } => Code::Kick,
+            Command::Privmsg { .. } => Code::Privmsg,
+            Command::Notice { .. } => Code::Notice,
+            Command::Motd { .. } => Code::Motd,
+            Command::Lusers { .. } => Code::Lusers,
+            Command::Version { .. } => Code::Version,
+            Command::Stats { .. } => Code::Stats,
+            Command::Links { .. } => Code::Links,
+            Command::Time { .. } => Code::Time,
+            Command::Connect { .. } => Code::Connect,
+            Command::Trace { .. } => Code::Trace,
+            Command::Admin { .. } => Code::Admin,
+            Command::Info { .. } => Code::Info,
+            Command::Servlist { .. } => Code::Servlist,
+            Command::Squery { .. } => Code::Squery,
+            Command::Who { .. } => Code::Who,
+            Command::Whois { .. } => Code::Whois,
+            Command::Whowas { .. } => Code::Whowas,
+            Command::Kill { .. } => Code::Kill,
+            Command::Ping { .. } => Code::Ping,
+            Command::Pong { .. } => Code::Pong,
+            Command::Error { .. } => Code::Error,
+            Command::Away { .. } => Code::Away,
+            Command::Rehash => Code::Rehash,
+            Command::Die => Code::Die,
+            Command::Restart => Code::Restart,
+            Command::Summon { .. } => Code::Summon,
+            Command::Users { .. } => Code::Users,
+            Command::Wallops { .. } => Code::Wallops,
+            Command::Userhost { .. } => Code::Userhost,
+            Command::Ison { .. } => Code::Ison,
+            Command::Cap { .. } => Code::Cap,
+            Command::Authenticate { .. } => Code::Authenticate,
+            Command::Account { .. } => Code::Account,
+            Command::Chghost { .. } => Code::Chghost,
+            Command::Setname { .. } => Code::Setname,
+            Command::Tagmsg { .. } => Code::Tagmsg,
+            Command::Batch { .. } => Code::Batch,
+            Command::Ack => Code::Ack,
+            Command::Monitor { .. } => Code::Monitor,
+            Command::Fail { .. } => Code::Fail,
+            Command::Warn { .. } => Code::Warn,
+            Command::Note { .. } => Code::Note,
+        }
+    }
+
+    fn into_args(self) -> Vec<String> {
+        let mut args = Vec::new();
+        match self {
+            Command::Pass { password } => args.push(password),
+            Command::Nick { nickname } => args.push(nickname),
+            Command::User {
+                username,
+                mode,
+                realname,
+            } => args.extend(vec![username, mode, "*".into(), realname]),
+            Command::Oper { name, password } => args.extend(vec![name, password]),
+            Command::Mode { target, changes } => {
+                args.push(target);
+                args.extend(changes);
+            }
+            Command::Service {
+                nickname,
+                distribution,
+                service_type,
+                info,
+            } => args.extend(vec![
+                nickname,
+                "*".into(),
+                distribution,
+                service_type,
+                "0".into(),
+                info,
+            ]),
+            Command::Quit { message } => args.extend(message),
+            Command::Squit { server, comment } => args.extend(vec![server, comment]),
+            Command::Join { channels, keys } => {
+                args.push(channels.join(","));
+                if !keys.is_empty() {
+                    args.push(keys.join(","));
+                }
+            }
+            Command::Part { channels, message } => {
+                args.push(channels.join(","));
+                args.extend(message);
+            }
+            Command::Topic { channel, topic } => {
+                args.push(channel);
+                args.extend(topic);
+            }
+            Command::Names { channels, target } | Command::List { channels, target } => {
+                if !channels.is_empty() || target.is_some() {
+                    args.push(or_placeholder(
+                        Some(channels.join(",")).filter(|c| !c.is_empty()),
+                    ));
+                    args.extend(target);
+                }
+            }
+            Command::Invite { nickname, channel } => args.extend(vec![nickname, channel]),
+            Command::Kick {
+                channel,
+                users,
+                reason,
+            } => {
+                args.extend(vec![channel, users.join(",")]);
+                args.extend(reason);
+            }
+            Command::Privmsg { target, text } | Command::Notice { target, text } => {
+                args.extend(vec![target, text])
+            }
+            Command::Motd { target }
+            | Command::Version { target }
+            | Command::Time { target }
+            | Command::Trace { target }
+            | Command::Admin { target }
+            | Command::Info { target }
+            | Command::Users { target } => args.extend(target),
+            Command::Lusers {
+                mask: first,
+                target: second,
+            }
+            | Command::Stats {
+                query: first,
+                target: second,
+            }
+            | Command::Servlist {
+                mask: first,
+                service_type: second,
+            } => {
+                if second.is_some() {
+                    args.push(or_placeholder(first));
+                    args.extend(second);
+                } else {
+                    args.extend(first);
+                }
+            }
+            Command::Links {
+                remote_server,
+                server_mask,
+            } => {
+                if remote_server.is_some() {
+                    args.extend(remote_server);
+                    args.push(or_placeholder(server_mask));
+                } else {
+                    args.extend(server_mask);
+                }
+            }
+            Command::Connect {
+                target_server,
+                port,
+                remote_server,
+            } => {
+                args.extend(vec![target_server, port]);
+                args.extend(remote_server);
+            }
+            Command::Squery { service, text } => args.extend(vec![service, text]),
+            Command::Who { mask, operators } => {
+                if let Some(mask) = mask {
+                    args.push(mask);
+                    if operators {
+                        args.push("o".into());
+                    }
+                }
+            }
+            Command::Whois { target, masks } => {
+                args.extend(target);
+                args.push(masks.join(","));
+            }
+            Command::Whowas {
+                nicknames,
+                count,
+                target,
+            } => {
+                args.push(nicknames.join(","));
+                if target.is_some() {
+                    args.push(or_placeholder(count));
+                    args.extend(target);
+                } else {
+                    args.extend(count);
+                }
+            }
+            Command::Kill { nickname, comment } => args.extend(vec![nickname, comment]),
+            Command::Ping { token, target } => {
+                args.push(token);
+                args.extend(target);
+            }
+            Command::Pong { server, token } => {
+                args.push(server);
+                args.extend(token);
+            }
+            Command::Error { message } => args.push(message),
+            Command::Away { text } => args.extend(text),
+            Command::Rehash | Command::Die | Command::Restart => {}
+            Command::Summon {
+                user,
+                target,
+                channel,
+            } => {
+                args.push(user);
+                if channel.is_some() {
+                    args.push(or_placeholder(target));
+                    args.extend(channel);
+                } else {
+                    args.extend(target);
+                }
+            }
+            Command::Wallops { text } => args.push(text),
+            Command::Userhost { nicknames } | Command::Ison { nicknames } => args.extend(nicknames),
+            Command::Cap {
+                target,
+                subcommand,
+                params,
+            } => {
+                args.extend(target);
+                args.push(subcommand);
+                args.extend(params);
+            }
+            Command::Authenticate { data } => args.push(data),
+            Command::Account { account } => args.push(account.unwrap_or_else(|| "*".into())),
+            Command::Chghost { username, hostname } => args.extend(vec![username, hostname]),
+            Command::Setname { realname } => args.push(realname),
+            Command::Tagmsg { target } => args.push(target),
+            Command::Batch {
+                reference,
+                batch_type,
+                params,
+            } => {
+                args.push(reference);
+                args.extend(batch_type);
+                args.extend(params);
+            }
+            Command::Ack => {}
+            Command::Monitor {
+                subcommand,
+                targets,
+            } => {
+                args.push(subcommand);
+                if !targets.is_empty() {
+                    args.push(targets.join(","));
+                }
+            }
+            Command::Fail {
+                command,
+                code,
+                context,
+                description,
+            }
+            | Command::Warn {
+                command,
+                code,
+                context,
+                description,
+            }
+            | Command::Note {
+                command,
+                code,
+                context,
+                description,
+            } => {
+                args.extend(vec![command, code]);
+                args.extend(context);
+                args.push(description);
+            }
+        }
+        args
+    }
+}
+
+/// Argument, or the placeholder if omitted.
+fn or_placeholder(arg: Option<String>) -> String {
+    arg.unwrap_or_else(|| PLACEHOLDER.into())
+}
+
+const CAP_SUBCOMMANDS: &[&str] = &["LS", "LIST", "REQ", "ACK", "NAK", "END", "NEW", "DEL"];
+
+struct Args<'a> {
+    code: &'a Code,
+    args: &'a [String],
+}
+
+impl Args<'_> {
+    fn req(&self, index: usize, name: &'static str) -> Result<String, CommandError> {
+        self.args
+            .get(index)
+            .cloned()
+            .ok_or_else(|| CommandError::MissingArgument {
+                code: self.code.clone(),
+                index,
+                name,
+            })
+    }
+
+    fn opt(&self, index: usize) -> Option<String> {
+        self.args.get(index).cloned()
+    }
+
+    /// Optional argument which may be the placeholder, if followed by another.
+    fn opt_before(&self, index: usize) -> Option<String> {
+        self.opt(index)
+            .filter(|arg| arg != PLACEHOLDER || self.args.len() <= index + 1)
+    }
+
+    fn list(&self, index: usize) -> Vec<String> {
+        match self.args.get(index) {
+            Some(arg) if !arg.is_empty() => arg.split(',').map(Into::into).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn req_list(&self, index: usize, name: &'static str) -> Result<Vec<String>, CommandError> {
+        let list = self.list(index);
+        if list.is_empty() {
+            return Err(CommandError::MissingArgument {
+                code: self.code.clone(),
+                index,
+                name,
+            });
+        }
+        Ok(list)
+    }
+
+    /// List which may be the placeholder, if followed by another argument.
+    fn list_before(&self, index: usize) -> Vec<String> {
+        match self.opt_before(index) {
+            Some(_) => self.list(index),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl TryFrom<&Message> for Command {
+    type Error = CommandError;
+
+    fn try_from(msg: &Message) -> Result<Self, CommandError> {
+        let a = Args {
+            code: &msg.code,
+            args: &msg.args,
+        };
+        let command = match msg.code {
+            Code::Pass => Command::Pass {
+                password: a.req(0, "password")?,
+            },
+            Code::Nick => Command::Nick {
+                nickname: a.req(0, "nickname")?,
+            },
+            Code::User => Command::User {
+                username: a.req(0, "user")?,
+                mode: a.req(1, "mode")?,
+                realname: a.req(3, "realname")?,
+            },
+            Code::Oper => Command::Oper {
+                name: a.req(0, "name")?,
+                password: a.req(1, "password")?,
+            },
+            Code::Mode => Command::Mode {
+                target: a.req(0, "target")?,
+                changes: msg.args[1..].to_vec(),
+            },
+            Code::Service => Command::Service {
+                nickname: a.req(0, "nickname")?,
+                distribution: a.req(2, "distribution")?,
+                service_type: a.req(3, "type")?,
+                info: a.req(5, "info")?,
+            },
+            Code::Quit => Command::Quit { message: a.opt(0) },
+            Code::Squit => Command::Squit {
+                server: a.req(0, "server")?,
+                comment: a.req(1, "comment")?,
+            },
+            Code::Join => Command::Join {
+                channels: a.req_list(0, "channel")?,
+                keys: a.list(1),
+            },
+            Code::Part => Command::Part {
+                channels: a.req_list(0, "channel")?,
+                message: a.opt(1),
+            },
+            Code::Topic => Command::Topic {
+                channel: a.req(0, "channel")?,
+                topic: a.opt(1),
+            },
+            Code::Names => Command::Names {
+                channels: a.list_before(0),
+                target: a.opt(1),
+            },
+            Code::List => Command::List {
+                channels: a.list_before(0),
+                target: a.opt(1),
+            },
+            Code::Invite => Command::Invite {
+                nickname: a.req(0, "nickname")?,
+                channel: a.req(1, "channel")?,
+            },
+            Code::Kick => Command::Kick {
+                channel: a.req(0, "channel")?,
+                users: a.req_list(1, "user")?,
+                reason: a.opt(2),
+            },
+            Code::Privmsg => Command::Privmsg {
+                target: a.req(0, "target")?,
+                text: a.req(1, "text")?,
+            },
+            Code::Notice => Command::Notice {
+                target: a.req(0, "target")?,
+                text: a.req(1, "text")?,
+            },
+            Code::Motd => Command::Motd { target: a.opt(0) },
+            Code::Lusers => Command::Lusers {
+                mask: a.opt_before(0),
+                target: a.opt(1),
+            },
+            Code::Version => Command::Version { target: a.opt(0) },
+            Code::Stats => Command::Stats {
+                query: a.opt_before(0),
+                target: a.opt(1),
+            },
+            Code::Links => match msg.args.len() {
+                0 | 1 => Command::Links {
+                    remote_server: None,
+                    server_mask: a.opt(0),
+                },
+                _ => Command::Links {
+                    remote_server: a.opt(0),
+                    server_mask: a.opt(1).filter(|mask| mask != PLACEHOLDER),
+                },
+            },
+            Code::Time => Command::Time { target: a.opt(0) },
+            Code::Connect => Command::Connect {
+                target_server: a.req(0, "target server")?,
+                port: a.req(1, "port")?,
+                remote_server: a.opt(2),
+            },
+            Code::Trace => Command::Trace { target: a.opt(0) },
+            Code::Admin => Command::Admin { target: a.opt(0) },
+            Code::Info => Command::Info { target: a.opt(0) },
+            Code::Servlist => Command::Servlist {
+                mask: a.opt_before(0),
+                service_type: a.opt(1),
+            },
+            Code::Squery => Command::Squery {
+                service: a.req(0, "servicename")?,
+                text: a.req(1, "text")?,
+            },
+            Code::Who => Command::Who {
+                mask: a.opt(0),
+                operators: a.opt(1).is_some_and(|o| o == "o"),
+            },
+            Code::Whois => match msg.args.len() {
+                0 | 1 => Command::Whois {
+                    target: None,
+                    masks: a.req_list(0, "mask")?,
+                },
+                _ => Command::Whois {
+                    target: a.opt(0),
+                    masks: a.req_list(1, "mask")?,
+                },
+            },
+            Code::Whowas => Command::Whowas {
+                nicknames: a.req_list(0, "nickname")?,
+                count: a.opt_before(1),
+                target: a.opt(2),
+            },
+            Code::Kill => Command::Kill {
+                nickname: a.req(0, "nickname")?,
+                comment: a.req(1, "comment")?,
+            },
+            Code::Ping => Command::Ping {
+                token: a.req(0, "token")?,
+                target: a.opt(1),
+            },
+            Code::Pong => Command::Pong {
+                server: a.req(0, "server")?,
+                token: a.opt(1),
+            },
+            Code::Error => Command::Error {
+                message: a.req(0, "message")?,
+            },
+            Code::Away => Command::Away { text: a.opt(0) },
+            Code::Rehash => Command::Rehash,
+            Code::Die => Command::Die,
+            Code::Restart => Command::Restart,
+            Code::Summon => Command::Summon {
+                user: a.req(0, "user")?,
+                target: a.opt_before(1),
+                channel: a.opt(2),
+            },
+            Code::Users => Command::Users { target: a.opt(0) },
+            Code::Wallops => Command::Wallops {
+                text: a.req(0, "text")?,
+            },
+            Code::Userhost => Command::Userhost {
+                nicknames: msg.args.clone(),
+            },
+            Code::Ison => Command::Ison {
+                nicknames: msg.args.clone(),
+            },
+            Code::Cap => {
+                let has_target = msg
+                    .args
+                    .get(1)
+                    .is_some_and(|arg| CAP_SUBCOMMANDS.contains(&arg.as_str()));
+                let start = if has_target { 1 } else { 0 };
+                Command::Cap {
+                    target: if has_target { a.opt(0) } else { None },
+                    subcommand: a.req(start, "subcommand")?,
+                    params: msg.args[start + 1..].to_vec(),
+                }
+            }
+            Code::Authenticate => Command::Authenticate {
+                data: a.req(0, "data")?,
+            },
+            Code::Account => Command::Account {
+                account: Some(a.req(0, "accountname")?).filter(|account| account != "*"),
+            },
+            Code::Chghost => Command::Chghost {
+                username: a.req(0, "new_user")?,
+                hostname: a.req(1, "new_host")?,
+            },
+            Code::Setname => Command::Setname {
+                realname: a.req(0, "realname")?,
+            },
+            Code::Tagmsg => Command::Tagmsg {
+                target: a.req(0, "target")?,
+            },
+            Code::Batch => Command::Batch {
+                reference: a.req(0, "reference")?,
+                batch_type: a.opt(1),
+                params: msg.args.get(2..).unwrap_or_default().to_vec(),
+            },
+            Code::Ack => Command::Ack,
+            Code::Monitor => Command::Monitor {
+                subcommand: a.req(0, "subcommand")?,
+                targets: a.list(1),
+            },
+            Code::Fail | Code::Warn | Code::Note => {
+                let command = a.req(0, "command")?;
+                let code = a.req(1, "code")?;
+                let description = a.req(msg.args.len().max(3) - 1, "description")?;
+                let context = msg.args[2..msg.args.len() - 1].to_vec();
+                match msg.code {
+                    Code::Fail => Command::Fail {
+                        command,
+                        code,
+                        context,
+                        description,
+                    },
+                    Code::Warn => Command::Warn {
+                        command,
+                        code,
+                        context,
+                        description,
+                    },
+                    _ => Command::Note {
+                        command,
+                        code,
+                        context,
+                        description,
+                    },
+                }
+            }
+            ref code => return Err(CommandError::NotCommand(code.clone())),
+        };
+        Ok(command)
+    }
+}
+
+impl TryFrom<Message> for Command {
+    type Error = CommandError;
+
+    fn try_from(msg: Message) -> Result<Self, CommandError> {
+        Command::try_from(&msg)
+    }
+}
+
+impl From<Command> for Message {
+    fn from(command: Command) -> Self {
+        Message {
+            tags: Tags::new(),
+            prefix: None,
+            code: command.code(),
+            args: command.into_args(),
+        }
+    }
+}
+
+#[test]
+fn test_privmsg() {
+    let msg = Message::parse(":bob!bob@bob.com PRIVMSG #chan :hello world").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Privmsg {
+            target: "#chan".into(),
+            text: "hello world".into(),
+        })
+    );
+}
+
+#[test]
+fn test_join() {
+    let msg = Message::parse("JOIN #a,#b,#c key1,key2").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Join {
+            channels: vec!["#a".into(), "#b".into(), "#c".into()],
+            keys: vec!["key1".into(), "key2".into()],
+        })
+    );
+}
+
+#[test]
+fn test_kick() {
+    let msg = Message::parse("KICK #chan alice,bob :bye").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Kick {
+            channel: "#chan".into(),
+            users: vec!["alice".into(), "bob".into()],
+            reason: Some("bye".into()),
+        })
+    );
+}
+
+#[test]
+fn test_mode() {
+    let msg = Message::parse("MODE #chan +ovk-l alice bob secret").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Mode {
+            target: "#chan".into(),
+            changes: vec![
+                "+ovk-l".into(),
+                "alice".into(),
+                "bob".into(),
+                "secret".into()
+            ],
+        })
+    );
+}
+
+#[test]
+fn test_missing_argument() {
+    let msg = Message::parse("KICK #chan").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Err(CommandError::MissingArgument {
+            code: Code::Kick,
+            index: 1,
+            name: "user",
+        })
+    );
+    let msg = Message::parse("MODE").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Err(CommandError::MissingArgument {
+            code: Code::Mode,
+            index: 0,
+            name: "target",
+        })
+    );
+}
+
+#[test]
+fn test_not_command() {
+    let msg = Message::parse(":irc.example.com 001 bob :Welcome").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Err(CommandError::NotCommand(Code::RplWelcome))
+    );
+}
+
+#[test]
+fn test_ircv3() {
+    let msg = Message::parse("BATCH +ref netsplit a.example b.example").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Batch {
+            reference: "+ref".into(),
+            batch_type: Some("netsplit".into()),
+            params: vec!["a.example".into(), "b.example".into()],
+        })
+    );
+    let msg = Message::parse("ACCOUNT *").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Account { account: None })
+    );
+    let msg = Message::parse("NOTE * OPER_MESSAGE :Reloaded").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Ok(Command::Note {
+            command: "*".into(),
+            code: "OPER_MESSAGE".into(),
+            context: vec![],
+            description: "Reloaded".into(),
+        })
+    );
+    let msg = Message::parse("FAIL * CODE").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Err(CommandError::MissingArgument {
+            code: Code::Fail,
+            index: 2,
+            name: "description",
+        })
+    );
+}
+
+#[test]
+fn test_into_message() {
+    let lines = [
+        "PRIVMSG #chan :hello world",
+        "JOIN #a,#b key",
+        "KICK #chan alice,bob :bye",
+        "MODE #chan +ov alice bob",
+        "USER bob 0 * :Bob Bobson",
+        "WHOIS irc.example.com bob",
+        "WHO #chan o",
+        "QUIT",
+        "PONG irc.example.com",
+        "CAP REQ :multi-prefix sasl",
+        "CAP * LS * :multi-prefix sasl",
+        "ACCOUNT *",
+        "ACCOUNT bob",
+        "CHGHOST bob host.example",
+        "SETNAME :Bob Bobson",
+        "TAGMSG #chan",
+        "BATCH +ref chathistory #chan",
+        "BATCH -ref",
+        "ACK",
+        "MONITOR + alice,bob",
+        "MONITOR L",
+        "FAIL CHATHISTORY INVALID_TARGET #chan :Messages could not be retrieved",
+        "WARN * ACCOUNT_REQUIRED :Log in to send",
+    ];
+    for line in lines.iter() {
+        let msg = Message::parse(line).unwrap();
+        let command = Command::try_from(&msg).unwrap();
+        assert_eq!(Message::from(command), msg);
+    }
+}
+
+#[test]
+fn test_optional_round_trip() {
+    let some = |s: &str| Some(String::from(s));
+    let commands = vec![
+        Command::Names {
+            channels: vec![],
+            target: None,
+        },
+        Command::Names {
+            channels: vec![],
+            target: some("irc.example.com"),
+        },
+        Command::List {
+            channels: vec!["#a".into(), "#b".into()],
+            target: None,
+        },
+        Command::List {
+            channels: vec![],
+            target: some("irc.example.com"),
+        },
+        Command::Lusers {
+            mask: None,
+            target: None,
+        },
+        Command::Lusers {
+            mask: some("*.fi"),
+            target: None,
+        },
+        Command::Lusers {
+            mask: None,
+            target: some("irc.example.com"),
+        },
+        Command::Lusers {
+            mask: some("*.fi"),
+            target: some("irc.example.com"),
+        },
+        Command::Stats {
+            query: some("m"),
+            target: None,
+        },
+        Command::Stats {
+            query: None,
+            target: some("irc.example.com"),
+        },
+        Command::Servlist {
+            mask: None,
+            service_type: some("0xD000"),
+        },
+        Command::Links {
+            remote_server: None,
+            server_mask: None,
+        },
+        Command::Links {
+            remote_server: None,
+            server_mask: some("*.fi"),
+        },
+        Command::Links {
+            remote_server: some("irc.example.com"),
+            server_mask: None,
+        },
+        Command::Links {
+            remote_server: some("irc.example.com"),
+            server_mask: some("*.fi"),
+        },
+        Command::Whowas {
+            nicknames: vec!["bob".into()],
+            count: None,
+            target: None,
+        },
+        Command::Whowas {
+            nicknames: vec!["bob".into()],
+            count: some("3"),
+            target: None,
+        },
+        Command::Whowas {
+            nicknames: vec!["bob".into()],
+            count: None,
+            target: some("irc.example.com"),
+        },
+        Command::Whowas {
+            nicknames: vec!["bob".into()],
+            count: some("3"),
+            target: some("irc.example.com"),
+        },
+        Command::Summon {
+            user: "bob".into(),
+            target: None,
+            channel: None,
+        },
+        Command::Summon {
+            user: "bob".into(),
+            target: some("irc.example.com"),
+            channel: None,
+        },
+        Command::Summon {
+            user: "bob".into(),
+            target: None,
+            channel: some("#chan"),
+        },
+        Command::Summon {
+            user: "bob".into(),
+            target: some("irc.example.com"),
+            channel: some("#chan"),
+        },
+    ];
+    for command in commands {
+        let line = Message::from(command.clone()).to_string();
+        let msg = Message::parse(&line).unwrap();
+        assert_eq!(Command::try_from(&msg), Ok(command), "{}", line);
+    }
+    assert_eq!(
+        Message::from(Command::Lusers {
+            mask: None,
+            target: some("irc.example.com"),
+        })
+        .to_string(),
+        "LUSERS * irc.example.com"
+    );
+}
+
+#[test]
+fn test_empty_list() {
+    let msg = Message::parse("WHOIS irc.example.com :").unwrap();
+    assert_eq!(
+        Command::try_from(&msg),
+        Err(CommandError::MissingArgument {
+            code: Code::Whois,
+            index: 1,
+            name: "mask",
+        })
+    );
+    let msg = Message::parse("JOIN :").unwrap();
+    assert!(Command::try_from(&msg).is_err());
+}
@@ -0,0 +1,343 @@
+//! Client-to-client protocol (CTCP) inside `PRIVMSG` and `NOTICE`.
+
+use {
+    crate::{
+        casemap::IrcString,
+        code::Code,
+        command::Command,
+        isupport::ISupport,
+        message::{Message, Prefix},
+        stream::{WriteError, Writer},
+    },
+    futures::io::AsyncWrite,
+    std::{
+        collections::HashMap,
+        fmt,
+        time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Delimiter of CTCP messages.
+const DELIM: char = '\x01';
+
+/// CTCP message, like `ACTION waves` or `VERSION`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Ctcp {
+    /// Command, in uppercase.
+    pub command: String,
+    /// Parameters, everything after the command.
+    pub params: Option<String>,
+}
+
+/// Whether a CTCP message is a request or a reply.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CtcpKind {
+    /// Request, sent with `PRIVMSG`.
+    Request,
+    /// Reply, sent with `NOTICE`.
+    Reply,
+}
+
+impl Ctcp {
+    /// Creates a CTCP message, uppercasing the command.
+    pub fn new(command: &str, params: Option<&str>) -> Self {
+        Ctcp {
+            command: command.to_ascii_uppercase(),
+            params: params.map(Into::into),
+        }
+    }
+
+    /// `ACTION <text>`, as in `/me`.
+    pub fn action(text: &str) -> Self {
+        Ctcp::new("ACTION", Some(text))
+    }
+
+    /// Parses the text of a `PRIVMSG` or `NOTICE`.
+    ///
+    /// The final delimiter may be missing, as some clients omit it.
+    pub fn parse(text: &str) -> Option<Self> {
+        let text = text.strip_prefix(DELIM)?;
+        let text = text.strip_suffix(DELIM).unwrap_or(text);
+        let (command, params) = match text.split_once(' ') {
+            Some((command, params)) => (command, Some(params)),
+            None => (text, None),
+        };
+        if command.is_empty() {
+            return None;
+        }
+        Some(Ctcp::new(command, params))
+    }
+
+    /// Parses a `PRIVMSG` or `NOTICE`, returning the target as well.
+    pub fn from_message(msg: &Message) -> Option<(CtcpKind, &str, Ctcp)> {
+        let kind = match msg.code {
+            Code::Privmsg => CtcpKind::Request,
+            Code::Notice => CtcpKind::Reply,
+            _ => return None,
+        };
+        match msg.args.as_slice() {
+            [target, text] => Some((kind, target, Ctcp::parse(text)?)),
+            _ => None,
+        }
+    }
+
+    /// `PRIVMSG` sending the request to the target.
+    pub fn request(&self, target: &str) -> Message {
+        Command::Privmsg {
+            target: target.into(),
+            text: self.to_string(),
+        }
+        .into()
+    }
+
+    /// `NOTICE` sending the reply to the target.
+    pub fn reply(&self, target: &str) -> Message {
+        Command::Notice {
+            target: target.into(),
+            text: self.to_string(),
+        }
+        .into()
+    }
+}
+
+impl fmt::Display for Ctcp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", DELIM, self.command)?;
+        if let Some(params) = &self.params {
+            write!(f, " {}", params)?;
+        }
+        write!(f, "{}", DELIM)
+    }
+}
+
+/// Automatic replies to `VERSION`, `PING`, `TIME`, `CLIENTINFO` and `SOURCE`.
+///
+/// A user gets at most one reply per interval, so that requests cannot be
+/// used to flood us off the server. Feed every message received from the
+/// server to `handle`, so that users are told apart with its `CASEMAPPING`.
+#[derive(Clone, Debug)]
+pub struct Responder {
+    version: String,
+    source: Option<String>,
+    interval: Duration,
+    isupport: ISupport,
+    last: HashMap<IrcString, Instant>,
+}
+
+impl Default for Responder {
+    fn default() -> Self {
+        Responder::new()
+    }
+}
+
+impl Responder {
+    /// Creates a responder replying `yaircc <version>` to `VERSION`, once every 5 seconds per user.
+    pub fn new() -> Self {
+        Responder {
+            version: concat!("yaircc ", env!("CARGO_PKG_VERSION")).into(),
+            source: None,
+            interval: Duration::from_secs(5),
+            isupport: ISupport::new(),
+            last: HashMap::new(),
+        }
+    }
+
+    /// Sets the reply to `VERSION`.
+    pub fn version(mut self, version: impl Into<String>) -> Self {
+        self.version = version.into();
+        self
+    }
+
+    /// Sets the reply to `SOURCE`, which is not answered otherwise.
+    pub fn source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Sets the minimum interval between two replies to the same user.
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    fn clientinfo(&self) -> String {
+        let mut commands = vec!["ACTION", "CLIENTINFO", "PING", "TIME", "VERSION"];
+        if self.source.is_some() {
+            commands.insert(3, "SOURCE");
+        }
+        commands.join(" ")
+    }
+
+    /// Handles a message, returning the reply to send if any.
+    pub fn handle(&mut self, msg: &Message, now: Instant) -> Option<Message> {
+        if self.isupport.handle(msg) {
+            let mapping = self.isupport.casemapping();
+            self.last = self
+                .last
+                .drain()
+                .map(|(key, last)| (key.with_mapping(mapping), last))
+                .collect();
+            return None;
+        }
+        let nickname = match msg.prefix {
+            Some(Prefix::User(ref user)) => user.nickname.as_str(),
+            _ => return None,
+        };
+        let ctcp = match Ctcp::from_message(msg) {
+            Some((CtcpKind::Request, _, ctcp)) => ctcp,
+            _ => return None,
+        };
+        let reply = match ctcp.command.as_str() {
+            "VERSION" => Ctcp::new("VERSION", Some(&self.version)),
+            "PING" => Ctcp::new("PING", ctcp.params.as_deref()),
+            "TIME" => Ctcp::new("TIME", Some(&format_time(SystemTime::now()))),
+            "CLIENTINFO" => Ctcp::new("CLIENTINFO", Some(&self.clientinfo())),
+            "SOURCE" => Ctcp::new("SOURCE", Some(self.source.as_ref()?)),
+            _ => return None,
+        };
+
+        let interval = self.interval;
+        self.last
+            .retain(|_, last| now.saturating_duration_since(*last) < interval);
+        let key = IrcString::new(nickname, self.isupport.casemapping());
+        if self.last.contains_key(&key) {
+            return None;
+        }
+        self.last.insert(key, now);
+        Some(reply.reply(nickname))
+    }
+
+    /// Handles a message, writing the reply through the writer.
+    ///
+    /// Returns if a reply was written.
+    pub async fn handle_with<S>(
+        &mut self,
+        msg: &Message,
+        writer: &Writer<S>,
+    ) -> Result<bool, WriteError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        match self.handle(msg, Instant::now()) {
+            Some(reply) => writer.send(&reply).await.map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Formats the time like `Sun, 18 Oct 2026 12:34:56 +0000`.
+fn format_time(time: SystemTime) -> String {
+    const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (days, secs) = (secs / 86400, secs % 86400);
+    // Civil date from the days since the epoch, by Howard Hinnant.
+    let z = days + 719_468;
+    let (era, doe) = (z / 146_097, z % 146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    format!(
+        "{}, {:02} {} {} {:02}:{:02}:{:02} +0000",
+        DAYS[(days % 7) as usize],
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[test]
+fn test_parse() {
+    let msg = Message::parse(":alice!a@host PRIVMSG #chan :\x01ACTION waves\x01").unwrap();
+    assert_eq!(
+        Ctcp::from_message(&msg),
+        Some((CtcpKind::Request, "#chan", Ctcp::action("waves")))
+    );
+    let msg = Message::parse(":alice!a@host NOTICE bot :\x01version yaircc").unwrap();
+    assert_eq!(
+        Ctcp::from_message(&msg),
+        Some((CtcpKind::Reply, "bot", Ctcp::new("VERSION", Some("yaircc"))))
+    );
+    assert_eq!(Ctcp::parse("\x01PING\x01"), Some(Ctcp::new("PING", None)));
+    assert_eq!(Ctcp::parse("hello"), None);
+    assert_eq!(Ctcp::parse("\x01\x01"), None);
+}
+
+#[test]
+fn test_encode() {
+    assert_eq!(
+        Ctcp::action("waves").request("#chan").to_string(),
+        "PRIVMSG #chan :\x01ACTION waves\x01"
+    );
+    assert_eq!(
+        Ctcp::new("ping", Some("123")).reply("alice").to_string(),
+        "NOTICE alice :\x01PING 123\x01"
+    );
+    assert_eq!(format_time(UNIX_EPOCH), "Thu, 01 Jan 1970 00:00:00 +0000");
+    assert_eq!(
+        format_time(UNIX_EPOCH + Duration::from_secs(1_792_326_896)),
+        "Sun, 18 Oct 2026 12:34:56 +0000"
+    );
+}
+
+#[test]
+fn test_responder() {
+    let mut responder = Responder::new()
+        .version("bot 1.0")
+        .source("https://example.com");
+    let now = Instant::now();
+    let request = |line: &str| Message::parse(line).unwrap();
+
+    let reply = responder.handle(&request(":alice!a@host PRIVMSG bot :\x01VERSION\x01"), now);
+    assert_eq!(
+        reply.unwrap().to_string(),
+        "NOTICE alice :\x01VERSION bot 1.0\x01"
+    );
+    // Rate limited, whatever the case of the nickname.
+    let reply = responder.handle(&request(":ALICE!a@host PRIVMSG bot :\x01PING 1\x01"), now);
+    assert!(reply.is_none());
+    let reply = responder.handle(&request(":alice^!a@host PRIVMSG bot :\x01PING 1\x01"), now);
+    assert_eq!(reply.unwrap().to_string(), "NOTICE alice^ :\x01PING 1\x01");
+    // With the casemapping of the server.
+    let isupport = request(":irc.example.com 005 bot CASEMAPPING=ascii :are supported");
+    assert!(responder.handle(&isupport, now).is_none());
+    let reply = responder.handle(&request(":alice~!a@host PRIVMSG bot :\x01PING 1\x01"), now);
+    assert_eq!(reply.unwrap().to_string(), "NOTICE alice~ :\x01PING 1\x01");
+    let reply = responder.handle(&request(":Alice^!a@host PRIVMSG bot :\x01PING 1\x01"), now);
+    assert!(reply.is_none());
+    let reply = responder.handle(
+        &request(":alice!a@host PRIVMSG bot :\x01PING 1\x01"),
+        now + Duration::from_secs(5),
+    );
+    assert_eq!(reply.unwrap().to_string(), "NOTICE alice :\x01PING 1\x01");
+
+    let reply = responder.handle(
+        &request(":bob!b@host PRIVMSG #chan :\x01CLIENTINFO\x01"),
+        now,
+    );
+    assert_eq!(
+        reply.unwrap().to_string(),
+        "NOTICE bob :\x01CLIENTINFO ACTION CLIENTINFO PING SOURCE TIME VERSION\x01"
+    );
+    for line in &[
+        ":carol!c@host PRIVMSG bot :\x01ACTION waves\x01",
+        ":carol!c@host NOTICE bot :\x01VERSION other\x01",
+        ":carol!c@host PRIVMSG bot :hello",
+        ":irc.example.com PRIVMSG bot :\x01VERSION\x01",
+    ] {
+        assert!(responder.handle(&request(line), now).is_none());
+    }
+}
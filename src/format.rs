@@ -0,0 +1,526 @@
+//! mIRC formatting codes: bold, colors and the like.
+
+use std::{borrow::Cow, fmt::Write};
+
+const BOLD: char = '\x02';
+const COLOR: char = '\x03';
+const HEX_COLOR: char = '\x04';
+const RESET: char = '\x0f';
+const MONOSPACE: char = '\x11';
+const REVERSE: char = '\x16';
+const ITALIC: char = '\x1d';
+const STRIKETHROUGH: char = '\x1e';
+const UNDERLINE: char = '\x1f';
+
+/// Colors 0 to 98, as RGB.
+const PALETTE: [u32; 99] = [
+    0xffffff, 0x000000, 0x00007f, 0x009300, 0xff0000, 0x7f0000, 0x9c009c, 0xfc7f00, 0xffff00,
+    0x00fc00, 0x009393, 0x00ffff, 0x0000fc, 0xff00ff, 0x7f7f7f, 0xd2d2d2, 0x470000, 0x472100,
+    0x474700, 0x324700, 0x004700, 0x00472c, 0x004747, 0x002747, 0x000047, 0x2e0047, 0x470047,
+    0x47002a, 0x740000, 0x743a00, 0x747400, 0x517400, 0x007400, 0x007449, 0x007474, 0x004074,
+    0x000074, 0x4b0074, 0x740074, 0x740045, 0xb50000, 0xb56300, 0xb5b500, 0x7db500, 0x00b500,
+    0x00b571, 0x00b5b5, 0x0063b5, 0x0000b5, 0x7500b5, 0xb500b5, 0xb5006b, 0xff0000, 0xff8c00,
+    0xffff00, 0xb2ff00, 0x00ff00, 0x00ffa0, 0x00ffff, 0x008cff, 0x0000ff, 0xa500ff, 0xff00ff,
+    0xff0098, 0xff5959, 0xffb459, 0xffff71, 0xcfff60, 0x6fff6f, 0x65ffc9, 0x6dffff, 0x59b4ff,
+    0x5959ff, 0xc459ff, 0xff66ff, 0xff59bc, 0xff9c9c, 0xffd39c, 0xffff9c, 0xe2ff9c, 0x9cff9c,
+    0x9cffdb, 0x9cffff, 0x9cd3ff, 0x9c9cff, 0xdc9cff, 0xff9cff, 0xff94d3, 0x000000, 0x131313,
+    0x282828, 0x363636, 0x4d4d4d, 0x656565, 0x818181, 0x9f9f9f, 0xbcbcbc, 0xe2e2e2, 0xffffff,
+];
+
+/// Colors 0 to 15 in the 256-color palette of terminals.
+const ANSI_PALETTE: [u8; 16] = [15, 0, 4, 2, 9, 1, 5, 3, 11, 10, 6, 14, 12, 13, 8, 7];
+
+/// Color of the text or its background.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Color {
+    /// Color from 0 to 98, like `4` for red.
+    Palette(u8),
+    /// Hex color, sent with `\x04`.
+    Rgb(u8, u8, u8),
+}
+
+impl Color {
+    /// Red, green and blue components, looked up in the palette if needed.
+    pub fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Palette(n) => {
+                let rgb = PALETTE[usize::from(n).min(PALETTE.len() - 1)];
+                ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    fn to_rgb(self) -> Self {
+        let (r, g, b) = self.rgb();
+        Color::Rgb(r, g, b)
+    }
+
+    /// Closest color of the palette.
+    fn to_palette(self) -> Self {
+        let (r, g, b) = self.rgb();
+        let distance = |n: &u8| {
+            let (pr, pg, pb) = Color::Palette(*n).rgb();
+            [(r, pr), (g, pg), (b, pb)]
+                .iter()
+                .map(|&(a, b)| (i32::from(a) - i32::from(b)).pow(2))
+                .sum::<i32>()
+        };
+        match self {
+            Color::Palette(_) => self,
+            Color::Rgb(..) => {
+                Color::Palette((0..PALETTE.len() as u8).min_by_key(distance).unwrap())
+            }
+        }
+    }
+}
+
+/// Style of a span of text.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Style {
+    /// Bold, toggled by `\x02`.
+    pub bold: bool,
+    /// Italic, toggled by `\x1d`.
+    pub italic: bool,
+    /// Underline, toggled by `\x1f`.
+    pub underline: bool,
+    /// Strikethrough, toggled by `\x1e`.
+    pub strikethrough: bool,
+    /// Monospace, toggled by `\x11`.
+    pub monospace: bool,
+    /// Reverse colors, toggled by `\x16`.
+    pub reverse: bool,
+    /// Color of the text, if not the default one.
+    pub fg: Option<Color>,
+    /// Color of the background, if not the default one.
+    pub bg: Option<Color>,
+}
+
+impl Style {
+    /// Checks if the style has no attribute nor color.
+    pub fn is_plain(&self) -> bool {
+        *self == Style::default()
+    }
+
+    /// Checks if any attribute or color of `self` is unset in `next`.
+    fn loses(&self, next: &Style) -> bool {
+        self.flags()
+            .iter()
+            .zip(next.flags().iter())
+            .any(|(a, b)| *a && !*b)
+            || self.fg.is_some() && next.fg.is_none()
+            || self.bg.is_some() && next.bg.is_none()
+    }
+
+    fn flags(&self) -> [bool; 6] {
+        [
+            self.bold,
+            self.italic,
+            self.underline,
+            self.strikethrough,
+            self.monospace,
+            self.reverse,
+        ]
+    }
+}
+
+/// Text with a single style.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Span<'a> {
+    /// Style of the text.
+    pub style: Style,
+    /// Text, without formatting codes.
+    pub text: Cow<'a, str>,
+}
+
+impl<'a> Span<'a> {
+    /// Creates a span with the given style.
+    pub fn new(style: Style, text: impl Into<Cow<'a, str>>) -> Self {
+        Span {
+            style,
+            text: text.into(),
+        }
+    }
+
+    /// Creates a span without any style.
+    pub fn plain(text: impl Into<Cow<'a, str>>) -> Self {
+        Span::new(Style::default(), text)
+    }
+}
+
+/// Splits formatted text into styled spans.
+pub fn parse(text: &str) -> Vec<Span<'_>> {
+    let mut spans = Vec::new();
+    let mut style = Style::default();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let mut next = style;
+        match c {
+            BOLD => next.bold = !next.bold,
+            ITALIC => next.italic = !next.italic,
+            UNDERLINE => next.underline = !next.underline,
+            STRIKETHROUGH => next.strikethrough = !next.strikethrough,
+            MONOSPACE => next.monospace = !next.monospace,
+            REVERSE => next.reverse = !next.reverse,
+            RESET => next = Style::default(),
+            COLOR | HEX_COLOR => {}
+            _ => continue,
+        }
+        push(&mut spans, style, &text[start..i]);
+
+        let mut end = i + c.len_utf8();
+        if c == COLOR || c == HEX_COLOR {
+            let parse_color = if c == COLOR { palette } else { hex };
+            match parse_color(&text[end..]) {
+                Some((fg, len)) => {
+                    next.fg = fg;
+                    end += len;
+                    if let Some((bg, len)) = text[end..].strip_prefix(',').and_then(parse_color) {
+                        next.bg = bg;
+                        end += 1 + len;
+                    }
+                }
+                None => {
+                    next.fg = None;
+                    next.bg = None;
+                }
+            }
+            while chars.peek().is_some_and(|(j, _)| *j < end) {
+                chars.next();
+            }
+        }
+        style = next;
+        start = end;
+    }
+    push(&mut spans, style, &text[start..]);
+    spans
+}
+
+fn push<'a>(spans: &mut Vec<Span<'a>>, style: Style, text: &'a str) {
+    if text.is_empty() {
+        return;
+    }
+    match spans.last_mut() {
+        // The codes in between changed nothing, like `\x02\x02`.
+        Some(last) if last.style == style => last.text.to_mut().push_str(text),
+        _ => spans.push(Span::new(style, text)),
+    }
+}
+
+/// Parses a color number of one or two digits. 99 is the default color.
+fn palette(s: &str) -> Option<(Option<Color>, usize)> {
+    let len = s.bytes().take(2).take_while(u8::is_ascii_digit).count();
+    let n = s[..len].parse::<u8>().ok()?;
+    Some((Some(Color::Palette(n)).filter(|_| n != 99), len))
+}
+
+/// Parses a hex color like `FF0000`.
+fn hex(s: &str) -> Option<(Option<Color>, usize)> {
+    let rgb = s
+        .get(..6)
+        .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))?;
+    let rgb = u32::from_str_radix(rgb, 16).ok()?;
+    Some((
+        Some(Color::Rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)),
+        6,
+    ))
+}
+
+/// Removes the formatting codes.
+pub fn strip(text: &str) -> String {
+    parse(text).into_iter().map(|span| span.text).collect()
+}
+
+/// Renders formatted text with ANSI terminal escapes.
+pub fn to_ansi(text: &str) -> String {
+    let mut out = String::new();
+    let mut styled = false;
+    for span in parse(text) {
+        if styled {
+            out.push_str("\x1b[0m");
+        }
+        styled = !span.style.is_plain();
+        if styled {
+            out.push_str(&ansi_codes(&span.style));
+        }
+        out.push_str(&span.text);
+    }
+    if styled {
+        out.push_str("\x1b[0m");
+    }
+    out
+}
+
+fn ansi_codes(style: &Style) -> String {
+    let mut codes = Vec::new();
+    let flags = [
+        (style.bold, "1"),
+        (style.italic, "3"),
+        (style.underline, "4"),
+        (style.reverse, "7"),
+        (style.strikethrough, "9"),
+    ];
+    for (set, code) in &flags {
+        if *set {
+            codes.push(code.to_string());
+        }
+    }
+    for (color, base) in &[(style.fg, 38), (style.bg, 48)] {
+        match color {
+            Some(Color::Palette(n)) if usize::from(*n) < ANSI_PALETTE.len() => {
+                codes.push(format!("{};5;{}", base, ANSI_PALETTE[usize::from(*n)]))
+            }
+            Some(color) => {
+                let (r, g, b) = color.rgb();
+                codes.push(format!("{};2;{};{};{}", base, r, g, b));
+            }
+            None => {}
+        }
+    }
+    format!("\x1b[{}m", codes.join(";"))
+}
+
+/// Renders formatted text as HTML, with inline styles.
+pub fn to_html(text: &str) -> String {
+    let mut out = String::new();
+    for span in parse(text) {
+        let css = css(&span.style);
+        if !css.is_empty() {
+            write!(out, "<span style=\"{}\">", css).unwrap();
+        }
+        for c in span.text.chars() {
+            match c {
+                '&' => out.push_str("&amp;"),
+                '<' => out.push_str("&lt;"),
+                '>' => out.push_str("&gt;"),
+                '"' => out.push_str("&quot;"),
+                '\'' => out.push_str("&#39;"),
+                _ => out.push(c),
+            }
+        }
+        if !css.is_empty() {
+            out.push_str("</span>");
+        }
+    }
+    out
+}
+
+fn css(style: &Style) -> String {
+    let mut css = Vec::new();
+    if style.bold {
+        css.push("font-weight:bold".to_string());
+    }
+    if style.italic {
+        css.push("font-style:italic".into());
+    }
+    match (style.underline, style.strikethrough) {
+        (true, true) => css.push("text-decoration:underline line-through".into()),
+        (true, false) => css.push("text-decoration:underline".into()),
+        (false, true) => css.push("text-decoration:line-through".into()),
+        (false, false) => {}
+    }
+    if style.monospace {
+        css.push("font-family:monospace".into());
+    }
+    let (fg, bg) = if style.reverse {
+        (style.bg, style.fg)
+    } else {
+        (style.fg, style.bg)
+    };
+    for (property, color) in &[("color", fg), ("background-color", bg)] {
+        if let Some(color) = color {
+            let (r, g, b) = color.rgb();
+            css.push(format!("{}:#{:02x}{:02x}{:02x}", property, r, g, b));
+        }
+    }
+    css.join(";")
+}
+
+/// Builds formatted text from spans, e.g. for an outgoing message.
+pub fn build(spans: &[Span]) -> String {
+    let mut out = String::new();
+    let mut current = Style::default();
+    for span in spans.iter().filter(|span| !span.text.is_empty()) {
+        let style = span.style;
+        if current.loses(&style) {
+            out.push(RESET);
+            current = Style::default();
+        }
+        let toggles = [BOLD, ITALIC, UNDERLINE, STRIKETHROUGH, MONOSPACE, REVERSE];
+        for ((from, to), code) in current
+            .flags()
+            .iter()
+            .zip(style.flags().iter())
+            .zip(&toggles)
+        {
+            if from != to {
+                out.push(*code);
+            }
+        }
+        if (style.fg, style.bg) != (current.fg, current.bg) {
+            push_colors(&mut out, style.fg, style.bg, &span.text);
+        }
+        current = style;
+        out.push_str(&span.text);
+    }
+    out
+}
+
+fn push_colors(out: &mut String, fg: Option<Color>, bg: Option<Color>, text: &str) {
+    // Colors of different kinds cannot be sent together, so fall back to hex.
+    // A hex background needs a hex color of text, so without one fall back
+    // to the closest color of the palette instead.
+    let (fg, bg) = match (fg, bg) {
+        (Some(Color::Palette(_)), Some(Color::Rgb(..)))
+        | (Some(Color::Rgb(..)), Some(Color::Palette(_))) => {
+            (fg.map(Color::to_rgb), bg.map(Color::to_rgb))
+        }
+        (None, Some(bg)) => (Some(Color::Palette(99)), Some(bg.to_palette())),
+        _ => (fg, bg),
+    };
+    for (i, color) in [fg, bg].iter().enumerate() {
+        match color {
+            // Always two digits, so that text starting with a digit is not taken as the color.
+            Some(Color::Palette(n)) if i == 0 => write!(out, "{}{:02}", COLOR, n).unwrap(),
+            Some(Color::Rgb(r, g, b)) if i == 0 => {
+                write!(out, "{}{:02X}{:02X}{:02X}", HEX_COLOR, r, g, b).unwrap()
+            }
+            Some(Color::Palette(n)) => write!(out, ",{:02}", n).unwrap(),
+            Some(Color::Rgb(r, g, b)) => write!(out, ",{:02X}{:02X}{:02X}", r, g, b).unwrap(),
+            None => {}
+        }
+    }
+    // Without a background, text like `,12` would be taken as one.
+    let parse_color = match fg {
+        Some(Color::Palette(_)) => palette,
+        _ => hex,
+    };
+    if fg.is_some() && bg.is_none() && text.strip_prefix(',').and_then(parse_color).is_some() {
+        out.push(BOLD);
+        out.push(BOLD);
+    }
+}
+
+#[test]
+fn test_parse() {
+    let spans = parse("a\x02b\x0304,12c\x03d\x1d\x1de\x0fxf");
+    let bold = Style {
+        bold: true,
+        ..Style::default()
+    };
+    let colored = Style {
+        fg: Some(Color::Palette(4)),
+        bg: Some(Color::Palette(12)),
+        ..bold
+    };
+    assert_eq!(
+        spans,
+        vec![
+            Span::plain("a"),
+            Span::new(bold, "b"),
+            Span::new(colored, "c"),
+            Span::new(bold, "de"),
+            Span::plain("xf"),
+        ]
+    );
+
+    let spans = parse("\x035,text \x0399,1bg \x04FF8000,000000hex\x04 \x031x");
+    assert_eq!(spans[0].text, ",text ");
+    assert_eq!(spans[0].style.fg, Some(Color::Palette(5)));
+    assert_eq!(spans[1].style.fg, None);
+    assert_eq!(spans[1].style.bg, Some(Color::Palette(1)));
+    assert_eq!(spans[2].style.fg, Some(Color::Rgb(0xff, 0x80, 0)));
+    assert_eq!(spans[2].style.bg, Some(Color::Rgb(0, 0, 0)));
+    assert_eq!(spans[3], Span::plain(" "));
+    assert_eq!(spans[4].text, "x");
+}
+
+#[test]
+fn test_strip() {
+    assert_eq!(
+        strip("\x02bold\x02 \x0312,01blue\x03 \x1funder\x0f"),
+        "bold blue under"
+    );
+    assert_eq!(strip("\x0312345"), "345");
+    assert_eq!(strip("\x04abcdefg\x16é"), "gé");
+    assert_eq!(strip("plain"), "plain");
+}
+
+#[test]
+fn test_render() {
+    assert_eq!(to_ansi("a\x02b\x0f"), "a\x1b[1mb\x1b[0m");
+    assert_eq!(to_ansi("\x0304,50x"), "\x1b[38;5;9;48;2;181;0;181mx\x1b[0m");
+    assert_eq!(
+        to_html("<\x02b\x1d&\x0f\x16\x0304x"),
+        "&lt;<span style=\"font-weight:bold\">b</span>\
+         <span style=\"font-weight:bold;font-style:italic\">&amp;</span>\
+         <span style=\"background-color:#ff0000\">x</span>"
+    );
+}
+
+#[test]
+fn test_build() {
+    let bold = Style {
+        bold: true,
+        ..Style::default()
+    };
+    let red = Style {
+        fg: Some(Color::Palette(4)),
+        ..bold
+    };
+    let spans = [
+        Span::plain("a"),
+        Span::new(bold, "b"),
+        Span::new(red, "1"),
+        Span::plain("c"),
+        Span::new(
+            Style {
+                fg: Some(Color::Palette(1)),
+                bg: Some(Color::Rgb(1, 2, 3)),
+                ..Style::default()
+            },
+            "d",
+        ),
+    ];
+    let text = build(&spans);
+    assert_eq!(text, "a\x02b\x03041\x0fc\x04000000,010203d");
+    assert_eq!(parse(&text)[..4], spans[..4]);
+
+    let color = |fg, bg, text| {
+        Span::new(
+            Style {
+                fg,
+                bg,
+                ..Style::default()
+            },
+            text,
+        )
+    };
+    let spans = [
+        color(Some(Color::Palette(4)), Some(Color::Palette(12)), "a"),
+        color(None, Some(Color::Palette(2)), "1"),
+        color(Some(Color::Rgb(1, 2, 3)), None, "b"),
+        color(Some(Color::Rgb(1, 2, 3)), Some(Color::Rgb(4, 5, 6)), "2"),
+        Span::new(bold, ","),
+    ];
+    assert_eq!(parse(&build(&spans)), spans);
+
+    // A hex background alone falls back to the palette.
+    let text = build(&[color(None, Some(Color::Rgb(0xfe, 0, 0)), "3")]);
+    assert_eq!(text, "\x0399,043");
+    assert_eq!(
+        parse(&text),
+        vec![color(None, Some(Color::Palette(4)), "3")]
+    );
+
+    // Text looking like a background is guarded.
+    let spans = [
+        color(Some(Color::Palette(4)), None, ",12"),
+        color(Some(Color::Rgb(1, 2, 3)), None, ",ABCDEF"),
+        color(Some(Color::Palette(5)), None, ",x"),
+    ];
+    let text = build(&spans);
+    assert_eq!(text, "\x0304\x02\x02,12\x04010203\x02\x02,ABCDEF\x0305,x");
+    assert_eq!(parse(&text), spans);
+}
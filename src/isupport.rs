@@ -0,0 +1,256 @@
+//! Server features advertised with `RPL_ISUPPORT` (005).
+
+use {
+    crate::{casemap::CaseMapping, code::Code, message::Message},
+    std::collections::BTreeMap,
+};
+
+/// Default `PREFIX`, as in `(ov)@+`.
+const DEFAULT_PREFIX: &[(char, char)] = &[('o', '@'), ('v', '+')];
+/// Default `CHANMODES`.
+const DEFAULT_CHANMODES: [&str; 4] = ["beI", "k", "l", "imnpst"];
+
+/// Server features, accumulated from the `RPL_ISUPPORT` replies.
+///
+/// Getters return the RFC defaults for the parameters not advertised.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct ISupport {
+    params: BTreeMap<String, String>,
+}
+
+impl ISupport {
+    /// Creates an empty feature set, with the defaults for every parameter.
+    pub fn new() -> Self {
+        ISupport::default()
+    }
+
+    /// Handles a message from the server, returning if it was `RPL_ISUPPORT`.
+    pub fn handle(&mut self, msg: &Message) -> bool {
+        if msg.code != Code::RplIsupport || msg.args.len() < 2 {
+            return false;
+        }
+        // <client> <tokens>... :are supported by this server
+        for token in &msg.args[1..msg.args.len() - 1] {
+            self.insert_token(token);
+        }
+        true
+    }
+
+    /// Applies a single token, like `NICKLEN=30` or `-EXCEPTS`.
+    pub fn insert_token(&mut self, token: &str) {
+        if let Some(name) = token.strip_prefix('-') {
+            self.params.remove(name);
+            return;
+        }
+        let (name, value) = match token.find('=') {
+            Some(idx) => (&token[..idx], &token[idx + 1..]),
+            None => (token, ""),
+        };
+        if !name.is_empty() {
+            self.params.insert(name.into(), unescape(value));
+        }
+    }
+
+    /// Checks if the parameter was advertised.
+    pub fn contains(&self, name: &str) -> bool {
+        self.params.contains_key(name)
+    }
+
+    /// Returns the value of the parameter. Parameters without a value have an empty one.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.params.get(name).map(String::as_str)
+    }
+
+    /// Iterates over the parameters and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.params.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    fn number(&self, name: &str) -> Option<usize> {
+        self.get(name).and_then(|value| value.parse().ok())
+    }
+
+    /// `CASEMAPPING`, defaults to `rfc1459`, also used for unknown casemappings.
+    pub fn casemapping(&self) -> CaseMapping {
+        self.get("CASEMAPPING")
+            .and_then(CaseMapping::from_name)
+            .unwrap_or_default()
+    }
+
+    /// `CHANTYPES`, defaults to `#&`.
+    pub fn chantypes(&self) -> &str {
+        self.get("CHANTYPES").unwrap_or("#&")
+    }
+
+    /// Checks if the target is a channel, according to `CHANTYPES`.
+    pub fn is_channel(&self, target: &str) -> bool {
+        target
+            .chars()
+            .next()
+            .is_some_and(|c| self.chantypes().contains(c))
+    }
+
+    /// `PREFIX` as pairs of mode and symbol from the highest, defaults to `(ov)@+`.
+    pub fn prefix(&self) -> Vec<(char, char)> {
+        let value = match self.get("PREFIX") {
+            Some(value) => value,
+            None => return DEFAULT_PREFIX.to_vec(),
+        };
+        match value
+            .strip_prefix('(')
+            .and_then(|value| value.split_once(')'))
+        {
+            Some((modes, symbols)) => modes.chars().zip(symbols.chars()).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// `CHANMODES` as the list, always-parameter, set-parameter and no-parameter modes.
+    ///
+    /// Defaults to `beI,k,l,imnpst`.
+    pub fn chanmodes(&self) -> [&str; 4] {
+        let value = match self.get("CHANMODES") {
+            Some(value) => value,
+            None => return DEFAULT_CHANMODES,
+        };
+        let mut kinds = value.split(',');
+        let mut chanmodes = [""; 4];
+        for kind in &mut chanmodes {
+            *kind = kinds.next().unwrap_or("");
+        }
+        chanmodes
+    }
+
+    /// `NICKLEN`, defaults to 9.
+    pub fn nicklen(&self) -> usize {
+        self.number("NICKLEN").unwrap_or(9)
+    }
+
+    /// `CHANNELLEN`, defaults to 200.
+    pub fn channellen(&self) -> usize {
+        self.number("CHANNELLEN").unwrap_or(200)
+    }
+
+    /// `TOPICLEN`, if limited.
+    pub fn topiclen(&self) -> Option<usize> {
+        self.number("TOPICLEN")
+    }
+
+    /// `MODES`, the number of modes with a parameter per `MODE`.
+    ///
+    /// Defaults to 3. Returns `None` if unlimited.
+    pub fn modes(&self) -> Option<usize> {
+        match self.get("MODES") {
+            Some(value) => value.parse().ok(),
+            None => Some(3),
+        }
+    }
+
+    /// `LINELEN`, defaults to 512.
+    pub fn linelen(&self) -> usize {
+        self.number("LINELEN").unwrap_or(512)
+    }
+
+    /// `TARGMAX` for the command, e.g. `PRIVMSG`.
+    ///
+    /// Returns `None` if unlimited or unknown.
+    pub fn targmax(&self, command: &str) -> Option<usize> {
+        self.get("TARGMAX")?
+            .split(',')
+            .filter_map(|pair| pair.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case(command))
+            .and_then(|(_, max)| max.parse().ok())
+    }
+
+    /// `NETWORK`, the name of the network.
+    pub fn network(&self) -> Option<&str> {
+        self.get("NETWORK")
+    }
+
+    /// `STATUSMSG`, the prefix symbols usable to message part of a channel.
+    pub fn statusmsg(&self) -> &str {
+        self.get("STATUSMSG").unwrap_or("")
+    }
+}
+
+/// Unescapes `\xHH` sequences in a value.
+fn unescape(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'x') {
+            let hex = value
+                .get(i + 2..i + 4)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = hex {
+                out.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+#[test]
+fn test_parse() {
+    let mut isupport = ISupport::new();
+    let msg = Message::parse(
+        ":irc.example.com 005 bot CASEMAPPING=ascii CHANTYPES=# PREFIX=(qaohv)~&@%+ \
+         CHANMODES=beI,k,l,BCMNORScimnpstz EXCEPTS :are supported by this server",
+    )
+    .unwrap();
+    assert!(isupport.handle(&msg));
+    let msg = Message::parse(
+        ":irc.example.com 005 bot NETWORK=Example\\x20Net MODES TARGMAX=PRIVMSG:4,JOIN: \
+         :are supported by this server",
+    )
+    .unwrap();
+    assert!(isupport.handle(&msg));
+
+    assert_eq!(isupport.casemapping(), CaseMapping::Ascii);
+    assert!(isupport.is_channel("#chan"));
+    assert!(!isupport.is_channel("&chan"));
+    assert_eq!(
+        isupport.prefix(),
+        vec![('q', '~'), ('a', '&'), ('o', '@'), ('h', '%'), ('v', '+')]
+    );
+    assert_eq!(isupport.chanmodes(), ["beI", "k", "l", "BCMNORScimnpstz"]);
+    assert_eq!(isupport.get("EXCEPTS"), Some(""));
+    assert_eq!(isupport.network(), Some("Example Net"));
+    assert_eq!(isupport.modes(), None);
+    assert_eq!(isupport.targmax("privmsg"), Some(4));
+    assert_eq!(isupport.targmax("JOIN"), None);
+
+    let msg = Message::parse(":irc.example.com 005 bot -EXCEPTS -MODES :are supported").unwrap();
+    isupport.handle(&msg);
+    assert!(!isupport.contains("EXCEPTS"));
+    assert_eq!(isupport.modes(), Some(3));
+
+    let msg = Message::parse(":irc.example.com 005 bot :are supported by this server").unwrap();
+    assert!(isupport.handle(&msg));
+    assert!(!isupport.iter().any(|(name, _)| name.contains(' ')));
+
+    let msg = Message::parse(":irc.example.com 005 bot SAFELIST :supported").unwrap();
+    assert!(isupport.handle(&msg));
+    assert!(isupport.contains("SAFELIST"));
+    assert!(!isupport.contains("supported"));
+}
+
+#[test]
+fn test_defaults() {
+    let isupport = ISupport::new();
+    assert_eq!(isupport.casemapping(), CaseMapping::Rfc1459);
+    assert_eq!(isupport.prefix(), vec![('o', '@'), ('v', '+')]);
+    assert_eq!(isupport.chanmodes(), ["beI", "k", "l", "imnpst"]);
+    assert_eq!(isupport.nicklen(), 9);
+    assert_eq!(isupport.linelen(), 512);
+    assert_eq!(isupport.topiclen(), None);
+
+    let msg = Message::parse(":irc.example.com 001 bot :Welcome").unwrap();
+    assert!(!ISupport::new().handle(&msg));
+    assert_eq!(unescape("a\\x3Db\\x"), "a=b\\x");
+}
@@ -0,0 +1,184 @@
+//! Automatic `PING`/`PONG` handling and dead connection detection.
+
+use {
+    crate::{
+        code::Code,
+        command::Command,
+        message::Message,
+        stream::{IrcStream, StreamError, WriteError, Writer},
+    },
+    futures::{
+        prelude::*,
+        ready,
+        task::{Context, Poll},
+    },
+    futures_timer::Delay,
+    std::{collections::VecDeque, pin::Pin, time::Duration},
+};
+
+/// Token of the `PING`s we send.
+const PING_TOKEN: &str = "yaircc";
+
+type SendFuture = Pin<Box<dyn Future<Output = Result<(), WriteError>> + Send>>;
+
+/// Stream wrapping an `IrcStream`, keeping the connection alive.
+///
+/// `PING`s from the server are answered with `PONG` and still yielded. When
+/// nothing is received for `interval`, a `PING` is sent, and when nothing is
+/// received for `timeout` after that, the stream yields `StreamError::Timeout`
+/// and ends.
+pub struct Keepalive<S> {
+    stream: IrcStream<S>,
+    writer: Writer<S>,
+    interval: Duration,
+    timeout: Duration,
+    delay: Delay,
+    waiting: bool,
+    done: bool,
+    queue: VecDeque<Message>,
+    sending: Option<SendFuture>,
+}
+
+impl<S> Keepalive<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    /// Wraps the stream, sending a `PING` after `interval` of silence.
+    pub fn new(stream: IrcStream<S>, interval: Duration, timeout: Duration) -> Self {
+        Keepalive {
+            writer: stream.writer(),
+            stream,
+            interval,
+            timeout,
+            delay: Delay::new(interval),
+            waiting: false,
+            done: false,
+            queue: VecDeque::new(),
+            sending: None,
+        }
+    }
+
+    /// Writer of the wrapped stream.
+    pub fn writer(&self) -> Writer<S> {
+        self.writer.clone()
+    }
+
+    /// Unwraps the inner stream.
+    pub fn into_inner(self) -> IrcStream<S> {
+        self.stream
+    }
+
+    /// Writes the queued messages.
+    fn poll_send(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), WriteError>> {
+        loop {
+            if let Some(fut) = self.sending.as_mut() {
+                ready!(fut.as_mut().poll(cx))?;
+                self.sending = None;
+            }
+            match self.queue.pop_front() {
+                Some(msg) => {
+                    let writer = self.writer.clone();
+                    self.sending = Some(Box::pin(async move { writer.send(&msg).await }));
+                }
+                None => return Poll::Ready(Ok(())),
+            }
+        }
+    }
+}
+
+impl<S> Stream for Keepalive<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    type Item = Result<Message, StreamError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.done {
+            return Poll::Ready(None);
+        }
+        if let Poll::Ready(Err(e)) = this.poll_send(cx) {
+            this.done = true;
+            return Poll::Ready(Some(Err(e.into())));
+        }
+
+        match Pin::new(&mut this.stream).poll_next(cx) {
+            Poll::Ready(Some(msg)) => {
+                this.waiting = false;
+                this.delay.reset(this.interval);
+                if let Ok(ref msg) = msg {
+                    if msg.code == Code::Ping {
+                        this.queue.push_back(
+                            Command::Pong {
+                                server: msg.args.first().cloned().unwrap_or_default(),
+                                token: None,
+                            }
+                            .into(),
+                        );
+                        // Errors are reported on the next poll.
+                        let _ = this.poll_send(cx);
+                    }
+                }
+                return Poll::Ready(Some(msg));
+            }
+            Poll::Ready(None) => {
+                this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {}
+        }
+
+        while Pin::new(&mut this.delay).poll(cx).is_ready() {
+            if this.waiting {
+                this.done = true;
+                return Poll::Ready(Some(Err(StreamError::Timeout)));
+            }
+            this.waiting = true;
+            this.delay.reset(this.timeout);
+            this.queue.push_back(
+                Command::Ping {
+                    token: PING_TOKEN.into(),
+                    target: None,
+                }
+                .into(),
+            );
+            if let Poll::Ready(Err(e)) = this.poll_send(cx) {
+                this.done = true;
+                return Poll::Ready(Some(Err(e.into())));
+            }
+        }
+        Poll::Pending
+    }
+}
+
+#[test]
+fn test_pong() {
+    use {crate::mock::MockStream, futures::executor::block_on};
+
+    let (mock, output) = MockStream::new("PING :irc.example.com\r\n:a!b@c PRIVMSG #chan :hi\r\n");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let keepalive = Keepalive::new(stream, Duration::from_secs(60), Duration::from_secs(60));
+    let msgs = block_on(keepalive.try_collect::<Vec<_>>()).unwrap();
+    assert_eq!(msgs.len(), 2);
+    assert_eq!(msgs[0].code, Code::Ping);
+    assert_eq!(output.get(), "PONG irc.example.com\r\n");
+}
+
+#[test]
+fn test_timeout() {
+    use {crate::mock::MockStream, futures::executor::block_on};
+
+    let (mock, output) = MockStream::new("PING :irc.example.com\r\n");
+    let stream = IrcStream::new(mock.hang(), encoding::all::UTF_8);
+    let mut keepalive =
+        Keepalive::new(stream, Duration::from_millis(20), Duration::from_millis(20));
+    block_on(async {
+        assert!(keepalive.next().await.unwrap().is_ok());
+        match keepalive.next().await {
+            Some(Err(StreamError::Timeout)) => {}
+            other => panic!("expected a timeout, got {:?}", other),
+        }
+        assert!(keepalive.next().await.is_none());
+    });
+    assert_eq!(output.get(), "PONG irc.example.com\r\nPING yaircc\r\n");
+}
@@ -1,9 +1,53 @@
+mod batch;
+mod cap;
+mod casemap;
 mod code;
+mod command;
+pub mod ctcp;
+pub mod format;
+mod isupport;
+mod keepalive;
 mod message;
+#[cfg(test)]
+mod mock;
+mod mode;
+mod query;
+mod reconnect;
+mod register;
+pub mod reply;
+pub mod sasl;
+mod split;
+mod state;
 mod stream;
+pub mod tags;
+mod throttle;
 
 pub use {
-    code::Code,
-    message::{Message, ParseError, Prefix, PrefixUser},
-    stream::{IrcStream, StreamError, Writer},
+    batch::{Batch, BatchAssembler, BatchItem, Batched},
+    cap::{CapNegotiator, CapState},
+    casemap::{CaseMapping, IrcString},
+    code::{Category, Code, Dialect, Vendor},
+    command::{Command, CommandError},
+    ctcp::{Ctcp, CtcpKind},
+    format::{Color, Span, Style},
+    isupport::ISupport,
+    keepalive::Keepalive,
+    message::{
+        Message, MessageBuilder, MessageRef, ParseError, Prefix, PrefixRef, PrefixUser,
+        PrefixUserRef, TagsRef, ValidationError,
+    },
+    mode::{ModeChange, ModeKind, Sign},
+    query::{
+        BanEntry, Correlator, ListEntry, Query, QueryError, QueryId, WhoEntry, WhoisInfo,
+        DEFAULT_QUERY_TIMEOUT,
+    },
+    reconnect::{Backoff, DisconnectReason, Event, Reconnecting, DEFAULT_REGISTER_TIMEOUT},
+    register::{NickSuffix, RegisterError, Registered, Registration},
+    reply::ReplyError,
+    sasl::{Sasl, SaslError, SaslState},
+    split::{Splitter, MAX_LINE_LEN},
+    state::{Channel, State, Topic, User},
+    stream::{IrcStream, StreamError, WriteError, Writer},
+    tags::Tags,
+    throttle::{RateLimit, Throttle, ThrottledWriter, TokenBucket},
 };
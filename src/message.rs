@@ -2,8 +2,11 @@
 //! See COPYRIGHT for details.
 
 use {
-    crate::code::Code,
-    std::{fmt, str::FromStr},
+    crate::{
+        code::{Code, Dialect},
+        tags::{self, Tags},
+    },
+    std::{borrow::Cow, fmt, str, str::FromStr},
 };
 
 /// Error generated by the parser.
@@ -33,9 +36,65 @@ impl fmt::Display for ParseError {
 
 impl std::error::Error for ParseError {}
 
+/// Error generated when a message cannot be safely serialized.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ValidationError {
+    /// Code is empty or contains a forbidden character.
+    InvalidCode,
+    /// Tag key is empty or contains a forbidden character.
+    InvalidTagKey(String),
+    /// Tag value contains NUL.
+    InvalidTagValue(String),
+    /// Prefix is empty or contains a forbidden character.
+    InvalidPrefix,
+    /// Argument contains CR, LF or NUL.
+    ForbiddenCharacter {
+        /// Position of the argument
+        index: usize,
+        /// Forbidden character
+        character: char,
+    },
+    /// Argument other than the last one is empty, contains a space or starts with a colon.
+    InvalidMiddle(usize),
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ValidationError::InvalidCode => write!(f, "Invalid code"),
+            ValidationError::InvalidTagKey(ref key) => write!(f, "Invalid tag key {:?}", key),
+            ValidationError::InvalidTagValue(ref key) => {
+                write!(f, "Invalid value for tag {:?}", key)
+            }
+            ValidationError::InvalidPrefix => write!(f, "Invalid prefix"),
+            ValidationError::ForbiddenCharacter { index, character } => write!(
+                f,
+                "Argument {} contains forbidden character {:?}",
+                index, character
+            ),
+            ValidationError::InvalidMiddle(index) => write!(
+                f,
+                "Argument {} is empty, contains a space or starts with a colon",
+                index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Characters that would end the line early or confuse the server.
+const FORBIDDEN: &[char] = &['\r', '\n', '\0'];
+
+fn is_word(s: &str) -> bool {
+    !s.is_empty() && !s.contains(FORBIDDEN) && !s.contains(' ')
+}
+
 /// Represents a message received from the server.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Message {
+    /// IRCv3 message tags
+    pub tags: Tags,
     /// Prefix
     pub prefix: Option<Prefix>,
     /// Code
@@ -49,21 +108,271 @@ impl Message {
     ///
     /// An error is returned if the message is not valid.
     pub fn parse(line: &str) -> Result<Message, ParseError> {
+        MessageRef::parse(line).map(|msg| msg.to_owned())
+    }
+
+    /// Gives the numeric the meaning it has in the dialect, if it differs.
+    pub fn reinterpret(&mut self, dialect: &impl Dialect) {
+        if let Some(code) = dialect.reinterpret(&self.code) {
+            self.code = code;
+        }
+    }
+
+    /// Checks that the message serializes into exactly one well-formed line.
+    ///
+    /// Any CR, LF or NUL outside of tag values is rejected, so user-controlled
+    /// text cannot inject additional commands.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        for (key, value) in &self.tags {
+            let valid_key = key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || "-./+".contains(c));
+            if key.is_empty() || !valid_key {
+                return Err(ValidationError::InvalidTagKey(key.into()));
+            }
+            if value.contains('\0') {
+                return Err(ValidationError::InvalidTagValue(key.into()));
+            }
+        }
+
+        let valid_prefix = match self.prefix {
+            None => true,
+            Some(Prefix::Server(ref server)) => is_word(server),
+            Some(Prefix::User(ref user)) => {
+                is_word(&user.nickname) && is_word(&user.username) && is_word(&user.hostname)
+            }
+        };
+        if !valid_prefix {
+            return Err(ValidationError::InvalidPrefix);
+        }
+
+        let code = self.code.to_string();
+        if !is_word(&code) || code.starts_with(':') || code.starts_with('@') {
+            return Err(ValidationError::InvalidCode);
+        }
+
+        for (index, arg) in self.args.iter().enumerate() {
+            if let Some(character) = arg.chars().find(|c| FORBIDDEN.contains(c)) {
+                return Err(ValidationError::ForbiddenCharacter { index, character });
+            }
+            let is_last = index + 1 == self.args.len();
+            if !is_last && (arg.is_empty() || arg.contains(' ') || arg.starts_with(':')) {
+                return Err(ValidationError::InvalidMiddle(index));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts building a message with the given code.
+    ///
+    /// ```
+    /// # use yaircc::{Code, Message};
+    /// let msg = Message::build(Code::Privmsg).arg("#chan").trailing("hello world");
+    /// assert_eq!(msg.to_string(), "PRIVMSG #chan :hello world");
+    /// ```
+    pub fn build(code: Code) -> MessageBuilder {
+        MessageBuilder {
+            msg: Message {
+                tags: Tags::new(),
+                prefix: None,
+                code,
+                args: Vec::new(),
+            },
+        }
+    }
+}
+
+/// Builder for `Message`. See `Message::build`.
+#[derive(Clone, Debug)]
+pub struct MessageBuilder {
+    msg: Message,
+}
+
+impl MessageBuilder {
+    /// Sets a tag.
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.msg.tags.insert(key, value);
+        self
+    }
+
+    /// Sets the prefix.
+    pub fn prefix(mut self, prefix: Prefix) -> Self {
+        self.msg.prefix = Some(prefix);
+        self
+    }
+
+    /// Appends an argument.
+    pub fn arg(mut self, arg: impl Into<String>) -> Self {
+        self.msg.args.push(arg.into());
+        self
+    }
+
+    /// Appends the trailing argument and finishes the message.
+    pub fn trailing(self, arg: impl Into<String>) -> Message {
+        self.arg(arg).finish()
+    }
+
+    /// Finishes the message.
+    pub fn finish(self) -> Message {
+        self.msg
+    }
+}
+
+impl From<MessageBuilder> for Message {
+    fn from(builder: MessageBuilder) -> Self {
+        builder.finish()
+    }
+}
+
+impl FromStr for Message {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Message::parse(s)
+    }
+}
+
+impl fmt::Display for Message {
+    /// Serializes the message into a line, without the trailing CRLF.
+    ///
+    /// The last argument is written as a trailing argument only when it has
+    /// to be, i.e. when it is empty, contains a space or starts with a colon.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.tags.is_empty() {
+            write!(f, "@{} ", self.tags)?;
+        }
+        if let Some(ref prefix) = self.prefix {
+            write!(f, ":{} ", prefix)?;
+        }
+        write!(f, "{}", self.code)?;
+        if let Some((last, middle)) = self.args.split_last() {
+            for arg in middle {
+                write!(f, " {}", arg)?;
+            }
+            if last.is_empty() || last.contains(' ') || last.starts_with(':') {
+                write!(f, " :{}", last)?;
+            } else {
+                write!(f, " {}", last)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_prefix(prefix: &str) -> Option<PrefixRef<'_>> {
+    match prefix.find('!') {
+        None => Some(PrefixRef::Server(prefix)),
+        Some(excpos) => {
+            let nick = &prefix[..excpos];
+            let rest = &prefix[excpos + 1..];
+            match rest.find('@') {
+                None => None,
+                Some(atpos) => {
+                    let user = &rest[..atpos];
+                    let host = &rest[atpos + 1..];
+                    Some(PrefixRef::User(PrefixUserRef {
+                        nickname: nick,
+                        username: user,
+                        hostname: host,
+                    }))
+                }
+            }
+        }
+    }
+}
+
+/// Prefix of the message.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Prefix {
+    /// Prefix is a user.
+    User(PrefixUser),
+    /// Prefix is a server.
+    Server(String),
+}
+
+impl fmt::Display for Prefix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Prefix::User(ref user) => write!(f, "{}", user),
+            Prefix::Server(ref server) => write!(f, "{}", server),
+        }
+    }
+}
+
+/// User prefix representation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PrefixUser {
+    /// Nickname
+    pub nickname: String,
+    /// Username
+    pub username: String,
+    /// Hostname
+    pub hostname: String,
+}
+
+impl PrefixUser {
+    fn new(nick: &str, user: &str, host: &str) -> PrefixUser {
+        PrefixUser {
+            nickname: nick.into(),
+            username: user.into(),
+            hostname: host.into(),
+        }
+    }
+}
+
+impl fmt::Display for PrefixUser {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}!{}@{}", self.nickname, self.username, self.hostname)
+    }
+}
+
+/// Message borrowing from the line it was parsed from.
+///
+/// Tags and prefix are kept raw and only parsed when asked for, so parsing a
+/// `MessageRef` allocates nothing but the list of argument slices.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MessageRef<'a> {
+    raw_tags: Option<&'a str>,
+    raw_prefix: Option<&'a str>,
+    /// Command, reply or error, as written on the line
+    pub command: &'a str,
+    /// Arguments
+    pub args: Vec<&'a str>,
+}
+
+impl<'a> MessageRef<'a> {
+    /// Parse the given string into a `MessageRef` struct.
+    ///
+    /// An error is returned if the message is not valid.
+    pub fn parse(line: &'a str) -> Result<MessageRef<'a>, ParseError> {
         if line.is_empty() || line.trim().is_empty() {
             return Err(ParseError::EmptyMessage);
         }
 
         let mut state = line.trim_end_matches("\r\n");
-        let mut prefix: Option<Prefix> = None;
+        let mut raw_tags: Option<&str> = None;
+        let mut raw_prefix: Option<&str> = None;
         let code: Option<&str>;
-        let mut args: Vec<String> = Vec::new();
+        let mut args: Vec<&str> = Vec::new();
+
+        // Look for tags
+        if state.starts_with('@') {
+            match state.find(' ') {
+                None => return Err(ParseError::UnexpectedEnd),
+                Some(idx) => {
+                    raw_tags = Some(&state[1..idx]);
+                    state = &state[idx + 1..];
+                }
+            }
+        }
 
         // Look for a prefix
         if state.starts_with(':') {
             match state.find(' ') {
                 None => return Err(ParseError::UnexpectedEnd),
                 Some(idx) => {
-                    prefix = parse_prefix(&state[1..idx]);
+                    raw_prefix = Some(&state[1..idx]);
                     state = &state[idx + 1..];
                 }
             }
@@ -75,12 +384,12 @@ impl Message {
                 if state.is_empty() {
                     return Err(ParseError::EmptyMessage);
                 } else {
-                    code = Some(&state[..]);
+                    code = Some(state);
                     state = &state[state.len()..];
                 }
             }
             Some(idx) => {
-                code = Some(state[..idx].into());
+                code = Some(&state[..idx]);
                 state = &state[idx + 1..];
             }
         }
@@ -88,17 +397,17 @@ impl Message {
         // Look for arguments and the suffix
         if !state.is_empty() {
             loop {
-                if state.starts_with(':') {
-                    args.push(state[1..].into());
+                if let Some(trailing) = state.strip_prefix(':') {
+                    args.push(trailing);
                     break;
                 } else {
                     match state.find(' ') {
                         None => {
-                            args.push(state[..].into());
+                            args.push(state);
                             break;
                         }
                         Some(idx) => {
-                            args.push(state[..idx].into());
+                            args.push(&state[..idx]);
                             state = &state[idx + 1..];
                         }
                     }
@@ -106,71 +415,121 @@ impl Message {
             }
         }
 
-        let code = match code {
+        let command = match code {
             None => return Err(ParseError::EmptyCommand),
-            Some(text) => match text.parse() {
-                Ok(code) => code,
-                Err(_) => Code::Unknown(text.into()),
-            },
+            Some(text) => text,
         };
 
-        Ok(Message { prefix, code, args })
+        Ok(MessageRef {
+            raw_tags,
+            raw_prefix,
+            command,
+            args,
+        })
     }
-}
 
-impl FromStr for Message {
-    type Err = ParseError;
+    /// Iterates over the tags, unescaping values as needed.
+    pub fn tags(&self) -> TagsRef<'a> {
+        TagsRef(self.raw_tags.unwrap_or("").split(';'))
+    }
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Message::parse(s)
+    /// Returns the value of the tag, if present.
+    pub fn tag(&self, key: &str) -> Option<Cow<'a, str>> {
+        self.tags()
+            .filter(|(k, _)| *k == key)
+            .last()
+            .map(|(_, v)| v)
+    }
+
+    /// Parses the prefix.
+    ///
+    /// Like `Message::prefix`, this is `None` for a malformed user prefix.
+    pub fn prefix(&self) -> Option<PrefixRef<'a>> {
+        self.raw_prefix.and_then(parse_prefix)
+    }
+
+    /// Parses the command into a `Code`.
+    pub fn code(&self) -> Code {
+        match self.command.parse() {
+            Ok(code) => code,
+            Err(_) => Code::UnknownCommand(self.command.into()),
+        }
+    }
+
+    /// Copies the message into an owned `Message`.
+    pub fn to_owned(&self) -> Message {
+        Message {
+            tags: self.raw_tags.map(Tags::parse).unwrap_or_default(),
+            prefix: self.prefix().map(|prefix| prefix.to_owned()),
+            code: self.code(),
+            args: self.args.iter().map(|arg| arg.to_string()).collect(),
+        }
     }
 }
 
-fn parse_prefix(prefix: &str) -> Option<Prefix> {
-    match prefix.find('!') {
-        None => Some(Prefix::Server(prefix.to_string())),
-        Some(excpos) => {
-            let nick = &prefix[..excpos];
-            let rest = &prefix[excpos + 1..];
-            match rest.find('@') {
-                None => None,
-                Some(atpos) => {
-                    let user = &rest[..atpos];
-                    let host = &rest[atpos + 1..];
-                    Some(Prefix::User(PrefixUser::new(nick, user, host)))
+impl<'a> From<MessageRef<'a>> for Message {
+    fn from(msg: MessageRef<'a>) -> Self {
+        msg.to_owned()
+    }
+}
+
+/// Iterator over the tags of a `MessageRef`.
+#[derive(Clone, Debug)]
+pub struct TagsRef<'a>(str::Split<'a, char>);
+
+impl<'a> Iterator for TagsRef<'a> {
+    type Item = (&'a str, Cow<'a, str>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let tag = self.0.find(|tag| !tag.is_empty())?;
+        Some(match tag.find('=') {
+            None => (tag, Cow::Borrowed("")),
+            Some(idx) => {
+                let value = &tag[idx + 1..];
+                if value.contains('\\') {
+                    (&tag[..idx], Cow::Owned(tags::unescape(value)))
+                } else {
+                    (&tag[..idx], Cow::Borrowed(value))
                 }
             }
-        }
+        })
     }
 }
 
-/// Prefix of the message.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub enum Prefix {
+/// Borrowed prefix of a `MessageRef`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PrefixRef<'a> {
     /// Prefix is a user.
-    User(PrefixUser),
+    User(PrefixUserRef<'a>),
     /// Prefix is a server.
-    Server(String),
+    Server(&'a str),
 }
 
-/// User prefix representation.
-#[derive(Clone, Debug, Eq, PartialEq)]
-pub struct PrefixUser {
+impl PrefixRef<'_> {
+    /// Copies the prefix into an owned `Prefix`.
+    pub fn to_owned(&self) -> Prefix {
+        match *self {
+            PrefixRef::User(ref user) => Prefix::User(user.to_owned()),
+            PrefixRef::Server(server) => Prefix::Server(server.into()),
+        }
+    }
+}
+
+/// Borrowed user prefix representation.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PrefixUserRef<'a> {
     /// Nickname
-    pub nickname: String,
+    pub nickname: &'a str,
     /// Username
-    pub username: String,
+    pub username: &'a str,
     /// Hostname
-    pub hostname: String,
+    pub hostname: &'a str,
 }
 
-impl PrefixUser {
-    fn new(nick: &str, user: &str, host: &str) -> PrefixUser {
-        PrefixUser {
-            nickname: nick.into(),
-            username: user.into(),
-            hostname: host.into(),
-        }
+impl PrefixUserRef<'_> {
+    /// Copies the user prefix into an owned `PrefixUser`.
+    pub fn to_owned(&self) -> PrefixUser {
+        PrefixUser::new(self.nickname, self.username, self.hostname)
     }
 }
 
@@ -179,7 +538,7 @@ fn test_full() {
     let res = Message::parse(":org.prefix.cool COMMAND arg1 arg2 arg3 :suffix is pretty cool yo");
     assert!(res.is_ok());
     let msg = res.ok().unwrap();
-    assert_eq!(msg.code, Code::Unknown("COMMAND".to_string()));
+    assert_eq!(msg.code, Code::UnknownCommand("COMMAND".to_string()));
     assert_eq!(
         msg.args,
         vec!["arg1", "arg2", "arg3", "suffix is pretty cool yo"]
@@ -277,3 +636,207 @@ fn test_prefix_user() {
         Some(Prefix::User(PrefixUser::new("bob", "bob", "bob.com")))
     );
 }
+
+#[test]
+fn test_tags() {
+    let res = Message::parse(
+        "@time=2019-11-24T12:34:56.789Z;msgid=abc;+example.com/typing=active;account :bob!bob@bob.com PRIVMSG #chan :hello",
+    );
+    assert!(res.is_ok());
+    let msg = res.ok().unwrap();
+    assert_eq!(
+        msg.tags.iter().collect::<Vec<_>>(),
+        vec![
+            ("time", "2019-11-24T12:34:56.789Z"),
+            ("msgid", "abc"),
+            ("+example.com/typing", "active"),
+            ("account", ""),
+        ]
+    );
+    assert_eq!(
+        msg.prefix,
+        Some(Prefix::User(PrefixUser::new("bob", "bob", "bob.com")))
+    );
+    assert_eq!(msg.code, Code::Privmsg);
+    assert_eq!(msg.args, vec!["#chan", "hello"]);
+}
+
+#[test]
+fn test_tags_no_prefix() {
+    let res = Message::parse("@a=b PING :token");
+    assert!(res.is_ok());
+    let msg = res.ok().unwrap();
+    assert_eq!(msg.tags.get("a"), Some("b"));
+    assert_eq!(msg.prefix, None);
+    assert_eq!(msg.code, Code::Ping);
+    assert_eq!(msg.args, vec!["token"]);
+}
+
+#[test]
+fn test_tags_unescape() {
+    let res = Message::parse(r"@note=a\\b\:c\sd\re\nf\;x=\z COMMAND");
+    assert!(res.is_ok());
+    let msg = res.ok().unwrap();
+    assert_eq!(msg.tags.get("note"), Some("a\\b;c d\re\nf"));
+    assert_eq!(msg.tags.get("x"), Some("z"));
+}
+
+#[test]
+fn test_only_tags() {
+    let res = Message::parse("@a=b");
+    assert!(res.is_err());
+    let err = res.err().unwrap();
+    assert!(err == ParseError::UnexpectedEnd);
+}
+
+#[test]
+fn test_display() {
+    let msg = Message {
+        tags: vec![("msgid", "a b;c"), ("+typing", "")]
+            .into_iter()
+            .collect(),
+        prefix: Some(Prefix::User(PrefixUser::new("bob", "bob", "bob.com"))),
+        code: Code::Privmsg,
+        args: vec!["#chan".into(), "hello world".into()],
+    };
+    assert_eq!(
+        msg.to_string(),
+        "@msgid=a\\sb\\:c;+typing :bob!bob@bob.com PRIVMSG #chan :hello world"
+    );
+}
+
+#[test]
+fn test_display_trailing() {
+    let display = |args: &[&str]| {
+        Message {
+            tags: Tags::new(),
+            prefix: None,
+            code: Code::Privmsg,
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+        .to_string()
+    };
+    assert_eq!(display(&[]), "PRIVMSG");
+    assert_eq!(display(&["#chan", "hi"]), "PRIVMSG #chan hi");
+    assert_eq!(display(&["#chan", ""]), "PRIVMSG #chan :");
+    assert_eq!(display(&["#chan", ":)"]), "PRIVMSG #chan ::)");
+    assert_eq!(display(&["#chan", "a b"]), "PRIVMSG #chan :a b");
+}
+
+#[test]
+fn test_round_trip() {
+    let lines = [
+        ":org.prefix.cool COMMAND arg1 arg2 arg3 :suffix is pretty cool yo",
+        "NICK arg1 arg2 arg3 :suffix is pretty cool yo",
+        ":bob!bob@bob.com PRIVMSG #chan ::)",
+        ":bob!bob PRIVMSG #chan :prefix is dropped",
+        "@a=b\\sc;d;+e.f/g=\\:\\\\ :irc.example.com 001 bob :Welcome",
+        "@; PING",
+        "PRIVMSG #chan :",
+        "PRIVMSG #chan trailing",
+        "MODE  #chan  +o bob",
+        ": EMPTY prefix",
+        "  leading spaces",
+        "NICK",
+    ];
+    for line in lines.iter() {
+        let msg = Message::parse(line).unwrap();
+        assert_eq!(Message::parse(&msg.to_string()), Ok(msg));
+    }
+}
+
+#[test]
+fn test_ref() {
+    let line = "@a=b\\sc;d :bob!bob@bob.com PRIVMSG #chan :hello world";
+    let res = MessageRef::parse(line);
+    assert!(res.is_ok());
+    let msg = res.ok().unwrap();
+    assert_eq!(
+        msg.tags().collect::<Vec<_>>(),
+        vec![("a", Cow::Borrowed("b c")), ("d", Cow::Borrowed(""))]
+    );
+    assert_eq!(
+        msg.prefix(),
+        Some(PrefixRef::User(PrefixUserRef {
+            nickname: "bob",
+            username: "bob",
+            hostname: "bob.com",
+        }))
+    );
+    assert_eq!(msg.command, "PRIVMSG");
+    assert_eq!(msg.code(), Code::Privmsg);
+    assert_eq!(msg.args, vec!["#chan", "hello world"]);
+    assert_eq!(msg.to_owned(), Message::parse(line).unwrap());
+}
+
+#[test]
+fn test_ref_tag_borrowed() {
+    let msg = MessageRef::parse("@a=b;a=c\\:d;e=f PING").unwrap();
+    assert_eq!(msg.tag("a"), Some(Cow::Owned("c;d".into())));
+    assert!(matches!(msg.tag("e"), Some(Cow::Borrowed("f"))));
+    assert_eq!(msg.tag("g"), None);
+}
+
+#[test]
+fn test_build() {
+    let msg = Message::build(Code::Kick)
+        .tag("label", "1")
+        .arg("#chan")
+        .arg("bob")
+        .trailing("bye");
+    assert_eq!(msg.to_string(), "@label=1 KICK #chan bob bye");
+    assert_eq!(
+        Message::build(Code::Quit).finish(),
+        Message::parse("QUIT").unwrap()
+    );
+}
+
+#[test]
+fn test_validate() {
+    let msg = Message::build(Code::Privmsg)
+        .tag("+draft/reply", "a;b c\r\n")
+        .arg("#chan")
+        .trailing(":) hello");
+    assert_eq!(msg.validate(), Ok(()));
+}
+
+#[test]
+fn test_validate_injection() {
+    let msg = Message::build(Code::Privmsg)
+        .arg("#chan")
+        .trailing("hi\r\nQUIT :pwned");
+    assert_eq!(
+        msg.validate(),
+        Err(ValidationError::ForbiddenCharacter {
+            index: 1,
+            character: '\r',
+        })
+    );
+    let msg = Message::build(Code::Privmsg).arg("#chan").trailing("a\0b");
+    assert_eq!(
+        msg.validate(),
+        Err(ValidationError::ForbiddenCharacter {
+            index: 1,
+            character: '\0',
+        })
+    );
+    let msg = Message::build(Code::UnknownCommand("PRIVMSG #chan :hi\nQUIT".into())).finish();
+    assert_eq!(msg.validate(), Err(ValidationError::InvalidCode));
+    let msg = Message::build(Code::Privmsg)
+        .prefix(Prefix::Server("a\nb".into()))
+        .finish();
+    assert_eq!(msg.validate(), Err(ValidationError::InvalidPrefix));
+    let msg = Message::build(Code::Privmsg).tag("a b", "").finish();
+    assert_eq!(
+        msg.validate(),
+        Err(ValidationError::InvalidTagKey("a b".into()))
+    );
+}
+
+#[test]
+fn test_validate_middle() {
+    for middle in ["#a #b", ":#a", ""].iter() {
+        let msg = Message::build(Code::Privmsg).arg(*middle).trailing("hi");
+        assert_eq!(msg.validate(), Err(ValidationError::InvalidMiddle(0)));
+    }
+}
@@ -0,0 +1,79 @@
+//! In-memory stream for tests.
+
+use {
+    futures::{
+        io::{AsyncRead, AsyncWrite, Cursor, Error},
+        task::{Context, Poll},
+    },
+    std::{
+        pin::Pin,
+        sync::{Arc, Mutex},
+    },
+};
+
+/// Stream reading from a fixed input and recording everything written to it.
+pub struct MockStream {
+    input: Cursor<Vec<u8>>,
+    output: Arc<Mutex<Vec<u8>>>,
+    hang: bool,
+}
+
+impl MockStream {
+    pub fn new(input: &str) -> (Self, Output) {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let stream = MockStream {
+            input: Cursor::new(input.as_bytes().to_vec()),
+            output: output.clone(),
+            hang: false,
+        };
+        (stream, Output(output))
+    }
+
+    /// Never reaches the end of the stream, like an idle connection.
+    pub fn hang(mut self) -> Self {
+        self.hang = true;
+        self
+    }
+}
+
+/// Handle to the data written to a `MockStream`.
+#[derive(Clone)]
+pub struct Output(Arc<Mutex<Vec<u8>>>);
+
+impl Output {
+    pub fn get(&self) -> String {
+        String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+    }
+}
+
+impl AsyncRead for MockStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<Result<usize, Error>> {
+        match Pin::new(&mut self.input).poll_read(cx, buf) {
+            Poll::Ready(Ok(0)) if self.hang && !buf.is_empty() => Poll::Pending,
+            poll => poll,
+        }
+    }
+}
+
+impl AsyncWrite for MockStream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, Error>> {
+        self.output.lock().unwrap().extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
@@ -0,0 +1,348 @@
+//! Parsing and serialization of mode changes.
+
+use {
+    crate::{code::Code, command::Command, isupport::ISupport, message::Message},
+    std::{collections::BTreeSet, fmt},
+};
+
+/// Whether a mode is set or unset.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Sign {
+    /// `+`, the mode is set.
+    Add,
+    /// `-`, the mode is unset.
+    Remove,
+}
+
+impl Sign {
+    /// `+` or `-`.
+    pub fn as_char(self) -> char {
+        match self {
+            Sign::Add => '+',
+            Sign::Remove => '-',
+        }
+    }
+}
+
+impl fmt::Display for Sign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.as_char())
+    }
+}
+
+/// Kind of a channel mode, from `CHANMODES` and `PREFIX`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ModeKind {
+    /// Type A, a list like `b`. Always has a parameter, except to query the list.
+    List,
+    /// Type B, like `k`. Always has a parameter.
+    Always,
+    /// Type C, like `l`. Has a parameter when set.
+    Set,
+    /// Type D, like `n`. Never has a parameter. Unknown modes are of this kind.
+    Never,
+    /// Membership prefix like `o`. Always has a nickname as parameter.
+    Prefix,
+}
+
+impl ModeKind {
+    /// Kind of the channel mode, according to the server features.
+    pub fn of(mode: char, isupport: &ISupport) -> Self {
+        if isupport.prefix().iter().any(|(m, _)| *m == mode) {
+            return ModeKind::Prefix;
+        }
+        let chanmodes = isupport.chanmodes();
+        let kinds = [ModeKind::List, ModeKind::Always, ModeKind::Set];
+        kinds
+            .iter()
+            .zip(chanmodes.iter())
+            .find(|(_, modes)| modes.contains(mode))
+            .map(|(kind, _)| *kind)
+            .unwrap_or(ModeKind::Never)
+    }
+
+    /// Checks if the mode takes a parameter with the sign.
+    pub fn has_arg(self, sign: Sign) -> bool {
+        match self {
+            ModeKind::List | ModeKind::Always | ModeKind::Prefix => true,
+            ModeKind::Set => sign == Sign::Add,
+            ModeKind::Never => false,
+        }
+    }
+}
+
+/// Single mode change, like `+k secret`.
+#[derive(Clone, Debug, Eq, Hash, PartialEq)]
+pub struct ModeChange {
+    /// Whether the mode is set or unset.
+    pub sign: Sign,
+    /// Mode letter, like `k`.
+    pub mode: char,
+    /// Parameter, like the key of `k`.
+    pub arg: Option<String>,
+}
+
+impl ModeChange {
+    /// Creates a mode change.
+    pub fn new(sign: Sign, mode: char, arg: Option<String>) -> Self {
+        ModeChange { sign, mode, arg }
+    }
+
+    /// Parses user mode changes, like `+iw-x`.
+    pub fn parse_user(modestring: &str) -> Vec<ModeChange> {
+        parse(modestring, &[], |_, _| false)
+    }
+
+    /// Parses channel mode changes, pairing the modes with the arguments.
+    ///
+    /// A mode missing its argument, like a list query, has none.
+    pub fn parse_channel(
+        modestring: &str,
+        args: &[String],
+        isupport: &ISupport,
+    ) -> Vec<ModeChange> {
+        parse(modestring, args, |mode, sign| {
+            ModeKind::of(mode, isupport).has_arg(sign)
+        })
+    }
+
+    /// Parses the changes of a `MODE`, `RPL_CHANNELMODEIS` or `RPL_UMODEIS`,
+    /// along with the target.
+    pub fn from_message<'a>(
+        msg: &'a Message,
+        isupport: &ISupport,
+    ) -> Option<(&'a str, Vec<ModeChange>)> {
+        let (target, rest) = match msg.code {
+            Code::Mode => (msg.args.first()?, &msg.args[1..]),
+            Code::RplChannelmodeis => (msg.args.get(1)?, &msg.args[2..]),
+            Code::RplUmodeis => (msg.args.first()?, &msg.args[1..]),
+            _ => return None,
+        };
+        let modestring = rest.first().map(String::as_str).unwrap_or("");
+        let args = rest.get(1..).unwrap_or(&[]);
+        let changes = if isupport.is_channel(target) {
+            ModeChange::parse_channel(modestring, args, isupport)
+        } else {
+            ModeChange::parse_user(modestring)
+        };
+        Some((target, changes))
+    }
+
+    /// Serializes the changes into as few `MODE` messages as possible.
+    ///
+    /// Each message has at most `MODES` changes with a parameter and fits
+    /// in `LINELEN` once relayed by the server. `prefix_len` is the length of
+    /// our own `nick!user@host`, as seen by the server.
+    pub fn to_messages(
+        target: &str,
+        changes: &[ModeChange],
+        isupport: &ISupport,
+        prefix_len: usize,
+    ) -> Vec<Message> {
+        let max_args = isupport.modes().unwrap_or(usize::MAX).max(1);
+        // ":" prefix " MODE " target " " changes "\r\n"
+        let overhead = 1 + prefix_len + 6 + target.len() + 1 + 2;
+        let max_len = isupport.linelen().saturating_sub(overhead);
+
+        let mut msgs = Vec::new();
+        let mut line = ModeLine::default();
+        for change in changes {
+            let has_arg = change.arg.is_some();
+            if !line.is_empty()
+                && (has_arg && line.args.len() >= max_args || line.len_with(change) > max_len)
+            {
+                msgs.push(line.into_message(target));
+                line = ModeLine::default();
+            }
+            line.push(change);
+        }
+        if !line.is_empty() {
+            msgs.push(line.into_message(target));
+        }
+        msgs
+    }
+}
+
+impl fmt::Display for ModeChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.sign, self.mode)?;
+        if let Some(arg) = &self.arg {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Applies user mode changes to the set of modes.
+pub(crate) fn apply_user_modes(modes: &mut BTreeSet<char>, modestring: &str) {
+    for change in ModeChange::parse_user(modestring) {
+        match change.sign {
+            Sign::Add => modes.insert(change.mode),
+            Sign::Remove => modes.remove(&change.mode),
+        };
+    }
+}
+
+fn parse<F>(modestring: &str, args: &[String], has_arg: F) -> Vec<ModeChange>
+where
+    F: Fn(char, Sign) -> bool,
+{
+    let mut args = args.iter();
+    let mut sign = Sign::Add;
+    let mut changes = Vec::new();
+    for mode in modestring.chars() {
+        match mode {
+            '+' => sign = Sign::Add,
+            '-' => sign = Sign::Remove,
+            _ => {
+                let arg = if has_arg(mode, sign) {
+                    args.next().cloned()
+                } else {
+                    None
+                };
+                changes.push(ModeChange::new(sign, mode, arg));
+            }
+        }
+    }
+    changes
+}
+
+/// Changes being gathered into one `MODE`.
+#[derive(Default)]
+struct ModeLine {
+    modestring: String,
+    sign: Option<Sign>,
+    args: Vec<String>,
+}
+
+impl ModeLine {
+    fn is_empty(&self) -> bool {
+        self.modestring.is_empty()
+    }
+
+    fn len_with(&self, change: &ModeChange) -> usize {
+        let args = self.args.iter().map(|arg| arg.len() + 1).sum::<usize>();
+        let sign = if self.sign == Some(change.sign) { 0 } else { 1 };
+        let arg = change.arg.as_ref().map(|arg| arg.len() + 2).unwrap_or(0);
+        self.modestring.len() + args + sign + 1 + arg
+    }
+
+    fn push(&mut self, change: &ModeChange) {
+        if self.sign != Some(change.sign) {
+            self.modestring.push(change.sign.as_char());
+            self.sign = Some(change.sign);
+        }
+        self.modestring.push(change.mode);
+        self.args.extend(change.arg.clone());
+    }
+
+    fn into_message(self, target: &str) -> Message {
+        let mut changes = vec![self.modestring];
+        changes.extend(self.args);
+        Command::Mode {
+            target: target.into(),
+            changes,
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+fn features(tokens: &str) -> ISupport {
+    let mut isupport = ISupport::new();
+    for token in tokens.split(' ') {
+        isupport.insert_token(token);
+    }
+    isupport
+}
+
+#[test]
+fn test_parse_channel() {
+    let isupport = features("PREFIX=(ov)@+ CHANMODES=beI,k,l,imnpst");
+    let msg = Message::parse(":op!o@host MODE #chan +ovk-lb+n alice bob secret *!*@spam").unwrap();
+    let (target, changes) = ModeChange::from_message(&msg, &isupport).unwrap();
+    assert_eq!(target, "#chan");
+    assert_eq!(
+        changes,
+        vec![
+            ModeChange::new(Sign::Add, 'o', Some("alice".into())),
+            ModeChange::new(Sign::Add, 'v', Some("bob".into())),
+            ModeChange::new(Sign::Add, 'k', Some("secret".into())),
+            ModeChange::new(Sign::Remove, 'l', None),
+            ModeChange::new(Sign::Remove, 'b', Some("*!*@spam".into())),
+            ModeChange::new(Sign::Add, 'n', None),
+        ]
+    );
+
+    let msg = Message::parse(":irc.example.com 324 bot #chan +ntl 10").unwrap();
+    let (target, changes) = ModeChange::from_message(&msg, &isupport).unwrap();
+    assert_eq!(target, "#chan");
+    assert_eq!(
+        changes[2],
+        ModeChange::new(Sign::Add, 'l', Some("10".into()))
+    );
+
+    let changes = ModeChange::parse_channel("+b", &[], &isupport);
+    assert_eq!(changes, vec![ModeChange::new(Sign::Add, 'b', None)]);
+    assert_eq!(ModeKind::of('x', &isupport), ModeKind::Never);
+}
+
+#[test]
+fn test_parse_user() {
+    let isupport = ISupport::new();
+    let msg = Message::parse(":bot MODE bot :+iw-x").unwrap();
+    let (target, changes) = ModeChange::from_message(&msg, &isupport).unwrap();
+    assert_eq!(target, "bot");
+    assert_eq!(
+        changes
+            .iter()
+            .map(ModeChange::to_string)
+            .collect::<Vec<_>>(),
+        vec!["+i", "+w", "-x"]
+    );
+    let msg = Message::parse(":irc.example.com 221 bot +Zi").unwrap();
+    let (target, changes) = ModeChange::from_message(&msg, &isupport).unwrap();
+    assert_eq!(target, "bot");
+    assert_eq!(changes.len(), 2);
+    let msg = Message::parse(":irc.example.com 001 bot :Welcome").unwrap();
+    assert!(ModeChange::from_message(&msg, &isupport).is_none());
+}
+
+#[test]
+fn test_to_messages() {
+    let isupport = features("MODES=2");
+    let changes = ModeChange::parse_channel(
+        "+ooo-v+nt-k",
+        &["a".into(), "b".into(), "c".into(), "d".into(), "key".into()],
+        &isupport,
+    );
+    let lines = ModeChange::to_messages("#chan", &changes, &isupport, 0)
+        .iter()
+        .map(Message::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            "MODE #chan +oo a b",
+            "MODE #chan +o-v+nt c d",
+            "MODE #chan -k key"
+        ]
+    );
+
+    let isupport = features("MODES");
+    let changes = (0..100)
+        .map(|i| ModeChange::new(Sign::Add, 'b', Some(format!("nick{}!*@*", i))))
+        .collect::<Vec<_>>();
+    let prefix = "bot!bot@localhost";
+    let msgs = ModeChange::to_messages("#chan", &changes, &isupport, prefix.len());
+    assert!(msgs.len() > 1);
+    // As relayed by the server.
+    assert!(msgs
+        .iter()
+        .all(|msg| format!(":{} {}\r\n", prefix, msg).len() <= 512));
+    let parsed = msgs
+        .iter()
+        .flat_map(|msg| ModeChange::from_message(msg, &isupport).unwrap().1)
+        .collect::<Vec<_>>();
+    assert_eq!(parsed, changes);
+}
@@ -0,0 +1,790 @@
+//! Correlation of queries like `WHOIS` with the replies answering them.
+
+use {
+    crate::{
+        batch::{BatchAssembler, BatchItem},
+        casemap::CaseMapping,
+        code::{Category, Code},
+        isupport::ISupport,
+        message::Message,
+        stream::WriteError,
+    },
+    futures::{
+        channel::oneshot,
+        future::{self, Either},
+    },
+    futures_timer::Delay,
+    std::{
+        collections::HashMap,
+        fmt,
+        sync::{Arc, Mutex},
+        time::Duration,
+    },
+};
+
+/// Default time to wait for the end of a query.
+pub const DEFAULT_QUERY_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Prefix of the labels we generate.
+const LABEL_PREFIX: &str = "yaircc";
+
+/// Error generated by a query.
+#[derive(Debug)]
+pub enum QueryError {
+    /// Server answered with an error, like `ERR_NOSUCHNICK`.
+    Failed(Message),
+    /// Query did not end in time, or no response came to a labeled message.
+    Timeout,
+    /// Stream ended before the query did.
+    Closed,
+    /// Failed to write the command.
+    WriteError(WriteError),
+}
+
+impl From<WriteError> for QueryError {
+    fn from(err: WriteError) -> Self {
+        QueryError::WriteError(err)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            QueryError::Failed(ref msg) => write!(f, "Failed: {}", msg),
+            QueryError::Timeout => write!(f, "Timeout"),
+            QueryError::Closed => write!(f, "Closed"),
+            QueryError::WriteError(ref e) => write!(f, "WriteError: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// Reply recognized by a query: a code or category, and the argument holding the key.
+#[derive(Clone, Debug)]
+struct Matcher {
+    code: Result<Code, Category>,
+    key: Option<usize>,
+}
+
+/// Replies answering a command, until an end or error reply.
+///
+/// Replies are recognized by their code and, when the query has a key like
+/// the nickname of a `WHOIS`, by the argument at the given position.
+#[derive(Clone, Debug)]
+pub struct Query {
+    key: Option<String>,
+    mapping: Option<CaseMapping>,
+    replies: Vec<Matcher>,
+    ends: Vec<Matcher>,
+    errors: Vec<Matcher>,
+    drain: bool,
+}
+
+impl Query {
+    /// Query recognizing its replies by the key, compared with the casemapping.
+    pub fn new(key: Option<&str>) -> Self {
+        Query {
+            key: key.map(Into::into),
+            mapping: None,
+            replies: Vec::new(),
+            ends: Vec::new(),
+            errors: Vec::new(),
+            drain: false,
+        }
+    }
+
+    /// Compares the key with the casemapping, like `ISupport::casemapping`.
+    ///
+    /// Defaults to the one the `Correlator` learned from `RPL_ISUPPORT`.
+    pub fn casemapping(mut self, mapping: CaseMapping) -> Self {
+        self.mapping = Some(mapping);
+        self
+    }
+
+    /// Collects the replies with the code, having the key at `key_index` if any.
+    pub fn reply(mut self, code: Code, key_index: Option<usize>) -> Self {
+        self.replies.push(Matcher {
+            code: Ok(code),
+            key: key_index,
+        });
+        self
+    }
+
+    /// Collects the replies of the category, having the key at `key_index` if any.
+    pub fn category(mut self, category: Category, key_index: Option<usize>) -> Self {
+        self.replies.push(Matcher {
+            code: Err(category),
+            key: key_index,
+        });
+        self
+    }
+
+    /// Ends the query with the replies with the code.
+    pub fn end(mut self, code: Code, key_index: Option<usize>) -> Self {
+        self.ends.push(Matcher {
+            code: Ok(code),
+            key: key_index,
+        });
+        self
+    }
+
+    /// Fails the query with the replies with the code.
+    pub fn error(mut self, code: Code, key_index: Option<usize>) -> Self {
+        self.errors.push(Matcher {
+            code: Ok(code),
+            key: key_index,
+        });
+        self
+    }
+
+    /// Still expects the end reply after an error, so it is not taken for
+    /// the end of another query.
+    pub fn drain(mut self) -> Self {
+        self.drain = true;
+        self
+    }
+
+    /// `WHOIS <nick>`, ending with `RPL_ENDOFWHOIS` or failing with `ERR_NOSUCHNICK`.
+    pub fn whois(nick: &str) -> Self {
+        Query::new(Some(nick))
+            .category(Category::Whois, Some(1))
+            .reply(Code::RplAway, Some(1))
+            .end(Code::RplEndofwhois, Some(1))
+            .error(Code::ErrNosuchnick, Some(1))
+            .drain()
+    }
+
+    /// `WHO <mask>`, ending with `RPL_ENDOFWHO`.
+    pub fn who(mask: &str) -> Self {
+        Query::new(Some(mask))
+            .reply(Code::RplWhoreply, None)
+            .end(Code::RplEndofwho, Some(1))
+    }
+
+    /// `NAMES <channel>`, ending with `RPL_ENDOFNAMES`.
+    pub fn names(channel: &str) -> Self {
+        Query::new(Some(channel))
+            .reply(Code::RplNamreply, Some(2))
+            .end(Code::RplEndofnames, Some(1))
+    }
+
+    /// `LIST`, ending with `RPL_LISTEND` or failing with `RPL_TRYAGAIN`.
+    pub fn list() -> Self {
+        Query::new(None)
+            .reply(Code::RplListstart, None)
+            .reply(Code::RplList, None)
+            .end(Code::RplListend, None)
+            .error(Code::RplTryagain, None)
+    }
+
+    /// `MOTD`, ending with `RPL_ENDOFMOTD` or failing with `ERR_NOMOTD`.
+    pub fn motd() -> Self {
+        Query::new(None)
+            .reply(Code::RplMotdstart, None)
+            .reply(Code::RplMotd, None)
+            .end(Code::RplEndofmotd, None)
+            .error(Code::ErrNomotd, None)
+    }
+
+    /// `MODE <channel> +b`, ending with `RPL_ENDOFBANLIST`.
+    pub fn banlist(channel: &str) -> Self {
+        Query::new(Some(channel))
+            .reply(Code::RplBanlist, Some(1))
+            .end(Code::RplEndofbanlist, Some(1))
+            .error(Code::ErrNosuchchannel, Some(1))
+            .error(Code::ErrNotonchannel, Some(1))
+            .error(Code::ErrChanoprivsneeded, Some(1))
+    }
+
+    fn matches(&self, matchers: &[Matcher], msg: &Message, mapping: CaseMapping) -> bool {
+        let mapping = self.mapping.unwrap_or(mapping);
+        matchers
+            .iter()
+            .any(|matcher| matcher.matches(msg, self.key.as_deref(), mapping))
+    }
+}
+
+impl Matcher {
+    fn matches(&self, msg: &Message, key: Option<&str>, mapping: CaseMapping) -> bool {
+        let code = match self.code {
+            Ok(ref code) => *code == msg.code,
+            Err(category) => msg.code.category() == Some(category),
+        };
+        code && match (self.key, key) {
+            (Some(index), Some(key)) => msg
+                .args
+                .get(index)
+                .is_some_and(|arg| mapping.equals(arg, key)),
+            _ => true,
+        }
+    }
+}
+
+/// Identifier of a query started in a `Correlator`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct QueryId(u64);
+
+#[derive(Debug)]
+struct Pending {
+    id: QueryId,
+    query: Query,
+    replies: Vec<Message>,
+    /// Failed, waiting for the end reply to drain.
+    failed: bool,
+}
+
+impl Pending {
+    fn accepts(&self, msg: &Message, mapping: CaseMapping) -> bool {
+        let query = &self.query;
+        if self.failed {
+            return query.matches(&query.ends, msg, mapping);
+        }
+        query.matches(&query.replies, msg, mapping)
+            || query.matches(&query.ends, msg, mapping)
+            || query.matches(&query.errors, msg, mapping)
+    }
+}
+
+/// Matches the replies from the server with the pending queries.
+///
+/// Servers answer commands in order, so a reply goes to the oldest query
+/// accepting it. Keys are compared with the `CASEMAPPING` of the server,
+/// unless the query has its own casemapping.
+#[derive(Debug, Default)]
+pub struct Correlator {
+    pending: Vec<Pending>,
+    next_id: u64,
+    isupport: ISupport,
+}
+
+impl Correlator {
+    /// Creates a correlator without any pending query.
+    pub fn new() -> Self {
+        Correlator::default()
+    }
+
+    /// Starts a query. Start it before sending the command, not to miss a reply.
+    pub fn start(&mut self, query: Query) -> QueryId {
+        let id = QueryId(self.next_id);
+        self.next_id += 1;
+        self.pending.push(Pending {
+            id,
+            query,
+            replies: Vec::new(),
+            failed: false,
+        });
+        id
+    }
+
+    /// Forgets a query, like one which timed out.
+    ///
+    /// A failed query still draining its end reply is kept.
+    pub fn cancel(&mut self, id: QueryId) {
+        self.pending
+            .retain(|pending| pending.id != id || pending.failed);
+    }
+
+    /// Checks if no query is pending.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Handles a message from the server, returning the query it ended if any,
+    /// with the replies collected or the error reply.
+    ///
+    /// `RPL_ISUPPORT` must be fed as well, for the casemapping of the server.
+    pub fn handle(&mut self, msg: &Message) -> Option<(QueryId, Result<Vec<Message>, Message>)> {
+        self.isupport.handle(msg);
+        let mapping = self.isupport.casemapping();
+        let mut index = self.pending.iter().position(|p| p.accepts(msg, mapping))?;
+        // The server moved on, older failed queries will not get their end reply.
+        let failed = self.pending[..index].iter().filter(|p| p.failed).count();
+        if failed > 0 {
+            let mut i = 0;
+            self.pending.retain(|p| {
+                i += 1;
+                i > index || !p.failed
+            });
+            index -= failed;
+        }
+
+        let pending = &mut self.pending[index];
+        let query = &pending.query;
+        if pending.failed {
+            self.pending.remove(index);
+            None
+        } else if query.matches(&query.ends, msg, mapping) {
+            let pending = self.pending.remove(index);
+            Some((pending.id, Ok(pending.replies)))
+        } else if query.matches(&query.errors, msg, mapping) {
+            let id = pending.id;
+            if query.drain {
+                pending.failed = true;
+            } else {
+                self.pending.remove(index);
+            }
+            Some((id, Err(msg.clone())))
+        } else {
+            pending.replies.push(msg.clone());
+            None
+        }
+    }
+}
+
+type Answer = Result<Vec<Message>, Message>;
+
+#[derive(Default)]
+struct Inner {
+    correlator: Correlator,
+    senders: HashMap<QueryId, oneshot::Sender<Answer>>,
+    /// Labeled messages waiting for their response.
+    labels: HashMap<String, oneshot::Sender<BatchItem>>,
+    next_label: u64,
+    batches: BatchAssembler,
+    closed: bool,
+}
+
+/// Correlator shared by an `IrcStream` and its `Writer`s.
+#[derive(Clone, Default)]
+pub(crate) struct Queries(Arc<Mutex<Inner>>);
+
+impl Queries {
+    /// Starts a query, returning the ticket to wait for its end.
+    pub(crate) fn start(&self, query: Query) -> Ticket<Answer> {
+        let (sender, receiver) = oneshot::channel();
+        let mut inner = self.0.lock().unwrap();
+        let id = inner.correlator.start(query);
+        // A dropped sender reports the query as closed.
+        if !inner.closed {
+            inner.senders.insert(id, sender);
+        }
+        Ticket {
+            queries: self.clone(),
+            key: Key::Query(id),
+            receiver,
+        }
+    }
+
+    /// Generates a label, returning the ticket to wait for the response.
+    pub(crate) fn start_labeled(&self) -> (String, Ticket<BatchItem>) {
+        let (sender, receiver) = oneshot::channel();
+        let mut inner = self.0.lock().unwrap();
+        let label = format!("{}{}", LABEL_PREFIX, inner.next_label);
+        inner.next_label += 1;
+        if !inner.closed {
+            inner.labels.insert(label.clone(), sender);
+        }
+        let ticket = Ticket {
+            queries: self.clone(),
+            key: Key::Label(label.clone()),
+            receiver,
+        };
+        (label, ticket)
+    }
+
+    /// Checks if some query is waiting for replies.
+    pub(crate) fn is_waiting(&self) -> bool {
+        let inner = self.0.lock().unwrap();
+        !inner.correlator.is_empty() || !inner.labels.is_empty() || !inner.batches.is_empty()
+    }
+
+    pub(crate) fn handle(&self, msg: &Message) {
+        let mut inner = self.0.lock().unwrap();
+        if let Some((id, answer)) = inner.correlator.handle(msg) {
+            if let Some(sender) = inner.senders.remove(&id) {
+                let _ = sender.send(answer);
+            }
+        }
+        if inner.labels.is_empty() && inner.batches.is_empty() {
+            return;
+        }
+        let item = match inner.batches.handle(msg.clone()) {
+            Some(item) => item,
+            None => return,
+        };
+        let sender = item.label().and_then(|label| inner.labels.remove(label));
+        if let Some(sender) = sender {
+            let _ = sender.send(item);
+        }
+    }
+
+    /// Fails every query, the stream having ended.
+    pub(crate) fn close(&self) {
+        let mut inner = self.0.lock().unwrap();
+        inner.closed = true;
+        inner.correlator = Correlator::new();
+        inner.senders.clear();
+        inner.labels.clear();
+        inner.batches = BatchAssembler::new();
+    }
+
+    fn cancel(&self, key: &Key) {
+        let mut inner = self.0.lock().unwrap();
+        match key {
+            Key::Query(id) => {
+                inner.correlator.cancel(*id);
+                inner.senders.remove(id);
+            }
+            Key::Label(label) => {
+                inner.labels.remove(label);
+            }
+        }
+    }
+}
+
+enum Key {
+    Query(QueryId),
+    Label(String),
+}
+
+/// Pending query or labeled message, cancelled when dropped.
+pub(crate) struct Ticket<T> {
+    queries: Queries,
+    key: Key,
+    receiver: oneshot::Receiver<T>,
+}
+
+impl<T> Ticket<T> {
+    /// Waits for the answer.
+    pub(crate) async fn wait(mut self, timeout: Duration) -> Result<T, QueryError> {
+        match future::select(&mut self.receiver, Delay::new(timeout)).await {
+            Either::Left((Ok(answer), _)) => Ok(answer),
+            Either::Left((Err(_), _)) => Err(QueryError::Closed),
+            Either::Right(_) => Err(QueryError::Timeout),
+        }
+    }
+}
+
+impl<T> Drop for Ticket<T> {
+    fn drop(&mut self) {
+        self.queries.cancel(&self.key);
+    }
+}
+
+/// Answer to `WHOIS`.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct WhoisInfo {
+    /// Nickname, as given by the server.
+    pub nickname: String,
+    /// Username, from `RPL_WHOISUSER`.
+    pub username: Option<String>,
+    /// Hostname, from `RPL_WHOISUSER`.
+    pub hostname: Option<String>,
+    /// Real name, from `RPL_WHOISUSER`.
+    pub realname: Option<String>,
+    /// Server the user is connected to, from `RPL_WHOISSERVER`.
+    pub server: Option<String>,
+    /// Channels, with their membership prefixes.
+    pub channels: Vec<String>,
+    /// Idle time, from `RPL_WHOISIDLE`.
+    pub idle: Option<Duration>,
+    /// Signon time, in seconds since the epoch.
+    pub signon: Option<u64>,
+    /// Account the user is logged in as, from `RPL_WHOISACCOUNT`.
+    pub account: Option<String>,
+    /// Away message, from `RPL_AWAY`.
+    pub away: Option<String>,
+    /// Whether the user is an IRC operator, from `RPL_WHOISOPERATOR`.
+    pub operator: bool,
+    /// Whether the user is using a secure connection, from `RPL_WHOISSECURE`.
+    pub secure: bool,
+    /// Every reply, including the ones not interpreted above.
+    pub replies: Vec<Message>,
+}
+
+impl WhoisInfo {
+    /// Interprets the replies of a `WHOIS` of the nickname.
+    pub fn from_replies(nickname: &str, replies: Vec<Message>) -> Self {
+        let mut info = WhoisInfo {
+            nickname: nickname.into(),
+            ..WhoisInfo::default()
+        };
+        for msg in &replies {
+            if let Ok(reply) = msg.as_whoisuser() {
+                info.nickname = reply.nick.into();
+                info.username = Some(reply.username.into());
+                info.hostname = Some(reply.host.into());
+                info.realname = Some(reply.realname.into());
+            } else if let Ok(reply) = msg.as_whoisserver() {
+                info.server = Some(reply.server.into());
+            } else if let Ok(reply) = msg.as_whoischannels() {
+                info.channels
+                    .extend(reply.channels.split_whitespace().map(Into::into));
+            } else if let Ok(reply) = msg.as_whoisidle() {
+                info.idle = reply.secs.parse().ok().map(Duration::from_secs);
+                info.signon = reply.signon.and_then(|signon| signon.parse().ok());
+            } else if let Ok(reply) = msg.as_whoisaccount() {
+                info.account = Some(reply.account.into());
+            } else if let Ok(reply) = msg.as_away() {
+                info.away = Some(reply.message.into());
+            } else if msg.code == Code::RplWhoisoperator {
+                info.operator = true;
+            } else if msg.code == Code::RplWhoissecure {
+                info.secure = true;
+            }
+        }
+        info.replies = replies;
+        info
+    }
+}
+
+/// Entry of a `WHO` answer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct WhoEntry {
+    /// Channel, or `*` if none.
+    pub channel: String,
+    /// Username.
+    pub username: String,
+    /// Hostname.
+    pub hostname: String,
+    /// Server the user is connected to.
+    pub server: String,
+    /// Nickname.
+    pub nickname: String,
+    /// Flags like `H@`, `H` for here or `G` for gone.
+    pub flags: String,
+    /// Number of hops to the server of the user, if given.
+    pub hopcount: Option<u32>,
+    /// Real name.
+    pub realname: String,
+}
+
+impl WhoEntry {
+    /// Interprets the `RPL_WHOREPLY`s of a `WHO`.
+    pub fn from_replies(replies: &[Message]) -> Vec<Self> {
+        replies
+            .iter()
+            .filter_map(|msg| msg.as_whoreply().ok())
+            .map(|reply| {
+                let (hopcount, realname) = match reply.hopcount_realname.split_once(' ') {
+                    Some((hopcount, realname)) => (hopcount.parse().ok(), realname),
+                    None => (reply.hopcount_realname.parse().ok(), ""),
+                };
+                WhoEntry {
+                    channel: reply.channel.into(),
+                    username: reply.username.into(),
+                    hostname: reply.host.into(),
+                    server: reply.server.into(),
+                    nickname: reply.nick.into(),
+                    flags: reply.flags.into(),
+                    hopcount,
+                    realname: realname.into(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Names in a `NAMES` answer, with their membership prefixes.
+pub fn names_from_replies(replies: &[Message]) -> Vec<String> {
+    replies
+        .iter()
+        .filter_map(|msg| msg.as_namreply().ok())
+        .flat_map(|reply| reply.names.split_whitespace())
+        .map(Into::into)
+        .collect()
+}
+
+/// Entry of a `LIST` answer.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ListEntry {
+    /// Channel name.
+    pub channel: String,
+    /// Number of visible users.
+    pub visible: usize,
+    /// Topic, empty if none.
+    pub topic: String,
+}
+
+impl ListEntry {
+    /// Interprets the `RPL_LIST`s of a `LIST`.
+    pub fn from_replies(replies: &[Message]) -> Vec<Self> {
+        replies
+            .iter()
+            .filter_map(|msg| msg.as_list().ok())
+            .map(|reply| ListEntry {
+                channel: reply.channel.into(),
+                visible: reply.visible.parse().unwrap_or(0),
+                topic: reply.topic.into(),
+            })
+            .collect()
+    }
+}
+
+/// Lines of a `MOTD` answer.
+pub fn motd_from_replies(replies: &[Message]) -> Vec<String> {
+    replies
+        .iter()
+        .filter_map(|msg| msg.as_motd().ok())
+        .map(|reply| reply.text.into())
+        .collect()
+}
+
+/// Entry of a ban list.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BanEntry {
+    /// Banned mask, like `*!*@host`.
+    pub mask: String,
+    /// Nickname or mask of who set the ban, if given.
+    pub setter: Option<String>,
+    /// Time the ban was set, in seconds since the epoch.
+    pub set_at: Option<u64>,
+}
+
+impl BanEntry {
+    /// Interprets the `RPL_BANLIST`s of a ban list query.
+    pub fn from_replies(replies: &[Message]) -> Vec<Self> {
+        replies
+            .iter()
+            .filter_map(|msg| msg.as_banlist().ok())
+            .map(|reply| BanEntry {
+                mask: reply.mask.into(),
+                setter: reply.setter.map(Into::into),
+                set_at: reply.set_at.and_then(|set_at| set_at.parse().ok()),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+fn reply(line: &str) -> Message {
+    Message::parse(line).unwrap()
+}
+
+#[test]
+fn test_whois() {
+    let mut correlator = Correlator::new();
+    let id = correlator.start(Query::whois("Bob"));
+    for line in &[
+        ":irc.example.com 311 me bob ~bob bob.com * :Bob Bobson",
+        ":irc.example.com 311 me alice ~alice alice.com * :Alice",
+        ":irc.example.com 319 me bob :@#chan +#other",
+        ":irc.example.com 317 me bob 42 1574000000 :seconds idle, signon time",
+        ":irc.example.com 330 me bob bobby :is logged in as",
+        ":irc.example.com 671 me bob :is using a secure connection",
+        ":irc.example.com 318 me alice :End of /WHOIS list",
+    ] {
+        assert!(correlator.handle(&reply(line)).is_none());
+    }
+    let (done, answer) = correlator
+        .handle(&reply(":irc.example.com 318 me BOB :End of /WHOIS list"))
+        .unwrap();
+    assert_eq!(done, id);
+    assert!(correlator.is_empty());
+
+    let info = WhoisInfo::from_replies("Bob", answer.unwrap());
+    assert_eq!(info.nickname, "bob");
+    assert_eq!(info.realname.as_deref(), Some("Bob Bobson"));
+    assert_eq!(info.channels, vec!["@#chan", "+#other"]);
+    assert_eq!(info.idle, Some(Duration::from_secs(42)));
+    assert_eq!(info.signon, Some(1_574_000_000));
+    assert_eq!(info.account.as_deref(), Some("bobby"));
+    assert!(info.secure);
+    assert!(!info.operator);
+    assert_eq!(info.replies.len(), 5);
+}
+
+#[test]
+fn test_casemapping() {
+    let end = |nick: &str| reply(&format!(":irc.example.com 318 me {} :End", nick));
+
+    let mut correlator = Correlator::new();
+    correlator.start(Query::whois("Bob[]"));
+    assert!(correlator.handle(&end("bob{}")).is_some());
+
+    correlator.handle(&reply(
+        ":irc.example.com 005 me CASEMAPPING=ascii :are supported by this server",
+    ));
+    correlator.start(Query::whois("Bob[]"));
+    assert!(correlator.handle(&end("bob{}")).is_none());
+    assert!(correlator.handle(&end("bob[]")).is_some());
+
+    correlator.start(Query::whois("Bob[]").casemapping(CaseMapping::Rfc1459));
+    assert!(correlator.handle(&end("bob{}")).is_some());
+    assert!(correlator.is_empty());
+}
+
+#[test]
+fn test_overlapping() {
+    let mut correlator = Correlator::new();
+    let first = correlator.start(Query::whois("ghost"));
+    let second = correlator.start(Query::whois("ghost"));
+    let names = correlator.start(Query::names("#chan"));
+
+    let (id, answer) = correlator
+        .handle(&reply(":irc.example.com 401 me ghost :No such nick"))
+        .unwrap();
+    assert_eq!(id, first);
+    assert_eq!(answer.unwrap_err().code, Code::ErrNosuchnick);
+    // The end of the failed query is drained, not taken by the second one.
+    assert!(correlator
+        .handle(&reply(":irc.example.com 318 me ghost :End of /WHOIS list"))
+        .is_none());
+
+    assert!(correlator
+        .handle(&reply(":irc.example.com 353 me = #chan :@alice bob"))
+        .is_none());
+    let (id, answer) = correlator
+        .handle(&reply(":irc.example.com 366 me #CHAN :End of /NAMES list"))
+        .unwrap();
+    assert_eq!(id, names);
+    assert_eq!(names_from_replies(&answer.unwrap()), vec!["@alice", "bob"]);
+
+    // Some servers do not send the end after an error.
+    let third = correlator.start(Query::whois("ghost"));
+    let (id, _) = correlator
+        .handle(&reply(":irc.example.com 401 me ghost :No such nick"))
+        .unwrap();
+    assert_eq!(id, second);
+    assert!(correlator
+        .handle(&reply(":irc.example.com 311 me ghost ~g host * :Ghost"))
+        .is_none());
+    let (id, answer) = correlator
+        .handle(&reply(":irc.example.com 318 me ghost :End of /WHOIS list"))
+        .unwrap();
+    assert_eq!(id, third);
+    assert_eq!(answer.unwrap().len(), 1);
+    assert!(correlator.is_empty());
+}
+
+#[test]
+fn test_cancel() {
+    let mut correlator = Correlator::new();
+    let motd = correlator.start(Query::motd());
+    let list = correlator.start(Query::list());
+    correlator.cancel(motd);
+    assert!(correlator
+        .handle(&reply(":irc.example.com 376 me :End of /MOTD command"))
+        .is_none());
+    let (id, answer) = correlator
+        .handle(&reply(":irc.example.com 263 me LIST :Please wait"))
+        .unwrap();
+    assert_eq!(id, list);
+    assert!(answer.is_err());
+    assert!(correlator.is_empty());
+}
+
+#[test]
+fn test_entries() {
+    let replies = [
+        reply(":irc.example.com 352 me #chan ~bob bob.com irc.example.com bob H@ :0 Bob Bobson"),
+        reply(":irc.example.com 322 me #chan 42 :Topic here"),
+        reply(":irc.example.com 367 me #chan *!*@spam op 1574000000"),
+        reply(":irc.example.com 372 me :- Hello"),
+    ];
+    let who = WhoEntry::from_replies(&replies);
+    assert_eq!(who.len(), 1);
+    assert_eq!(who[0].nickname, "bob");
+    assert_eq!(who[0].flags, "H@");
+    assert_eq!(who[0].hopcount, Some(0));
+    assert_eq!(who[0].realname, "Bob Bobson");
+    let list = ListEntry::from_replies(&replies);
+    assert_eq!(list[0].visible, 42);
+    assert_eq!(list[0].topic, "Topic here");
+    let bans = BanEntry::from_replies(&replies);
+    assert_eq!(bans[0].mask, "*!*@spam");
+    assert_eq!(bans[0].setter.as_deref(), Some("op"));
+    assert_eq!(bans[0].set_at, Some(1_574_000_000));
+    assert_eq!(motd_from_replies(&replies), vec!["- Hello"]);
+}
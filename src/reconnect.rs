@@ -0,0 +1,610 @@
+//! Reconnecting client, replaying joined channels and user modes.
+
+use {
+    crate::{
+        casemap::CaseMapping,
+        code::Code,
+        command::Command,
+        isupport::ISupport,
+        keepalive::Keepalive,
+        message::{Message, Prefix},
+        mode::apply_user_modes,
+        register::{RegisterError, Registered, Registration},
+        stream::{IrcStream, StreamError, WriteError, Writer},
+    },
+    encoding::EncodingRef,
+    futures::{
+        future::{self, Either},
+        prelude::*,
+    },
+    futures_timer::Delay,
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        io::Error as IoError,
+        pin::Pin,
+        time::{Duration, Instant},
+    },
+};
+
+/// User modes that cannot be set by the user, thus not replayed.
+const SERVER_MODES: &[char] = &['o', 'O', 'r', 'z', 'Z'];
+
+/// Default time given to the registration before giving up on the connection.
+pub const DEFAULT_REGISTER_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Exponential backoff with jitter.
+///
+/// The `n`th delay is picked at random between half and all of
+/// `initial * 2^n`, capped to `max`.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+    attempt: u32,
+}
+
+impl Backoff {
+    /// Creates a backoff starting from `initial`, up to `max`.
+    pub fn new(initial: Duration, max: Duration) -> Self {
+        Backoff {
+            initial,
+            max,
+            attempt: 0,
+        }
+    }
+
+    /// Number of delays returned since the last reset.
+    pub fn attempt(&self) -> u32 {
+        self.attempt
+    }
+
+    /// Returns the next delay.
+    pub fn next_delay(&mut self) -> Duration {
+        let base = self
+            .initial
+            .checked_mul(1 << self.attempt.min(16))
+            .map_or(self.max, |delay| delay.min(self.max));
+        self.attempt = self.attempt.saturating_add(1);
+
+        let mut random = [0; 4];
+        getrandom::getrandom(&mut random).expect("failed to generate a random number");
+        let jitter = f64::from(u32::from_ne_bytes(random)) / f64::from(u32::MAX);
+        base.mul_f64(0.5 + 0.5 * jitter)
+    }
+
+    /// Starts over from the initial delay.
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+impl Default for Backoff {
+    /// From 1 second up to 5 minutes.
+    fn default() -> Self {
+        Backoff::new(Duration::from_secs(1), Duration::from_secs(300))
+    }
+}
+
+/// Why the connection was lost.
+#[derive(Debug)]
+pub enum DisconnectReason {
+    /// Connector failed.
+    Connect(IoError),
+    /// Registration failed.
+    Register(RegisterError),
+    /// Registration did not complete in time.
+    RegisterTimeout,
+    /// Replaying the state failed.
+    Write(WriteError),
+    /// Stream failed.
+    Stream(StreamError),
+    /// Server closed the connection.
+    Closed,
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            DisconnectReason::Connect(ref e) => write!(f, "Connect: {}", e),
+            DisconnectReason::Register(ref e) => write!(f, "Register: {}", e),
+            DisconnectReason::RegisterTimeout => write!(f, "RegisterTimeout"),
+            DisconnectReason::Write(ref e) => write!(f, "Write: {}", e),
+            DisconnectReason::Stream(ref e) => write!(f, "Stream: {}", e),
+            DisconnectReason::Closed => write!(f, "Closed"),
+        }
+    }
+}
+
+/// Event yielded by `Reconnecting`.
+#[derive(Debug)]
+pub enum Event {
+    /// Connected and registered. Joined channels and user modes are replayed.
+    Connected(Registered),
+    /// Message received from the server.
+    Message(Message),
+    /// Message could not be parsed. The connection is still alive.
+    ParseError(StreamError),
+    /// Connection was lost, and the next attempt happens after `retry_in`.
+    Disconnected {
+        reason: DisconnectReason,
+        retry_in: Duration,
+    },
+}
+
+type Connector<S> =
+    Box<dyn FnMut() -> Pin<Box<dyn Future<Output = Result<S, IoError>> + Send>> + Send>;
+type MessageStream = Pin<Box<dyn Stream<Item = Result<Message, StreamError>> + Send>>;
+
+/// Client reconnecting whenever the connection is lost.
+///
+/// Every connection is registered with a fresh `Registration`. Channels we
+/// are in and our user modes are tracked from the messages received, and
+/// replayed after registration. Channel keys are only known when joining
+/// through `join`.
+pub struct Reconnecting<S> {
+    connector: Connector<S>,
+    registration: Box<dyn FnMut() -> Registration + Send>,
+    encoding: EncodingRef,
+    backoff: Backoff,
+    keepalive: Option<(Duration, Duration)>,
+    register_timeout: Duration,
+    stream: Option<MessageStream>,
+    writer: Option<Writer<S>>,
+    retry_at: Option<Instant>,
+    closing: bool,
+    nickname: String,
+    isupport: ISupport,
+    channels: BTreeMap<String, Option<String>>,
+    modes: BTreeSet<char>,
+}
+
+impl<S> Reconnecting<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    /// Creates a client connecting with `connector` and registering with `registration`.
+    pub fn new<C, F, R>(connector: C, encoding: EncodingRef, registration: R) -> Self
+    where
+        C: FnMut() -> F + Send + 'static,
+        F: Future<Output = Result<S, IoError>> + Send + 'static,
+        R: FnMut() -> Registration + Send + 'static,
+    {
+        let mut connector = connector;
+        Reconnecting {
+            connector: Box::new(move || Box::pin(connector())),
+            registration: Box::new(registration),
+            encoding,
+            backoff: Backoff::default(),
+            keepalive: None,
+            register_timeout: DEFAULT_REGISTER_TIMEOUT,
+            stream: None,
+            writer: None,
+            retry_at: None,
+            closing: false,
+            nickname: String::new(),
+            isupport: ISupport::new(),
+            channels: BTreeMap::new(),
+            modes: BTreeSet::new(),
+        }
+    }
+
+    /// Uses the given backoff between attempts.
+    pub fn backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Wraps every connection in a `Keepalive`.
+    pub fn keepalive(mut self, interval: Duration, timeout: Duration) -> Self {
+        self.keepalive = Some((interval, timeout));
+        self
+    }
+
+    /// Gives up on a connection when the registration takes longer than `timeout`.
+    ///
+    /// Defaults to `DEFAULT_REGISTER_TIMEOUT`.
+    pub fn register_timeout(mut self, timeout: Duration) -> Self {
+        self.register_timeout = timeout;
+        self
+    }
+
+    /// Writer of the current connection, if connected.
+    pub fn writer(&self) -> Option<Writer<S>> {
+        self.writer.clone()
+    }
+
+    /// Our current nickname.
+    pub fn nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    /// Channels we are in, with their keys.
+    pub fn channels(&self) -> &BTreeMap<String, Option<String>> {
+        &self.channels
+    }
+
+    /// Our user modes.
+    pub fn modes(&self) -> &BTreeSet<char> {
+        &self.modes
+    }
+
+    /// Joins the channel, remembering the key for later reconnections.
+    ///
+    /// When disconnected, the channel is joined once connected.
+    pub async fn join(&mut self, channel: &str, key: Option<&str>) -> Result<(), WriteError> {
+        let channel = self.channel_key(channel).unwrap_or_else(|| channel.into());
+        let entry = self.channels.entry(channel.clone()).or_insert(None);
+        if let Some(key) = key {
+            *entry = Some(key.into());
+        }
+        match self.writer {
+            Some(ref writer) => writer.join(&channel, key).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Casemapping of the current connection.
+    fn mapping(&self) -> CaseMapping {
+        self.isupport.casemapping()
+    }
+
+    /// Tracked channel equal to the given one under the casemapping.
+    fn channel_key(&self, channel: &str) -> Option<String> {
+        let mapping = self.mapping();
+        self.channels
+            .keys()
+            .find(|key| mapping.equals(key, channel))
+            .cloned()
+    }
+
+    fn remove_channel(&mut self, channel: &str) {
+        if let Some(key) = self.channel_key(channel) {
+            self.channels.remove(&key);
+        }
+    }
+
+    /// Stops reconnecting. The current connection is left as is.
+    pub fn close(&mut self) {
+        self.closing = true;
+    }
+
+    /// Sends `QUIT` and stops reconnecting.
+    pub async fn quit(&mut self, message: Option<&str>) -> Result<(), WriteError> {
+        self.closing = true;
+        match self.writer {
+            Some(ref writer) => writer.quit(message).await,
+            None => Ok(()),
+        }
+    }
+
+    /// Waits for the next event, connecting when needed.
+    ///
+    /// Returns `None` once closed and disconnected.
+    ///
+    /// Dropping the future while waiting to reconnect is safe: the next call
+    /// waits for the rest of the delay. Dropping it while connecting drops
+    /// the connection in progress without an event, and the next call
+    /// connects again at once.
+    pub async fn next(&mut self) -> Option<Event> {
+        if let Some(ref mut stream) = self.stream {
+            let reason = match stream.next().await {
+                Some(Ok(msg)) => {
+                    self.track(&msg);
+                    return Some(Event::Message(msg));
+                }
+                Some(Err(e @ StreamError::ParseError(_))) => return Some(Event::ParseError(e)),
+                Some(Err(e)) => DisconnectReason::Stream(e),
+                None => DisconnectReason::Closed,
+            };
+            return self.disconnected(reason);
+        }
+        if self.closing {
+            return None;
+        }
+
+        if let Some(retry_at) = self.retry_at {
+            Delay::new(retry_at.saturating_duration_since(Instant::now())).await;
+            self.retry_at = None;
+        }
+        match self.connect().await {
+            Ok(registered) => {
+                self.backoff.reset();
+                Some(Event::Connected(registered))
+            }
+            Err(reason) => self.disconnected(reason),
+        }
+    }
+
+    fn disconnected(&mut self, reason: DisconnectReason) -> Option<Event> {
+        self.stream = None;
+        self.writer = None;
+        if self.closing {
+            return None;
+        }
+        let retry_in = self.backoff.next_delay();
+        self.retry_at = Some(Instant::now() + retry_in);
+        Some(Event::Disconnected { reason, retry_in })
+    }
+
+    async fn connect(&mut self) -> Result<Registered, DisconnectReason> {
+        let stream = (self.connector)()
+            .await
+            .map_err(DisconnectReason::Connect)?;
+        let mut stream = IrcStream::new(stream, self.encoding);
+        let writer = stream.writer();
+        let mut registration = (self.registration)();
+        let register = Box::pin(registration.register(&mut stream, &writer));
+        let registered = match future::select(register, Delay::new(self.register_timeout)).await {
+            Either::Left((registered, _)) => registered.map_err(DisconnectReason::Register)?,
+            Either::Right(_) => return Err(DisconnectReason::RegisterTimeout),
+        };
+        self.nickname = registered.nickname.clone();
+        self.isupport = ISupport::new();
+
+        let modes = self
+            .modes
+            .iter()
+            .filter(|mode| !SERVER_MODES.contains(mode))
+            .collect::<String>();
+        if !modes.is_empty() {
+            let mode = Command::Mode {
+                target: self.nickname.clone(),
+                changes: vec![format!("+{}", modes)],
+            };
+            writer
+                .send(&mode.into())
+                .await
+                .map_err(DisconnectReason::Write)?;
+        }
+        for (channel, key) in &self.channels {
+            writer
+                .join(channel, key.as_deref())
+                .await
+                .map_err(DisconnectReason::Write)?;
+        }
+
+        self.stream = Some(match self.keepalive {
+            Some((interval, timeout)) => Box::pin(Keepalive::new(stream, interval, timeout)),
+            None => Box::pin(stream),
+        });
+        self.writer = Some(writer);
+        Ok(registered)
+    }
+
+    /// Keeps track of our nickname, channels and user modes.
+    fn track(&mut self, msg: &Message) {
+        self.isupport.handle(msg);
+        let mapping = self.mapping();
+        let is_us = |nickname: &str| mapping.equals(nickname, &self.nickname);
+        let from_us = match msg.prefix {
+            Some(Prefix::User(ref user)) => is_us(&user.nickname),
+            _ => false,
+        };
+        let arg = |i: usize| msg.args.get(i).map(String::as_str).unwrap_or("");
+
+        match msg.code {
+            Code::Join if from_us => {
+                for channel in arg(0).split(',') {
+                    if self.channel_key(channel).is_none() {
+                        self.channels.insert(channel.into(), None);
+                    }
+                }
+            }
+            Code::Part if from_us => {
+                for channel in arg(0).split(',') {
+                    self.remove_channel(channel);
+                }
+            }
+            Code::Kick if is_us(arg(1)) => self.remove_channel(arg(0)),
+            // Do not replay a join that failed.
+            Code::ErrChannelisfull
+            | Code::ErrInviteonlychan
+            | Code::ErrBannedfromchan
+            | Code::ErrBadchannelkey => self.remove_channel(arg(1)),
+            Code::Nick if from_us => self.nickname = arg(0).into(),
+            Code::Mode if is_us(arg(0)) => apply_user_modes(&mut self.modes, arg(1)),
+            Code::RplUmodeis => {
+                self.modes.clear();
+                apply_user_modes(&mut self.modes, arg(1));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[test]
+fn test_backoff() {
+    let mut backoff = Backoff::new(Duration::from_secs(1), Duration::from_secs(10));
+    for &max in &[1, 2, 4, 8, 10, 10] {
+        let delay = backoff.next_delay();
+        assert!(delay >= Duration::from_secs(max) / 2);
+        assert!(delay <= Duration::from_secs(max));
+    }
+    backoff.reset();
+    assert!(backoff.next_delay() <= Duration::from_secs(1));
+}
+
+#[test]
+fn test_reconnect() {
+    use {
+        crate::mock::{MockStream, Output},
+        futures::executor::block_on,
+        std::sync::{Arc, Mutex},
+    };
+
+    let outputs = Arc::new(Mutex::new(Vec::<Output>::new()));
+    let inputs = Arc::new(Mutex::new(vec![
+        ":irc.example.com 001 bot :Welcome\r\n",
+        "",
+        ":irc.example.com 001 bot :Welcome\r\n\
+         :bot!bot@localhost JOIN #chan\r\n\
+         :bot!bot@localhost JOIN #other\r\n\
+         :bot MODE bot :+iw\r\n\
+         :bot!bot@localhost PART #other\r\n",
+    ]));
+    let connector = {
+        let outputs = outputs.clone();
+        move || {
+            let input = inputs.lock().unwrap().pop();
+            let outputs = outputs.clone();
+            async move {
+                match input {
+                    Some(input) if !input.is_empty() => {
+                        let (mock, output) = MockStream::new(input);
+                        outputs.lock().unwrap().push(output);
+                        Ok(mock)
+                    }
+                    _ => Err(IoError::new(
+                        std::io::ErrorKind::ConnectionRefused,
+                        "refused",
+                    )),
+                }
+            }
+        }
+    };
+    let mut client = Reconnecting::new(connector, encoding::all::UTF_8, || {
+        Registration::new("bot", "bot", "A bot")
+    })
+    .backoff(Backoff::new(
+        Duration::from_millis(1),
+        Duration::from_millis(1),
+    ));
+
+    block_on(async {
+        assert!(matches!(client.next().await, Some(Event::Connected(_))));
+        client.join("#chan", Some("secret")).await.unwrap();
+        for _ in 0..4 {
+            assert!(matches!(client.next().await, Some(Event::Message(_))));
+        }
+        assert!(matches!(
+            client.next().await,
+            Some(Event::Disconnected {
+                reason: DisconnectReason::Closed,
+                ..
+            })
+        ));
+        assert!(matches!(
+            client.next().await,
+            Some(Event::Disconnected {
+                reason: DisconnectReason::Connect(_),
+                ..
+            })
+        ));
+        assert!(matches!(client.next().await, Some(Event::Connected(_))));
+        client.quit(None).await.unwrap();
+        assert!(client.next().await.is_none());
+    });
+
+    assert_eq!(client.modes().iter().collect::<String>(), "iw");
+    let outputs = outputs.lock().unwrap();
+    assert_eq!(
+        outputs[1].get(),
+        "NICK bot\r\n\
+         USER bot 0 * :A bot\r\n\
+         MODE bot +iw\r\n\
+         JOIN #chan secret\r\n\
+         QUIT\r\n"
+    );
+}
+
+#[test]
+fn test_join_disconnected() {
+    use {
+        crate::mock::{MockStream, Output},
+        futures::executor::block_on,
+        std::sync::{Arc, Mutex},
+    };
+
+    let outputs = Arc::new(Mutex::new(Vec::<Output>::new()));
+    let connector = {
+        let outputs = outputs.clone();
+        move || {
+            let (mock, output) = MockStream::new(
+                ":irc.example.com 001 Bot :Welcome\r\n\
+                 :irc.example.com 005 Bot CASEMAPPING=rfc1459 :are supported by this server\r\n\
+                 :bot!bot@localhost PART #Chan\r\n\
+                 :irc.example.com KICK #Other BOT :bye\r\n\
+                 :bot MODE BOT :+i\r\n",
+            );
+            outputs.lock().unwrap().push(output);
+            future::ready(Ok(mock))
+        }
+    };
+    let mut client = Reconnecting::new(connector, encoding::all::UTF_8, || {
+        Registration::new("Bot", "bot", "A bot")
+    });
+
+    block_on(async {
+        client.join("#chan", None).await.unwrap();
+        client.join("#other", Some("secret")).await.unwrap();
+        client.join("#OTHER", None).await.unwrap();
+        assert_eq!(client.channels().len(), 2);
+        assert!(matches!(client.next().await, Some(Event::Connected(_))));
+        for _ in 0..4 {
+            assert!(matches!(client.next().await, Some(Event::Message(_))));
+        }
+    });
+
+    assert!(client.channels().is_empty());
+    assert_eq!(client.modes().iter().collect::<String>(), "i");
+    assert_eq!(
+        outputs.lock().unwrap()[0].get(),
+        "NICK Bot\r\n\
+         USER bot 0 * :A bot\r\n\
+         JOIN #chan\r\n\
+         JOIN #other secret\r\n"
+    );
+}
+
+#[test]
+fn test_register_timeout() {
+    use {crate::mock::MockStream, futures::executor::block_on};
+
+    let mut client = Reconnecting::new(
+        || future::ready(Ok(MockStream::new("").0.hang())),
+        encoding::all::UTF_8,
+        || Registration::new("bot", "bot", "A bot"),
+    )
+    .register_timeout(Duration::from_millis(10));
+    assert!(matches!(
+        block_on(client.next()),
+        Some(Event::Disconnected {
+            reason: DisconnectReason::RegisterTimeout,
+            ..
+        })
+    ));
+}
+
+#[test]
+fn test_cancel_retry() {
+    use {crate::mock::MockStream, futures::executor::block_on};
+
+    let mut client = Reconnecting::new(
+        || {
+            future::ready(Err::<MockStream, _>(IoError::from(
+                std::io::ErrorKind::ConnectionRefused,
+            )))
+        },
+        encoding::all::UTF_8,
+        || Registration::new("bot", "bot", "A bot"),
+    )
+    .backoff(Backoff::new(
+        Duration::from_secs(10),
+        Duration::from_secs(10),
+    ));
+
+    block_on(async {
+        assert!(matches!(
+            client.next().await,
+            Some(Event::Disconnected {
+                reason: DisconnectReason::Connect(_),
+                ..
+            })
+        ));
+        let next = Box::pin(client.next());
+        let cancelled = future::select(next, Delay::new(Duration::from_millis(10))).await;
+        assert!(matches!(cancelled, Either::Right(_)));
+    });
+    assert!(client.retry_at.is_some());
+}
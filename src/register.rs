@@ -0,0 +1,550 @@
+//! Connection registration, with nickname collision fallback.
+
+use {
+    crate::{
+        cap::{CapNegotiator, CapState},
+        code::Code,
+        command::Command,
+        message::{Message, Prefix},
+        sasl::{Sasl, SaslError, SaslState},
+        stream::{StreamError, WriteError, Writer},
+    },
+    futures::{io::AsyncWrite, prelude::*},
+    std::fmt,
+};
+
+/// Number of nicknames generated with `NickSuffix` before giving up.
+const MAX_SUFFIXED: usize = 9;
+
+/// Error generated during the registration.
+#[derive(Debug)]
+pub enum RegisterError {
+    /// Every alternative nickname is in use or invalid.
+    NicknamesExhausted,
+    /// Server rejected the connection password (`ERR_PASSWDMISMATCH`).
+    PasswordMismatch,
+    /// Server closed the connection, with the message of `ERROR` if any.
+    Closed(Option<String>),
+    /// Error reading from the stream.
+    StreamError(StreamError),
+    /// Error writing to the stream.
+    WriteError(WriteError),
+}
+
+impl From<StreamError> for RegisterError {
+    fn from(err: StreamError) -> Self {
+        RegisterError::StreamError(err)
+    }
+}
+
+impl From<WriteError> for RegisterError {
+    fn from(err: WriteError) -> Self {
+        RegisterError::WriteError(err)
+    }
+}
+
+impl fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            RegisterError::NicknamesExhausted => write!(f, "no nickname available"),
+            RegisterError::PasswordMismatch => write!(f, "password incorrect"),
+            RegisterError::Closed(Some(ref message)) => {
+                write!(f, "connection closed: {}", message)
+            }
+            RegisterError::Closed(None) => write!(f, "connection closed"),
+            RegisterError::StreamError(ref e) => write!(f, "StreamError: {}", e),
+            RegisterError::WriteError(ref e) => write!(f, "WriteError: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+/// Suffix appended to the nickname once the alternatives are exhausted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum NickSuffix {
+    /// Gives up once the alternatives are exhausted.
+    None,
+    /// `nick_`, `nick__`, ...
+    Underscores,
+    /// `nick1`, `nick2`, ...
+    Digits,
+}
+
+/// Result of a successful registration.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Registered {
+    /// Nickname confirmed by `RPL_WELCOME`.
+    pub nickname: String,
+    /// Name of the server that sent `RPL_WELCOME`.
+    pub server: String,
+    /// Account we are logged in as, if SASL succeeded.
+    pub account: Option<String>,
+}
+
+/// Registration state machine.
+///
+/// Send the messages returned by `start`, then feed every message received
+/// from the server to `handle` and send the messages it returns, until it
+/// returns `Registered`. `PING`s received in the meantime are answered.
+///
+/// When a `CapNegotiator` is given, capabilities are negotiated first, and
+/// when a `Sasl` is given as well, authentication happens before `CAP END`.
+/// When the server does not support capabilities, registration goes on
+/// without authenticating.
+#[derive(Debug)]
+pub struct Registration {
+    wanted: String,
+    username: String,
+    realname: String,
+    password: Option<String>,
+    alternatives: Vec<String>,
+    suffix: NickSuffix,
+    cap: Option<CapNegotiator>,
+    sasl: Option<Sasl>,
+    /// `AUTHENTICATE *` was sent, and `CAP END` waits for the server to confirm.
+    aborting: bool,
+    attempt: usize,
+    nickname: String,
+}
+
+impl Registration {
+    /// Creates a registration with the given nickname, username and realname.
+    pub fn new(
+        nickname: impl Into<String>,
+        username: impl Into<String>,
+        realname: impl Into<String>,
+    ) -> Self {
+        let nickname = nickname.into();
+        Registration {
+            nickname: nickname.clone(),
+            wanted: nickname,
+            username: username.into(),
+            realname: realname.into(),
+            password: None,
+            alternatives: Vec::new(),
+            suffix: NickSuffix::Underscores,
+            cap: None,
+            sasl: None,
+            aborting: false,
+            attempt: 0,
+        }
+    }
+
+    /// Sends `PASS` with the given connection password.
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Nicknames tried, in order, when the nickname is not available.
+    pub fn alternatives<I>(mut self, alternatives: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        self.alternatives = alternatives.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Suffix appended to the nickname once the alternatives are exhausted.
+    ///
+    /// Defaults to `NickSuffix::Underscores`.
+    pub fn suffix(mut self, suffix: NickSuffix) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// Negotiates capabilities before registering.
+    pub fn cap(mut self, cap: CapNegotiator) -> Self {
+        self.cap = Some(cap);
+        self
+    }
+
+    /// Authenticates with SASL before registering.
+    ///
+    /// The `sasl` capability is requested when no `CapNegotiator` is given.
+    pub fn sasl(mut self, sasl: Sasl) -> Self {
+        self.sasl = Some(sasl);
+        self
+    }
+
+    /// Nickname we are currently trying.
+    pub fn nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    /// Capability negotiator, if any.
+    pub fn cap_negotiator(&self) -> Option<&CapNegotiator> {
+        self.cap.as_ref()
+    }
+
+    /// SASL state machine, if any.
+    pub fn sasl_state(&self) -> Option<&Sasl> {
+        self.sasl.as_ref()
+    }
+
+    /// Starts the registration, returning `[PASS]`, `[CAP LS]`, `NICK` and `USER`.
+    pub fn start(&mut self) -> Vec<Message> {
+        let mut out = Vec::new();
+        if let Some(ref password) = self.password {
+            out.push(
+                Command::Pass {
+                    password: password.clone(),
+                }
+                .into(),
+            );
+        }
+        if self.sasl.is_some() {
+            let cap = self
+                .cap
+                .take()
+                .unwrap_or_else(|| CapNegotiator::new(vec!["sasl"]));
+            self.cap = Some(cap.hold_end());
+        }
+        if let Some(ref mut cap) = self.cap {
+            out.push(cap.start());
+        }
+        self.aborting = false;
+        self.attempt = 0;
+        self.nickname = self.wanted.clone();
+        out.push(self.nick());
+        out.push(
+            Command::User {
+                username: self.username.clone(),
+                mode: "0".into(),
+                realname: self.realname.clone(),
+            }
+            .into(),
+        );
+        out
+    }
+
+    fn nick(&self) -> Message {
+        Command::Nick {
+            nickname: self.nickname.clone(),
+        }
+        .into()
+    }
+
+    /// Moves to the next alternative nickname.
+    fn next_nickname(&mut self) -> Option<String> {
+        self.attempt += 1;
+        if let Some(nickname) = self.alternatives.get(self.attempt - 1) {
+            return Some(nickname.clone());
+        }
+        let n = self.attempt - self.alternatives.len();
+        if n > MAX_SUFFIXED {
+            return None;
+        }
+        match self.suffix {
+            NickSuffix::None => None,
+            NickSuffix::Underscores => Some(format!("{}{}", self.wanted, "_".repeat(n))),
+            NickSuffix::Digits => Some(format!("{}{}", self.wanted, n)),
+        }
+    }
+
+    /// Handles a message from the server, returning the messages to send.
+    ///
+    /// Returns `Registered` along with the messages once `RPL_WELCOME` is received.
+    pub fn handle(
+        &mut self,
+        msg: &Message,
+    ) -> Result<(Vec<Message>, Option<Registered>), RegisterError> {
+        let mut out = Vec::new();
+
+        if let Some(ref mut cap) = self.cap {
+            out.extend(cap.handle(msg));
+        }
+        if let Some(ref mut sasl) = self.sasl {
+            match sasl.state() {
+                // A server without capabilities ends the negotiation
+                // without `CAP END`: registration goes on without an account.
+                SaslState::Idle => {
+                    if let Some(ref mut cap) = self.cap {
+                        if cap.state() == CapState::Negotiated {
+                            if cap.is_enabled("sasl") {
+                                out.push(sasl.start());
+                            } else {
+                                out.extend(cap.end());
+                            }
+                        }
+                    }
+                }
+                SaslState::Authenticating => {
+                    match sasl.handle(msg) {
+                        Ok(messages) => out.extend(messages),
+                        Err(SaslError::InvalidPayload) | Err(SaslError::Mechanism(_)) => {
+                            out.push(sasl.abort());
+                            self.aborting = true;
+                        }
+                        // Registration goes on without an account.
+                        Err(_) => {}
+                    }
+                    if sasl.is_done() && !self.aborting {
+                        out.extend(self.cap.as_mut().and_then(CapNegotiator::end));
+                    }
+                }
+                SaslState::Succeeded | SaslState::Failed => {
+                    // Keep track of RPL_LOGGEDIN and RPL_LOGGEDOUT.
+                    let _ = sasl.handle(msg);
+                    // The server answers `AUTHENTICATE *` with ERR_SASLABORTED,
+                    // or ERR_SASLFAIL if the exchange was already over.
+                    if self.aborting
+                        && (msg.code == Code::ErrSaslaborted || msg.code == Code::ErrSaslfail)
+                    {
+                        self.aborting = false;
+                        out.extend(self.cap.as_mut().and_then(CapNegotiator::end));
+                    }
+                }
+            }
+        }
+
+        match msg.code {
+            Code::Ping => out.push(
+                Command::Pong {
+                    server: msg.args.first().cloned().unwrap_or_default(),
+                    token: None,
+                }
+                .into(),
+            ),
+            // ERR_UNAVAILRESOURCE may also be about a channel.
+            Code::ErrUnavailresource if msg.args.get(1) != Some(&self.nickname) => {}
+            Code::ErrNicknameinuse
+            | Code::ErrErroneousnickname
+            | Code::ErrNickcollision
+            | Code::ErrUnavailresource => {
+                self.nickname = self
+                    .next_nickname()
+                    .ok_or(RegisterError::NicknamesExhausted)?;
+                out.push(self.nick());
+            }
+            Code::ErrPasswdmismatch => return Err(RegisterError::PasswordMismatch),
+            Code::Error => {
+                return Err(RegisterError::Closed(msg.args.first().cloned()));
+            }
+            Code::RplWelcome => {
+                if let Some(nickname) = msg.args.first() {
+                    self.nickname = nickname.clone();
+                }
+                let server = match msg.prefix {
+                    Some(Prefix::Server(ref server)) => server.clone(),
+                    _ => String::new(),
+                };
+                let registered = Registered {
+                    nickname: self.nickname.clone(),
+                    server,
+                    account: self.sasl.as_ref().and_then(Sasl::account).map(Into::into),
+                };
+                return Ok((out, Some(registered)));
+            }
+            _ => {}
+        }
+        Ok((out, None))
+    }
+
+    /// Registers over the stream, writing through the writer.
+    ///
+    /// Messages received before `RPL_WELCOME` are consumed.
+    pub async fn register<St, S>(
+        &mut self,
+        stream: &mut St,
+        writer: &Writer<S>,
+    ) -> Result<Registered, RegisterError>
+    where
+        St: Stream<Item = Result<Message, StreamError>> + Unpin,
+        S: AsyncWrite + Unpin,
+    {
+        for msg in self.start() {
+            writer.send(&msg).await?;
+        }
+        while let Some(msg) = stream.next().await {
+            let (out, registered) = self.handle(&msg?)?;
+            for msg in out {
+                writer.send(&msg).await?;
+            }
+            if let Some(registered) = registered {
+                return Ok(registered);
+            }
+        }
+        Err(RegisterError::Closed(None))
+    }
+}
+
+#[cfg(test)]
+fn handle_line(reg: &mut Registration, line: &str) -> Vec<String> {
+    reg.handle(&Message::parse(line).unwrap())
+        .unwrap()
+        .0
+        .iter()
+        .map(ToString::to_string)
+        .collect()
+}
+
+#[test]
+fn test_start() {
+    let mut reg = Registration::new("bot", "bot", "A bot").password("secret");
+    let out = reg
+        .start()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(out, vec!["PASS secret", "NICK bot", "USER bot 0 * :A bot"]);
+}
+
+#[test]
+fn test_nick_fallback() {
+    let mut reg = Registration::new("bot", "bot", "A bot").alternatives(vec!["robot"]);
+    reg.start();
+    assert_eq!(
+        handle_line(
+            &mut reg,
+            ":irc.example.com 433 * bot :Nickname is already in use"
+        ),
+        vec!["NICK robot"]
+    );
+    assert_eq!(
+        handle_line(&mut reg, ":irc.example.com 432 * robot :Erroneous nickname"),
+        vec!["NICK bot_"]
+    );
+    assert_eq!(
+        handle_line(
+            &mut reg,
+            ":irc.example.com 433 * bot_ :Nickname is already in use"
+        ),
+        vec!["NICK bot__"]
+    );
+    assert!(handle_line(
+        &mut reg,
+        ":irc.example.com 437 * #chan :Nick/channel is temporarily unavailable"
+    )
+    .is_empty());
+    assert_eq!(reg.nickname(), "bot__");
+
+    let mut reg = Registration::new("bot", "bot", "A bot").suffix(NickSuffix::Digits);
+    reg.start();
+    assert_eq!(
+        handle_line(
+            &mut reg,
+            ":irc.example.com 433 * bot :Nickname is already in use"
+        ),
+        vec!["NICK bot1"]
+    );
+
+    let mut reg = Registration::new("bot", "bot", "A bot").suffix(NickSuffix::None);
+    reg.start();
+    let msg = Message::parse(":irc.example.com 433 * bot :Nickname is already in use").unwrap();
+    assert!(matches!(
+        reg.handle(&msg),
+        Err(RegisterError::NicknamesExhausted)
+    ));
+}
+
+#[test]
+fn test_welcome() {
+    let mut reg = Registration::new("bot", "bot", "A bot");
+    reg.start();
+    assert_eq!(
+        handle_line(&mut reg, "PING :irc.example.com"),
+        vec!["PONG irc.example.com"]
+    );
+    let msg = Message::parse(":irc.example.com 001 bot :Welcome to the network, bot").unwrap();
+    let (_, registered) = reg.handle(&msg).unwrap();
+    assert_eq!(
+        registered,
+        Some(Registered {
+            nickname: "bot".into(),
+            server: "irc.example.com".into(),
+            account: None,
+        })
+    );
+
+    let msg = Message::parse("ERROR :Closing Link: bot (Banned)").unwrap();
+    assert!(matches!(
+        reg.handle(&msg),
+        Err(RegisterError::Closed(Some(_)))
+    ));
+}
+
+#[test]
+fn test_sasl_abort() {
+    use crate::sasl::Plain;
+
+    let mut reg = Registration::new("bot", "bot", "A bot").sasl(Sasl::new(Plain::new("bot", "pw")));
+    reg.start();
+    handle_line(&mut reg, ":irc.example.com CAP * LS :sasl");
+    assert_eq!(
+        handle_line(&mut reg, ":irc.example.com CAP * ACK :sasl"),
+        vec!["AUTHENTICATE PLAIN"]
+    );
+    // Invalid base64.
+    assert_eq!(
+        handle_line(&mut reg, "AUTHENTICATE !!!"),
+        vec!["AUTHENTICATE *"]
+    );
+    // CAP END waits for the server to confirm the abort.
+    assert_eq!(
+        handle_line(&mut reg, "PING :irc.example.com"),
+        vec!["PONG irc.example.com"]
+    );
+    assert_eq!(
+        handle_line(
+            &mut reg,
+            ":irc.example.com 906 bot :SASL authentication aborted"
+        ),
+        vec!["CAP END"]
+    );
+}
+
+#[test]
+fn test_sasl_without_cap() {
+    use crate::sasl::Plain;
+
+    let mut reg = Registration::new("bot", "bot", "A bot").sasl(Sasl::new(Plain::new("bot", "pw")));
+    reg.start();
+    assert!(handle_line(&mut reg, ":irc.example.com 421 bot CAP :Unknown command").is_empty());
+    assert!(handle_line(&mut reg, ":irc.example.com NOTICE * :hello").is_empty());
+    assert_eq!(reg.sasl_state().unwrap().state(), SaslState::Idle);
+    let msg = Message::parse(":irc.example.com 001 bot :Welcome to the network, bot").unwrap();
+    let (out, registered) = reg.handle(&msg).unwrap();
+    assert!(out.is_empty());
+    assert_eq!(registered.unwrap().account, None);
+}
+
+#[test]
+fn test_register_stream() {
+    use {
+        crate::{mock::MockStream, sasl::Plain, stream::IrcStream},
+        futures::executor::block_on,
+    };
+
+    let (mock, output) = MockStream::new(
+        ":irc.example.com CAP * LS :multi-prefix sasl=PLAIN\r\n\
+         :irc.example.com CAP * ACK :sasl\r\n\
+         AUTHENTICATE +\r\n\
+         :irc.example.com 900 bot bot!bot@localhost bot :You are now logged in as bot\r\n\
+         :irc.example.com 903 bot :SASL authentication successful\r\n\
+         :irc.example.com 433 * bot :Nickname is already in use\r\n\
+         :irc.example.com 001 bot_ :Welcome to the network, bot_\r\n\
+         :irc.example.com 002 bot_ :Your host is irc.example.com\r\n",
+    );
+    let mut stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    let mut reg = Registration::new("bot", "bot", "A bot").sasl(Sasl::new(Plain::new("bot", "pw")));
+    let registered = block_on(reg.register(&mut stream, &writer)).unwrap();
+    assert_eq!(registered.nickname, "bot_");
+    assert_eq!(registered.account.as_deref(), Some("bot"));
+    assert_eq!(
+        output.get(),
+        "CAP LS 302\r\n\
+         NICK bot\r\n\
+         USER bot 0 * :A bot\r\n\
+         CAP REQ sasl\r\n\
+         AUTHENTICATE PLAIN\r\n\
+         AUTHENTICATE AGJvdABwdw==\r\n\
+         CAP END\r\n\
+         NICK bot_\r\n"
+    );
+    // The rest of the stream is left untouched.
+    assert!(block_on(stream.next()).is_some());
+}
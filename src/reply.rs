@@ -0,0 +1,194 @@
+//! Typed accessors for replies and errors.
+//!
+//! Each reply or error with parameters described in `gen/codes.txt` gets a
+//! struct borrowing its arguments, and a method on `Message` to interpret the
+//! message as such, e.g. `msg.as_whoisuser()?.realname`.
+
+use {
+    crate::{code::Code, message::Message},
+    std::fmt,
+};
+
+/// Error generated when interpreting a `Message` as a specific reply.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ReplyError {
+    /// Message has another code.
+    UnexpectedCode(Code),
+    /// Required argument is missing.
+    MissingArgument {
+        /// Code of the message
+        code: Code,
+        /// Position of the missing argument
+        index: usize,
+        /// Name of the missing argument
+        name: &'static str,
+    },
+}
+
+impl fmt::Display for ReplyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReplyError::UnexpectedCode(ref code) => write!(f, "Unexpected code {}", code),
+            ReplyError::MissingArgument {
+                ref code,
+                index,
+                name,
+            } => write!(f, "{} is missing argument {} <{}>", code, index, name),
+        }
+    }
+}
+
+impl std::error::Error for ReplyError {}
+
+/// Checks the code of the message and that it has at least the required arguments.
+///
+/// Returns the number of arguments.
+fn check(msg: &Message, code: &Code, required: &[&'static str]) -> Result<usize, ReplyError> {
+    if msg.code != *code {
+        return Err(ReplyError::UnexpectedCode(msg.code.clone()));
+    }
+    let len = msg.args.len();
+    match required.get(len) {
+        Some(name) => Err(ReplyError::MissingArgument {
+            code: code.clone(),
+            index: len,
+            name,
+        }),
+        None => Ok(len),
+    }
+}
+
+include!(concat!(env!("OUT_DIR"), "/reply.rs"));
+
+#[test]
+fn test_whoisuser() {
+    let msg = Message::parse(":irc.example.com 311 me bob ~bob bob.com * :Bob Bobson").unwrap();
+    let reply = msg.as_whoisuser().unwrap();
+    assert_eq!(reply.client, "me");
+    assert_eq!(reply.nick, "bob");
+    assert_eq!(reply.username, "~bob");
+    assert_eq!(reply.host, "bob.com");
+    assert_eq!(reply.realname, "Bob Bobson");
+}
+
+#[test]
+fn test_optional() {
+    let msg = Message::parse(":irc.example.com 317 me bob 42 1574000000 :seconds idle").unwrap();
+    let reply = msg.as_whoisidle().unwrap();
+    assert_eq!(reply.secs, "42");
+    assert_eq!(reply.signon, Some("1574000000"));
+
+    let msg = Message::parse(":irc.example.com 317 me bob 42 :seconds idle").unwrap();
+    let reply = msg.as_whoisidle().unwrap();
+    assert_eq!(reply.signon, None);
+}
+
+#[test]
+fn test_variadic() {
+    let msg = Message::parse(":irc.example.com 324 me #chan +kl secret 10").unwrap();
+    let reply = msg.as_channelmodeis().unwrap();
+    assert_eq!(reply.channel, "#chan");
+    assert_eq!(reply.modestring, "+kl");
+    assert_eq!(
+        reply.mode_args,
+        &["secret".to_string(), "10".to_string()][..]
+    );
+}
+
+#[test]
+fn test_error() {
+    let msg = Message::parse(":irc.example.com 433 * bob :Nickname is already in use").unwrap();
+    assert_eq!(msg.as_err_nicknameinuse().unwrap().nick, "bob");
+}
+
+#[test]
+fn test_missing_argument() {
+    let msg = Message::parse(":irc.example.com 311 me bob ~bob").unwrap();
+    assert_eq!(
+        msg.as_whoisuser(),
+        Err(ReplyError::MissingArgument {
+            code: Code::RplWhoisuser,
+            index: 3,
+            name: "host",
+        })
+    );
+}
+
+#[test]
+fn test_unexpected_code() {
+    let msg = Message::parse(":irc.example.com 001 me :Welcome").unwrap();
+    assert_eq!(
+        msg.as_whoisuser(),
+        Err(ReplyError::UnexpectedCode(Code::RplWelcome))
+    );
+}
+
+#[test]
+fn test_alias() {
+    let msg = Message::parse(
+        ":irc.example.com 005 me CHANTYPES=# NICKLEN=30 :are supported by this server",
+    )
+    .unwrap();
+    assert_eq!(msg.code, Code::RplIsupport);
+    assert_eq!(msg.code, Code::RplBounce);
+    assert!(matches!(msg.code, Code::RplBounce));
+    assert_eq!(
+        msg.as_isupport().unwrap().tokens,
+        ["CHANTYPES=#", "NICKLEN=30"]
+    );
+    assert_eq!(msg.as_bounce(), msg.as_isupport());
+
+    let msg = Message::parse(":irc.example.com 462 me :You may not reregister").unwrap();
+    assert_eq!(msg.code, Code::ErrAlreadyregistered);
+    assert!(msg.as_err_alreadyregistered().is_ok());
+}
+
+#[test]
+fn test_modern() {
+    let msg = Message::parse(":irc.example.com 330 me bob bobby :is logged in as").unwrap();
+    assert_eq!(msg.as_whoisaccount().unwrap().account, "bobby");
+    let msg = Message::parse(":irc.example.com 730 me :alice!a@host,bob!b@host").unwrap();
+    assert_eq!(
+        msg.as_mononline().unwrap().targets,
+        "alice!a@host,bob!b@host"
+    );
+    let msg = Message::parse(":irc.example.com 354 me 152 #chan bob").unwrap();
+    assert_eq!(msg.as_whospcrpl().unwrap().fields, ["152", "#chan", "bob"]);
+    for (line, code) in &[
+        (":bob!b@host ACCOUNT bobby", Code::Account),
+        (":bob!b@host CHGHOST ident new.host", Code::Chghost),
+        ("@+typing=active TAGMSG #chan", Code::Tagmsg),
+        (":irc.example.com BATCH +abc netsplit a b", Code::Batch),
+        (
+            ":irc.example.com FAIL CHATHISTORY INVALID_TARGET :Nope",
+            Code::Fail,
+        ),
+    ] {
+        assert_eq!(Message::parse(line).unwrap().code, *code);
+    }
+}
+
+#[test]
+fn test_trailing_omitted() {
+    let msg = Message::parse(":irc.example.com 318 me bob").unwrap();
+    assert_eq!(msg.as_endofwhois().unwrap().nick, "bob");
+    let msg = Message::parse(":irc.example.com 318 me bob :End of WHOIS").unwrap();
+    assert_eq!(msg.as_endofwhois().unwrap().nick, "bob");
+
+    let msg = Message::parse(":irc.example.com 317 me bob 42").unwrap();
+    let reply = msg.as_whoisidle().unwrap();
+    assert_eq!(reply.secs, "42");
+    assert_eq!(reply.signon, None);
+    let msg = Message::parse(":irc.example.com 317 me bob 42 :seconds idle").unwrap();
+    assert_eq!(msg.as_whoisidle().unwrap().signon, None);
+
+    let msg = Message::parse(":irc.example.com 311 me bob ~bob bob.com").unwrap();
+    assert_eq!(
+        msg.as_whoisuser(),
+        Err(ReplyError::MissingArgument {
+            code: Code::RplWhoisuser,
+            index: 4,
+            name: "*",
+        })
+    );
+}
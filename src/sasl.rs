@@ -0,0 +1,659 @@
+//! SASL authentication with `AUTHENTICATE`, as described in IRCv3 `sasl-3.1`.
+
+use {
+    crate::{
+        code::Code,
+        message::Message,
+        stream::{WriteError, Writer},
+    },
+    base64::{engine::general_purpose::STANDARD as BASE64, Engine},
+    futures::io::AsyncWrite,
+    hmac::{Hmac, Mac},
+    sha2::{Digest, Sha256},
+    std::fmt,
+};
+
+/// Maximum length of the payload in a single `AUTHENTICATE`.
+const CHUNK_LEN: usize = 400;
+
+/// Error generated by the SASL authentication.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SaslError {
+    /// Authentication failed (`ERR_SASLFAIL`).
+    Failed,
+    /// Authentication was rejected because the account is locked to another nick (`ERR_NICKLOCKED`).
+    NickLocked,
+    /// Message sent by the client was too long (`ERR_SASLTOOLONG`).
+    TooLong,
+    /// Authentication was aborted (`ERR_SASLABORTED`).
+    Aborted,
+    /// Client is already authenticated (`ERR_SASLALREADY`).
+    AlreadyAuthenticated,
+    /// Server sent a payload that is not valid base64.
+    InvalidPayload,
+    /// Mechanism could not process the challenge of the server.
+    Mechanism(String),
+}
+
+impl fmt::Display for SaslError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SaslError::Failed => write!(f, "SASL authentication failed"),
+            SaslError::NickLocked => write!(f, "nick is locked to another account"),
+            SaslError::TooLong => write!(f, "SASL message too long"),
+            SaslError::Aborted => write!(f, "SASL authentication aborted"),
+            SaslError::AlreadyAuthenticated => write!(f, "already authenticated"),
+            SaslError::InvalidPayload => write!(f, "invalid base64 payload"),
+            SaslError::Mechanism(ref message) => write!(f, "mechanism error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for SaslError {}
+
+/// SASL mechanism, computing responses to the challenges of the server.
+pub trait Mechanism {
+    /// Name of the mechanism, e.g. `PLAIN`.
+    fn name(&self) -> &str;
+
+    /// Computes the response to a decoded challenge. The first challenge is empty.
+    fn respond(&mut self, challenge: &[u8]) -> Result<Vec<u8>, SaslError>;
+}
+
+/// `PLAIN` mechanism, sending the password in clear text.
+#[derive(Clone, Debug)]
+pub struct Plain {
+    authzid: String,
+    authcid: String,
+    password: String,
+}
+
+impl Plain {
+    /// Creates the mechanism for the given account and password.
+    pub fn new(account: impl Into<String>, password: impl Into<String>) -> Self {
+        Plain {
+            authzid: String::new(),
+            authcid: account.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Authenticates as another identity.
+    pub fn authzid(mut self, authzid: impl Into<String>) -> Self {
+        self.authzid = authzid.into();
+        self
+    }
+}
+
+impl Mechanism for Plain {
+    fn name(&self) -> &str {
+        "PLAIN"
+    }
+
+    fn respond(&mut self, _challenge: &[u8]) -> Result<Vec<u8>, SaslError> {
+        Ok(format!("{}\0{}\0{}", self.authzid, self.authcid, self.password).into_bytes())
+    }
+}
+
+/// `EXTERNAL` mechanism, relying on a TLS client certificate.
+#[derive(Clone, Debug, Default)]
+pub struct External {
+    authzid: String,
+}
+
+impl External {
+    /// Creates the mechanism.
+    pub fn new() -> Self {
+        External::default()
+    }
+
+    /// Authenticates as another identity.
+    pub fn authzid(mut self, authzid: impl Into<String>) -> Self {
+        self.authzid = authzid.into();
+        self
+    }
+}
+
+impl Mechanism for External {
+    fn name(&self) -> &str {
+        "EXTERNAL"
+    }
+
+    fn respond(&mut self, _challenge: &[u8]) -> Result<Vec<u8>, SaslError> {
+        Ok(self.authzid.clone().into_bytes())
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ScramState {
+    Initial,
+    ClientFirst { bare: String },
+    ClientFinal { signature: Vec<u8> },
+    Done,
+}
+
+/// `SCRAM-SHA-256` mechanism, as described in RFC 7677.
+#[derive(Clone, Debug)]
+pub struct ScramSha256 {
+    username: String,
+    password: String,
+    nonce: String,
+    state: ScramState,
+}
+
+impl ScramSha256 {
+    /// Creates the mechanism for the given account and password.
+    pub fn new(account: impl Into<String>, password: impl Into<String>) -> Self {
+        let mut nonce = [0; 18];
+        getrandom::getrandom(&mut nonce).expect("failed to generate a nonce");
+        Self::with_nonce(account, password, BASE64.encode(nonce))
+    }
+
+    /// Creates the mechanism with a fixed client nonce.
+    ///
+    /// The nonce must be unpredictable, so this is only useful for testing.
+    pub fn with_nonce(
+        account: impl Into<String>,
+        password: impl Into<String>,
+        nonce: impl Into<String>,
+    ) -> Self {
+        ScramSha256 {
+            username: account.into(),
+            password: password.into(),
+            nonce: nonce.into(),
+            state: ScramState::Initial,
+        }
+    }
+}
+
+/// GS2 header: no channel binding, no authzid.
+const GS2_HEADER: &str = "n,,";
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Looks up an attribute in a SCRAM message, e.g. `r` in `r=abc,s=def`.
+fn attribute(message: &str, name: char) -> Option<&str> {
+    message.split(',').find_map(|attr| {
+        let mut chars = attr.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), Some('=')) if c == name => Some(&attr[2..]),
+            _ => None,
+        }
+    })
+}
+
+/// Compares two byte strings without returning early on the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn scram_error(message: &str) -> SaslError {
+    SaslError::Mechanism(message.to_string())
+}
+
+impl Mechanism for ScramSha256 {
+    fn name(&self) -> &str {
+        "SCRAM-SHA-256"
+    }
+
+    fn respond(&mut self, challenge: &[u8]) -> Result<Vec<u8>, SaslError> {
+        let challenge =
+            std::str::from_utf8(challenge).map_err(|_| scram_error("challenge is not UTF-8"))?;
+        match std::mem::replace(&mut self.state, ScramState::Done) {
+            ScramState::Initial => {
+                let username = self.username.replace('=', "=3D").replace(',', "=2C");
+                let bare = format!("n={},r={}", username, self.nonce);
+                let response = format!("{}{}", GS2_HEADER, bare);
+                self.state = ScramState::ClientFirst { bare };
+                Ok(response.into_bytes())
+            }
+            ScramState::ClientFirst { bare } => {
+                if attribute(challenge, 'm').is_some() {
+                    return Err(scram_error("unsupported mandatory extension"));
+                }
+                let nonce =
+                    attribute(challenge, 'r').ok_or_else(|| scram_error("missing nonce"))?;
+                if !nonce.starts_with(&self.nonce) || nonce.len() == self.nonce.len() {
+                    return Err(scram_error("invalid server nonce"));
+                }
+                let salt = attribute(challenge, 's')
+                    .and_then(|salt| BASE64.decode(salt).ok())
+                    .ok_or_else(|| scram_error("missing or invalid salt"))?;
+                let iterations = attribute(challenge, 'i')
+                    .and_then(|i| i.parse::<u32>().ok())
+                    .filter(|&i| i > 0)
+                    .ok_or_else(|| scram_error("missing or invalid iteration count"))?;
+
+                let mut salted = [0; 32];
+                pbkdf2::pbkdf2_hmac::<Sha256>(
+                    self.password.as_bytes(),
+                    &salt,
+                    iterations,
+                    &mut salted,
+                );
+                let client_key = hmac(&salted, b"Client Key");
+                let stored_key = Sha256::digest(&client_key);
+                let server_key = hmac(&salted, b"Server Key");
+
+                let without_proof = format!("c={},r={}", BASE64.encode(GS2_HEADER), nonce);
+                let auth_message = format!("{},{},{}", bare, challenge, without_proof);
+                let client_signature = hmac(&stored_key, auth_message.as_bytes());
+                let proof = client_key
+                    .iter()
+                    .zip(&client_signature)
+                    .map(|(k, s)| k ^ s)
+                    .collect::<Vec<_>>();
+
+                self.state = ScramState::ClientFinal {
+                    signature: hmac(&server_key, auth_message.as_bytes()),
+                };
+                Ok(format!("{},p={}", without_proof, BASE64.encode(proof)).into_bytes())
+            }
+            ScramState::ClientFinal { signature } => {
+                if let Some(error) = attribute(challenge, 'e') {
+                    return Err(scram_error(error));
+                }
+                let verifier = attribute(challenge, 'v')
+                    .and_then(|v| BASE64.decode(v).ok())
+                    .ok_or_else(|| scram_error("missing server signature"))?;
+                if !constant_time_eq(&verifier, &signature) {
+                    return Err(scram_error("server signature mismatch"));
+                }
+                Ok(Vec::new())
+            }
+            ScramState::Done => Err(scram_error("unexpected challenge")),
+        }
+    }
+}
+
+/// State of the SASL authentication.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SaslState {
+    /// `AUTHENTICATE` was not sent yet.
+    Idle,
+    /// Exchanging challenges and responses with the server.
+    Authenticating,
+    /// Authentication succeeded.
+    Succeeded,
+    /// Authentication failed or was aborted.
+    Failed,
+}
+
+/// SASL authentication state machine.
+///
+/// Start it once the `sasl` capability is enabled, feed every message
+/// received from the server to `handle` and send the messages it returns.
+/// Payloads are base64 encoded and split into chunks of 400 bytes both ways.
+pub struct Sasl {
+    mechanism: Box<dyn Mechanism + Send>,
+    state: SaslState,
+    buffer: String,
+    account: Option<String>,
+    mechanisms: Vec<String>,
+}
+
+impl fmt::Debug for Sasl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Sasl")
+            .field("mechanism", &self.mechanism.name())
+            .field("state", &self.state)
+            .field("account", &self.account)
+            .finish()
+    }
+}
+
+impl Sasl {
+    /// Creates the state machine authenticating with the given mechanism.
+    pub fn new(mechanism: impl Mechanism + Send + 'static) -> Self {
+        Sasl {
+            mechanism: Box::new(mechanism),
+            state: SaslState::Idle,
+            buffer: String::new(),
+            account: None,
+            mechanisms: Vec::new(),
+        }
+    }
+
+    /// Current state.
+    pub fn state(&self) -> SaslState {
+        self.state
+    }
+
+    /// Checks if the authentication is over, whether it succeeded or not.
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, SaslState::Succeeded | SaslState::Failed)
+    }
+
+    /// Account we are logged in as, from `RPL_LOGGEDIN`.
+    pub fn account(&self) -> Option<&str> {
+        self.account.as_deref()
+    }
+
+    /// Mechanisms supported by the server, from `RPL_SASLMECHS`.
+    pub fn mechanisms(&self) -> &[String] {
+        &self.mechanisms
+    }
+
+    /// Starts the authentication, returning `AUTHENTICATE <mechanism>`.
+    pub fn start(&mut self) -> Message {
+        self.state = SaslState::Authenticating;
+        self.buffer.clear();
+        Message::build(Code::Authenticate)
+            .arg(self.mechanism.name())
+            .finish()
+    }
+
+    /// Aborts the authentication, returning `AUTHENTICATE *`.
+    pub fn abort(&mut self) -> Message {
+        self.state = SaslState::Failed;
+        Message::build(Code::Authenticate).arg("*").finish()
+    }
+
+    /// Handles a message from the server, returning the messages to send.
+    ///
+    /// Returns an error when the authentication fails. The state is then
+    /// `SaslState::Failed`, and registration can go on without an account.
+    /// When the challenge itself could not be processed, the server still
+    /// waits for a response: send `abort` to end the exchange.
+    pub fn handle(&mut self, msg: &Message) -> Result<Vec<Message>, SaslError> {
+        let mut out = Vec::new();
+        match msg.code {
+            Code::Authenticate if self.state == SaslState::Authenticating => {
+                let chunk = msg.args.first().map(String::as_str).unwrap_or("+");
+                if chunk != "+" {
+                    self.buffer.push_str(chunk);
+                }
+                if chunk.len() == CHUNK_LEN {
+                    // More to come.
+                    return Ok(out);
+                }
+                let payload = std::mem::take(&mut self.buffer);
+                let response = BASE64
+                    .decode(payload)
+                    .map_err(|_| SaslError::InvalidPayload)
+                    .and_then(|challenge| self.mechanism.respond(&challenge));
+                match response {
+                    Ok(response) => authenticate(&response, &mut out),
+                    Err(e) => return self.fail(e),
+                }
+            }
+            Code::RplLoggedin => {
+                if let Ok(reply) = msg.as_loggedin() {
+                    self.account = Some(reply.account.to_string());
+                }
+            }
+            Code::RplLoggedout => self.account = None,
+            Code::RplSaslmechs => {
+                if let Ok(reply) = msg.as_saslmechs() {
+                    self.mechanisms = reply.mechanisms.split(',').map(Into::into).collect();
+                }
+            }
+            Code::RplSaslsuccess => self.state = SaslState::Succeeded,
+            Code::ErrNicklocked => return self.fail(SaslError::NickLocked),
+            Code::ErrSaslfail => return self.fail(SaslError::Failed),
+            Code::ErrSasltoolong => return self.fail(SaslError::TooLong),
+            Code::ErrSaslaborted => return self.fail(SaslError::Aborted),
+            Code::ErrSaslalready => return self.fail(SaslError::AlreadyAuthenticated),
+            _ => {}
+        }
+        Ok(out)
+    }
+
+    fn fail(&mut self, error: SaslError) -> Result<Vec<Message>, SaslError> {
+        self.state = SaslState::Failed;
+        Err(error)
+    }
+
+    /// Starts the authentication, writing `AUTHENTICATE <mechanism>` through the writer.
+    pub async fn start_with<S>(&mut self, writer: &Writer<S>) -> Result<(), WriteError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        writer.send(&self.start()).await
+    }
+
+    /// Handles a message from the server, writing the replies through the writer.
+    ///
+    /// When the mechanism fails, `AUTHENTICATE *` is written before returning the error.
+    pub async fn handle_with<S>(
+        &mut self,
+        msg: &Message,
+        writer: &Writer<S>,
+    ) -> Result<Result<(), SaslError>, WriteError>
+    where
+        S: AsyncWrite + Unpin,
+    {
+        match self.handle(msg) {
+            Ok(out) => {
+                for msg in out {
+                    writer.send(&msg).await?;
+                }
+                Ok(Ok(()))
+            }
+            Err(e) => {
+                if let SaslError::InvalidPayload | SaslError::Mechanism(_) = e {
+                    writer.send(&self.abort()).await?;
+                }
+                Ok(Err(e))
+            }
+        }
+    }
+}
+
+/// Encodes the response into `AUTHENTICATE` messages.
+fn authenticate(response: &[u8], out: &mut Vec<Message>) {
+    let encoded = BASE64.encode(response);
+    for chunk in encoded.as_bytes().chunks(CHUNK_LEN) {
+        // base64 is ASCII, so chunks are valid UTF-8.
+        let chunk = std::str::from_utf8(chunk).unwrap();
+        out.push(Message::build(Code::Authenticate).arg(chunk).finish());
+    }
+    if encoded.len().is_multiple_of(CHUNK_LEN) {
+        out.push(Message::build(Code::Authenticate).arg("+").finish());
+    }
+}
+
+#[cfg(test)]
+fn handle_line(sasl: &mut Sasl, line: &str) -> Result<Vec<String>, SaslError> {
+    sasl.handle(&Message::parse(line).unwrap())
+        .map(|out| out.iter().map(ToString::to_string).collect())
+}
+
+#[test]
+fn test_plain() {
+    let mut sasl = Sasl::new(Plain::new("jilles", "sesame"));
+    assert_eq!(sasl.start().to_string(), "AUTHENTICATE PLAIN");
+    assert_eq!(
+        handle_line(&mut sasl, "AUTHENTICATE +").unwrap(),
+        vec!["AUTHENTICATE AGppbGxlcwBzZXNhbWU="]
+    );
+    assert!(handle_line(
+        &mut sasl,
+        ":irc.example.com 900 jilles jilles!jilles@localhost jilles :You are now logged in as jilles"
+    )
+    .unwrap()
+    .is_empty());
+    handle_line(
+        &mut sasl,
+        ":irc.example.com 903 jilles :SASL authentication successful",
+    )
+    .unwrap();
+    assert_eq!(sasl.state(), SaslState::Succeeded);
+    assert_eq!(sasl.account(), Some("jilles"));
+}
+
+#[test]
+fn test_external() {
+    let mut sasl = Sasl::new(External::new());
+    assert_eq!(sasl.start().to_string(), "AUTHENTICATE EXTERNAL");
+    assert_eq!(
+        handle_line(&mut sasl, "AUTHENTICATE +").unwrap(),
+        vec!["AUTHENTICATE +"]
+    );
+}
+
+#[test]
+fn test_fail() {
+    let mut sasl = Sasl::new(Plain::new("jilles", "wrong"));
+    sasl.start();
+    handle_line(&mut sasl, "AUTHENTICATE +").unwrap();
+    handle_line(
+        &mut sasl,
+        ":irc.example.com 908 jilles PLAIN,EXTERNAL :are available SASL mechanisms",
+    )
+    .unwrap();
+    assert_eq!(
+        handle_line(
+            &mut sasl,
+            ":irc.example.com 904 jilles :SASL authentication failed"
+        ),
+        Err(SaslError::Failed)
+    );
+    assert_eq!(sasl.state(), SaslState::Failed);
+    assert_eq!(sasl.mechanisms(), ["PLAIN", "EXTERNAL"]);
+
+    let mut sasl = Sasl::new(Plain::new("jilles", "sesame"));
+    sasl.start();
+    assert_eq!(
+        handle_line(
+            &mut sasl,
+            ":irc.example.com 902 jilles :You must use a nick assigned to you"
+        ),
+        Err(SaslError::NickLocked)
+    );
+}
+
+#[test]
+fn test_chunks() {
+    let mut out = Vec::new();
+    authenticate(&[b'a'; 300], &mut out);
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[0].args[0].len(), 400);
+    assert_eq!(out[1].args[0], "+");
+
+    let mut out = Vec::new();
+    authenticate(&[b'a'; 301], &mut out);
+    assert_eq!(out.len(), 2);
+    assert_eq!(out[1].args[0], "YQ==");
+
+    let mut out = Vec::new();
+    authenticate(&[], &mut out);
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].args[0], "+");
+
+    // Incoming chunks are joined before decoding.
+    let mut sasl = Sasl::new(External::new());
+    sasl.start();
+    let encoded = BASE64.encode([b'a'; 300]);
+    assert!(handle_line(&mut sasl, &format!("AUTHENTICATE {}", encoded))
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        handle_line(&mut sasl, "AUTHENTICATE +").unwrap(),
+        vec!["AUTHENTICATE +"]
+    );
+}
+
+#[test]
+fn test_scram_sha_256() {
+    // Test vector from RFC 7677.
+    let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+    assert_eq!(
+        scram.respond(b"").unwrap(),
+        b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO"
+    );
+    assert_eq!(
+        scram
+            .respond(
+                b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+                  s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096"
+            )
+            .unwrap(),
+        &b"c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+           p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ="[..]
+    );
+    assert_eq!(
+        scram
+            .respond(b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=")
+            .unwrap(),
+        b""
+    );
+
+    let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+    scram.respond(b"").unwrap();
+    assert!(scram
+        .respond(b"r=somethingelse,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096")
+        .is_err());
+
+    let mut scram = ScramSha256::with_nonce("user", "pencil", "rOprNGfwEbeRWgbNEkqO");
+    scram.respond(b"").unwrap();
+    scram
+        .respond(
+            b"r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+              s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+        )
+        .unwrap();
+    assert_eq!(
+        scram.respond(b"v=AAAATRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="),
+        Err(scram_error("server signature mismatch"))
+    );
+}
+
+#[test]
+fn test_drive_stream() {
+    use {
+        crate::{mock::MockStream, stream::IrcStream},
+        futures::{executor::block_on, prelude::*},
+    };
+
+    let server_first = BASE64.encode(
+        "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096",
+    );
+    let server_final = BASE64.encode("v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=");
+    let (mock, output) = MockStream::new(&format!(
+        "AUTHENTICATE +\r\n\
+         AUTHENTICATE {}\r\n\
+         AUTHENTICATE {}\r\n\
+         :irc.example.com 900 user user!user@localhost user :You are now logged in as user\r\n\
+         :irc.example.com 903 user :SASL authentication successful\r\n",
+        server_first, server_final
+    ));
+    let mut stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    let mut sasl = Sasl::new(ScramSha256::with_nonce(
+        "user",
+        "pencil",
+        "rOprNGfwEbeRWgbNEkqO",
+    ));
+    block_on(async {
+        sasl.start_with(&writer).await.unwrap();
+        while let Some(msg) = stream.next().await {
+            sasl.handle_with(&msg.unwrap(), &writer)
+                .await
+                .unwrap()
+                .unwrap();
+        }
+    });
+    assert_eq!(sasl.state(), SaslState::Succeeded);
+    assert_eq!(sasl.account(), Some("user"));
+
+    let client_first = BASE64.encode("n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+    let client_final = BASE64.encode(
+        "c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,\
+         p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=",
+    );
+    assert_eq!(
+        output.get(),
+        format!(
+            "AUTHENTICATE SCRAM-SHA-256\r\n\
+             AUTHENTICATE {}\r\n\
+             AUTHENTICATE {}\r\n\
+             AUTHENTICATE +\r\n",
+            client_first, client_final
+        )
+    );
+}
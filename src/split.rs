@@ -0,0 +1,185 @@
+//! Splitting long messages to respect the line length limit.
+
+use {
+    crate::code::Code,
+    encoding::{EncoderTrap, EncodingRef},
+};
+
+/// Maximum length of a line in bytes, including the trailing CRLF.
+pub const MAX_LINE_LEN: usize = 512;
+
+/// Splits text of `PRIVMSG` and `NOTICE` into lines fitting `MAX_LINE_LEN`.
+///
+/// The length is computed on the line as relayed by the server to other
+/// clients, i.e. `:<nick>!<user>@<host> <command> <target> :<text>\r\n`,
+/// after encoding.
+#[derive(Clone, Copy)]
+pub struct Splitter {
+    encoding: EncodingRef,
+    prefix_len: usize,
+}
+
+impl Splitter {
+    /// Creates a splitter for the given encoding.
+    ///
+    /// `prefix_len` is the length of our own `nick!user@host`, as seen by the server.
+    pub fn new(encoding: EncodingRef, prefix_len: usize) -> Self {
+        Splitter {
+            encoding,
+            prefix_len,
+        }
+    }
+
+    fn encoded_len(&self, s: &str) -> usize {
+        self.encoding
+            .encode(s, EncoderTrap::Ignore)
+            .map(|bytes| bytes.len())
+            .unwrap_or_else(|_| s.len())
+    }
+
+    /// Maximum length of the text in bytes.
+    pub fn max_text_len(&self, code: &Code, target: &str) -> usize {
+        // ":" prefix " " code " " target " :" text "\r\n"
+        let overhead =
+            1 + self.prefix_len + 1 + code.to_string().len() + 1 + self.encoded_len(target) + 2 + 2;
+        MAX_LINE_LEN.saturating_sub(overhead)
+    }
+
+    /// Splits the text into chunks fitting a single line each.
+    ///
+    /// Text is split on spaces when possible. A word longer than a line is
+    /// split between characters, but never inside a multibyte character or a
+    /// formatting sequence.
+    pub fn split(&self, code: &Code, target: &str, text: &str) -> Vec<String> {
+        let max = self.max_text_len(code, target).max(1);
+        let mut lines = Vec::new();
+        let mut line = String::new();
+        let mut line_len = 0;
+        let mut first = true;
+
+        for word in text.split(' ') {
+            let word_len = self.encoded_len(word);
+            let sep = if first { 0 } else { 1 };
+            if line_len + sep + word_len <= max {
+                if !first {
+                    line.push(' ');
+                }
+                line.push_str(word);
+                line_len += sep + word_len;
+                first = false;
+                continue;
+            }
+
+            if !line.is_empty() {
+                lines.push(line.split_off(0));
+                line_len = 0;
+            }
+            let mut rest = word;
+            while !rest.is_empty() {
+                let atom = &rest[..atom_len(rest)];
+                let len = self.encoded_len(atom);
+                if line_len + len > max && !line.is_empty() {
+                    lines.push(line.split_off(0));
+                    line_len = 0;
+                }
+                line.push_str(atom);
+                line_len += len;
+                rest = &rest[atom.len()..];
+            }
+            first = false;
+        }
+
+        if !line.is_empty() || lines.is_empty() {
+            lines.push(line);
+        }
+        lines
+    }
+}
+
+/// Length in bytes of the leading character or formatting sequence.
+fn atom_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let digits = |start: usize, max: usize, f: fn(&u8) -> bool| {
+        bytes[start..].iter().take(max).take_while(|b| f(b)).count()
+    };
+    match bytes[0] {
+        0x03 => {
+            let fg = digits(1, 2, u8::is_ascii_digit);
+            let mut len = 1 + fg;
+            if fg > 0 && bytes.get(len) == Some(&b',') {
+                let bg = digits(len + 1, 2, u8::is_ascii_digit);
+                if bg > 0 {
+                    len += 1 + bg;
+                }
+            }
+            len
+        }
+        0x04 => {
+            let fg = digits(1, 6, u8::is_ascii_hexdigit);
+            let mut len = 1 + fg;
+            if fg == 6
+                && bytes.get(len) == Some(&b',')
+                && digits(len + 1, 6, u8::is_ascii_hexdigit) == 6
+            {
+                len += 7;
+            }
+            len
+        }
+        _ => s.chars().next().map_or(1, char::len_utf8),
+    }
+}
+
+#[test]
+fn test_short() {
+    let splitter = Splitter::new(encoding::all::UTF_8, 20);
+    assert_eq!(
+        splitter.split(&Code::Privmsg, "#chan", "hello world"),
+        vec!["hello world"]
+    );
+    assert_eq!(splitter.split(&Code::Privmsg, "#chan", ""), vec![""]);
+}
+
+#[test]
+fn test_words() {
+    let splitter = Splitter::new(encoding::all::UTF_8, 20);
+    let max = splitter.max_text_len(&Code::Privmsg, "#chan");
+    assert_eq!(max, 512 - 39);
+    let text = vec!["word"; 200].join(" ");
+    let lines = splitter.split(&Code::Privmsg, "#chan", &text);
+    assert_eq!(lines.len(), 3);
+    assert!(lines.iter().all(|line| line.len() <= max));
+    assert!(lines.iter().all(|line| !line.starts_with(' ')));
+    assert_eq!(lines.join(" "), text);
+}
+
+#[test]
+fn test_multibyte() {
+    let text = "가".repeat(400);
+
+    let splitter = Splitter::new(encoding::all::UTF_8, 20);
+    let max = splitter.max_text_len(&Code::Privmsg, "#chan");
+    let lines = splitter.split(&Code::Privmsg, "#chan", &text);
+    assert!(lines.iter().all(|line| line.len() <= max));
+    assert_eq!(lines.concat(), text);
+
+    let splitter = Splitter::new(encoding::all::WINDOWS_949, 20);
+    let lines = splitter.split(&Code::Privmsg, "#chan", &text);
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0].chars().count(), max / 2);
+    assert_eq!(lines.concat(), text);
+}
+
+#[test]
+fn test_formatting() {
+    let splitter = Splitter::new(encoding::all::UTF_8, 0);
+    let max = splitter.max_text_len(&Code::Privmsg, "#chan");
+    let text = format!("{}\x0312,34{}", "a".repeat(max - 3), "b".repeat(10));
+    let lines = splitter.split(&Code::Privmsg, "#chan", &text);
+    assert_eq!(lines[0], "a".repeat(max - 3));
+    assert!(lines[1].starts_with("\x0312,34b"));
+
+    assert_eq!(atom_len("\x03"), 1);
+    assert_eq!(atom_len("\x034,b"), 2);
+    assert_eq!(atom_len("\x04ff00ff,00ff00x"), 14);
+    assert_eq!(atom_len("\x02x"), 1);
+}
@@ -0,0 +1,720 @@
+//! Tracking of channels and users, from the messages received.
+
+use {
+    crate::{
+        casemap::{CaseMapping, IrcString},
+        code::Code,
+        isupport::ISupport,
+        message::{Message, Prefix},
+        mode::{apply_user_modes, ModeChange, ModeKind, Sign},
+    },
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// Topic of a channel.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Topic {
+    /// Text of the topic.
+    pub text: String,
+    /// Nickname or mask of who set the topic, if known.
+    pub setter: Option<String>,
+    /// When the topic was set, in seconds since the Unix epoch, if known.
+    pub time: Option<u64>,
+}
+
+/// Channel we are in.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Channel {
+    /// Name of the channel, as first seen.
+    ///
+    /// It is compared with the casemapping of the server. Use `as_str` or
+    /// `into_string` for the plain name.
+    pub name: IrcString,
+    /// Topic, if any.
+    pub topic: Option<Topic>,
+    /// Members, with their prefix modes from the highest, e.g. `ov`.
+    pub members: BTreeMap<IrcString, String>,
+    /// Channel modes, with their parameter if any. List modes like `b` are not tracked.
+    pub modes: BTreeMap<char, Option<String>>,
+}
+
+impl Channel {
+    fn new(name: IrcString) -> Self {
+        Channel {
+            name,
+            topic: None,
+            members: BTreeMap::new(),
+            modes: BTreeMap::new(),
+        }
+    }
+
+    /// Checks if the nickname is a member, according to the casemapping.
+    pub fn contains(&self, nickname: &str) -> bool {
+        self.member(nickname).is_some()
+    }
+
+    /// Prefix modes of the member, according to the casemapping.
+    pub fn member(&self, nickname: &str) -> Option<&str> {
+        self.members
+            .get(&IrcString::new(nickname, self.name.mapping()))
+            .map(String::as_str)
+    }
+}
+
+/// User sharing a channel with us.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct User {
+    /// Nickname.
+    pub nickname: IrcString,
+    /// Username, if known.
+    pub username: Option<String>,
+    /// Hostname, if known.
+    pub hostname: Option<String>,
+    /// Real name, if known from `extended-join`, `RPL_WHOREPLY` or `RPL_WHOISUSER`.
+    pub realname: Option<String>,
+}
+
+impl User {
+    fn new(nickname: IrcString) -> Self {
+        User {
+            nickname,
+            username: None,
+            hostname: None,
+            realname: None,
+        }
+    }
+}
+
+/// Model of the network as seen by the client.
+///
+/// Feed every message received from the server to `handle`. Users are
+/// tracked while they share a channel with us. Nicknames and channel names
+/// are compared with the `CASEMAPPING` of the server.
+#[derive(Clone, Debug)]
+pub struct State {
+    nickname: String,
+    modes: BTreeSet<char>,
+    channels: BTreeMap<IrcString, Channel>,
+    users: BTreeMap<IrcString, User>,
+    names: BTreeMap<IrcString, BTreeMap<IrcString, String>>,
+    isupport: ISupport,
+    mapping: CaseMapping,
+}
+
+impl Default for State {
+    fn default() -> Self {
+        State::new()
+    }
+}
+
+impl State {
+    /// Creates an empty state, using the default casemapping until `RPL_ISUPPORT`.
+    pub fn new() -> Self {
+        State {
+            nickname: String::new(),
+            modes: BTreeSet::new(),
+            channels: BTreeMap::new(),
+            users: BTreeMap::new(),
+            names: BTreeMap::new(),
+            isupport: ISupport::new(),
+            mapping: CaseMapping::default(),
+        }
+    }
+
+    fn key(&self, name: &str) -> IrcString {
+        IrcString::new(name, self.mapping)
+    }
+
+    fn is_me(&self, nickname: &str) -> bool {
+        self.mapping.equals(nickname, &self.nickname)
+    }
+
+    /// Our nickname.
+    pub fn nickname(&self) -> &str {
+        &self.nickname
+    }
+
+    /// Our user modes.
+    pub fn modes(&self) -> &BTreeSet<char> {
+        &self.modes
+    }
+
+    /// Channels we are in.
+    pub fn channels(&self) -> impl Iterator<Item = &Channel> {
+        self.channels.values()
+    }
+
+    /// Channel we are in, according to the casemapping.
+    pub fn channel(&self, name: &str) -> Option<&Channel> {
+        self.channels.get(&self.key(name))
+    }
+
+    /// Users sharing a channel with us, including ourselves.
+    pub fn users(&self) -> impl Iterator<Item = &User> {
+        self.users.values()
+    }
+
+    /// User sharing a channel with us, according to the casemapping.
+    pub fn user(&self, nickname: &str) -> Option<&User> {
+        self.users.get(&self.key(nickname))
+    }
+
+    /// Server features, from `RPL_ISUPPORT`.
+    pub fn isupport(&self) -> &ISupport {
+        &self.isupport
+    }
+
+    /// Highest prefix symbol of the member, e.g. `@`.
+    pub fn member_prefix(&self, channel: &str, nickname: &str) -> Option<char> {
+        let modes = self.channel(channel)?.member(nickname)?;
+        let mode = modes.chars().next()?;
+        self.isupport
+            .prefix()
+            .into_iter()
+            .find(|(m, _)| *m == mode)
+            .map(|(_, symbol)| symbol)
+    }
+
+    /// Handles a message from the server.
+    pub fn handle(&mut self, msg: &Message) {
+        let mapping = self.mapping;
+        let key = |name: &str| IrcString::new(name, mapping);
+        let source = match msg.prefix {
+            Some(Prefix::User(ref user)) => {
+                if let Some(known) = self.users.get_mut(&key(&user.nickname)) {
+                    known.username = Some(user.username.clone());
+                    known.hostname = Some(user.hostname.clone());
+                }
+                Some(user)
+            }
+            _ => None,
+        };
+        let nick = source.map(|user| user.nickname.as_str()).unwrap_or("");
+        let arg = |i: usize| msg.args.get(i).map(String::as_str).unwrap_or("");
+
+        match msg.code {
+            Code::RplWelcome => self.nickname = arg(0).into(),
+            Code::RplIsupport => {
+                self.isupport.handle(msg);
+                let mapping = self.isupport.casemapping();
+                if mapping != self.mapping {
+                    self.remap(mapping);
+                }
+            }
+            Code::Nick if !nick.is_empty() => self.rename(nick, arg(0)),
+            Code::Join if !nick.is_empty() => {
+                let user = source.unwrap();
+                let entry = self
+                    .users
+                    .entry(key(nick))
+                    .or_insert_with(|| User::new(key(nick)));
+                entry.username = Some(user.username.clone());
+                entry.hostname = Some(user.hostname.clone());
+                // extended-join: <channel> <account> :<realname>
+                if msg.args.len() >= 3 {
+                    entry.realname = Some(arg(2).into());
+                }
+
+                for channel in arg(0).split(',') {
+                    if self.is_me(nick) {
+                        self.channels
+                            .insert(key(channel), Channel::new(key(channel)));
+                    }
+                    if let Some(channel) = self.channels.get_mut(&key(channel)) {
+                        channel.members.insert(key(nick), String::new());
+                    }
+                }
+            }
+            Code::Part => {
+                for channel in arg(0).split(',') {
+                    self.leave(channel, nick);
+                }
+            }
+            Code::Kick => {
+                // Either one channel for every user, or one channel per user.
+                let channels = arg(0).split(',').collect::<Vec<_>>();
+                for (i, nick) in arg(1).split(',').enumerate() {
+                    let channel = match channels.len() {
+                        1 => channels[0],
+                        _ => match channels.get(i) {
+                            Some(channel) => channel,
+                            None => continue,
+                        },
+                    };
+                    self.leave(channel, nick);
+                }
+            }
+            Code::Quit => {
+                for channel in self.channels.values_mut() {
+                    channel.members.remove(&key(nick));
+                }
+                self.prune(nick);
+            }
+            Code::Topic => {
+                if let Some(channel) = self.channels.get_mut(&key(arg(0))) {
+                    channel.topic =
+                        Some(arg(1))
+                            .filter(|text| !text.is_empty())
+                            .map(|text| Topic {
+                                text: text.into(),
+                                setter: Some(nick.into()),
+                                time: SystemTime::now()
+                                    .duration_since(UNIX_EPOCH)
+                                    .ok()
+                                    .map(|d| d.as_secs()),
+                            });
+                }
+            }
+            Code::RplTopic => {
+                if let Some(channel) = self.channels.get_mut(&key(arg(1))) {
+                    channel.topic = Some(Topic {
+                        text: arg(2).into(),
+                        setter: None,
+                        time: None,
+                    });
+                }
+            }
+            Code::RplTopicwhotime => {
+                if let Some(topic) = self
+                    .channels
+                    .get_mut(&key(arg(1)))
+                    .and_then(|channel| channel.topic.as_mut())
+                {
+                    topic.setter = Some(arg(2).into());
+                    topic.time = arg(3).parse().ok();
+                }
+            }
+            Code::RplNotopic => {
+                if let Some(channel) = self.channels.get_mut(&key(arg(1))) {
+                    channel.topic = None;
+                }
+            }
+            Code::RplNamreply => self.names(arg(2), arg(3)),
+            Code::RplEndofnames => {
+                let names = self.names.remove(&key(arg(1))).unwrap_or_default();
+                if let Some(channel) = self.channels.get_mut(&key(arg(1))) {
+                    let old = std::mem::replace(&mut channel.members, names);
+                    for nick in old.keys() {
+                        self.prune(nick);
+                    }
+                }
+            }
+            Code::Mode if self.is_me(arg(0)) => apply_user_modes(&mut self.modes, arg(1)),
+            Code::Mode => self.apply_channel_modes(arg(0), arg(1), msg.args.get(2..)),
+            Code::RplUmodeis => {
+                self.modes.clear();
+                apply_user_modes(&mut self.modes, arg(1));
+            }
+            Code::RplChannelmodeis => {
+                if let Some(channel) = self.channels.get_mut(&key(arg(1))) {
+                    channel.modes.clear();
+                }
+                self.apply_channel_modes(arg(1), arg(2), msg.args.get(3..));
+            }
+            Code::RplWhoreply => {
+                if let Some(user) = self.users.get_mut(&key(arg(5))) {
+                    user.username = Some(arg(2).into());
+                    user.hostname = Some(arg(3).into());
+                    // :<hopcount> <realname>
+                    if let Some((_, realname)) = arg(7).split_once(' ') {
+                        user.realname = Some(realname.into());
+                    }
+                }
+            }
+            Code::RplWhoisuser => {
+                if let Some(user) = self.users.get_mut(&key(arg(1))) {
+                    user.username = Some(arg(2).into());
+                    user.hostname = Some(arg(3).into());
+                    user.realname = msg.args.last().cloned();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Re-keys the maps after `CASEMAPPING` changed.
+    ///
+    /// Entries that become equal under the new casemapping are merged, the
+    /// first one in the old order giving the name.
+    fn remap(&mut self, mapping: CaseMapping) {
+        fn merge_members(
+            into: &mut BTreeMap<IrcString, String>,
+            members: BTreeMap<IrcString, String>,
+            mapping: CaseMapping,
+            prefix: &[(char, char)],
+        ) {
+            for (nick, modes) in members {
+                let merged = into.entry(nick.with_mapping(mapping)).or_default();
+                if merged.is_empty() {
+                    *merged = modes;
+                } else {
+                    *merged = prefix
+                        .iter()
+                        .map(|(m, _)| *m)
+                        .filter(|m| merged.contains(*m) || modes.contains(*m))
+                        .collect();
+                }
+            }
+        }
+
+        let prefix = self.isupport.prefix();
+        self.mapping = mapping;
+
+        let mut channels = BTreeMap::new();
+        for channel in std::mem::take(&mut self.channels).into_values() {
+            let name = channel.name.with_mapping(mapping);
+            let merged = channels
+                .entry(name.clone())
+                .or_insert_with(|| Channel::new(name));
+            merged.topic = merged.topic.take().or(channel.topic);
+            merge_members(&mut merged.members, channel.members, mapping, &prefix);
+            for (mode, arg) in channel.modes {
+                merged.modes.entry(mode).or_insert(arg);
+            }
+        }
+        self.channels = channels;
+
+        let mut users = BTreeMap::new();
+        for user in std::mem::take(&mut self.users).into_values() {
+            let nickname = user.nickname.with_mapping(mapping);
+            let merged = users
+                .entry(nickname.clone())
+                .or_insert_with(|| User::new(nickname));
+            merged.username = merged.username.take().or(user.username);
+            merged.hostname = merged.hostname.take().or(user.hostname);
+            merged.realname = merged.realname.take().or(user.realname);
+        }
+        self.users = users;
+
+        let mut names = BTreeMap::new();
+        for (channel, members) in std::mem::take(&mut self.names) {
+            let merged = names.entry(channel.with_mapping(mapping)).or_default();
+            merge_members(merged, members, mapping, &prefix);
+        }
+        self.names = names;
+    }
+
+    fn rename(&mut self, old: &str, new: &str) {
+        if self.is_me(old) {
+            self.nickname = new.into();
+        }
+        if let Some(mut user) = self.users.remove(&self.key(old)) {
+            user.nickname = self.key(new);
+            self.users.insert(self.key(new), user);
+        }
+        let (old, new) = (self.key(old), self.key(new));
+        for channel in self.channels.values_mut() {
+            if let Some(modes) = channel.members.remove(&old) {
+                channel.members.insert(new.clone(), modes);
+            }
+        }
+    }
+
+    fn leave(&mut self, channel: &str, nick: &str) {
+        if self.is_me(nick) {
+            if let Some(channel) = self.channels.remove(&self.key(channel)) {
+                for nick in channel.members.keys() {
+                    self.prune(nick);
+                }
+            }
+        } else {
+            let (channel, key) = (self.key(channel), self.key(nick));
+            if let Some(channel) = self.channels.get_mut(&channel) {
+                channel.members.remove(&key);
+                self.prune(nick);
+            }
+        }
+    }
+
+    /// Forgets the user if it does not share any channel with us anymore.
+    fn prune(&mut self, nick: &str) {
+        let key = self.key(nick);
+        if !self.is_me(nick)
+            && !self
+                .channels
+                .values()
+                .any(|channel| channel.members.contains_key(&key))
+        {
+            self.users.remove(&key);
+        }
+    }
+
+    /// Collects the names of `RPL_NAMREPLY`, until `RPL_ENDOFNAMES`.
+    fn names(&mut self, channel: &str, names: &str) {
+        let channel = self.key(channel);
+        if !self.channels.contains_key(&channel) {
+            return;
+        }
+        let mapping = self.mapping;
+        let prefix = self.isupport.prefix();
+        let members = self.names.entry(channel).or_default();
+        for name in names.split(' ').filter(|name| !name.is_empty()) {
+            // multi-prefix may give more than one prefix.
+            let start = name
+                .find(|c| !prefix.iter().any(|(_, symbol)| *symbol == c))
+                .unwrap_or(name.len());
+            let modes = name[..start]
+                .chars()
+                .filter_map(|c| prefix.iter().find(|(_, symbol)| *symbol == c))
+                .map(|(mode, _)| *mode)
+                .collect::<String>();
+            // userhost-in-names gives nick!user@host.
+            let (nick, userhost) = match name[start..].split_once('!') {
+                Some((nick, userhost)) => (nick, userhost.split_once('@')),
+                None => (&name[start..], None),
+            };
+
+            let nick = IrcString::new(nick, mapping);
+            let user = self
+                .users
+                .entry(nick.clone())
+                .or_insert_with(|| User::new(nick.clone()));
+            if let Some((username, hostname)) = userhost {
+                user.username = Some(username.into());
+                user.hostname = Some(hostname.into());
+            }
+            members.insert(nick, modes);
+        }
+    }
+
+    fn apply_channel_modes(&mut self, channel: &str, modestring: &str, args: Option<&[String]>) {
+        let mapping = self.mapping;
+        let channel = match self.channels.get_mut(&IrcString::new(channel, mapping)) {
+            Some(channel) => channel,
+            None => return,
+        };
+        let prefix = self.isupport.prefix();
+        let changes = ModeChange::parse_channel(modestring, args.unwrap_or(&[]), &self.isupport);
+        for change in changes {
+            let adding = change.sign == Sign::Add;
+            match ModeKind::of(change.mode, &self.isupport) {
+                ModeKind::Prefix => {
+                    let modes =
+                        match change.arg.as_deref().and_then(|nick| {
+                            channel.members.get_mut(&IrcString::new(nick, mapping))
+                        }) {
+                            Some(modes) => modes,
+                            None => continue,
+                        };
+                    let mut set = modes
+                        .chars()
+                        .filter(|m| *m != change.mode)
+                        .collect::<Vec<_>>();
+                    if adding {
+                        set.push(change.mode);
+                    }
+                    *modes = prefix
+                        .iter()
+                        .map(|(m, _)| *m)
+                        .filter(|m| set.contains(m))
+                        .collect();
+                }
+                ModeKind::List => {}
+                _ if adding => {
+                    channel.modes.insert(change.mode, change.arg);
+                }
+                _ => {
+                    channel.modes.remove(&change.mode);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+fn handle_lines(state: &mut State, lines: &str) {
+    for line in lines.lines() {
+        state.handle(&Message::parse(line).unwrap());
+    }
+}
+
+#[test]
+fn test_join_names() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :bot!bot@localhost JOIN #chan\n\
+         :irc.example.com 332 bot #chan :Hello world\n\
+         :irc.example.com 333 bot #chan alice!a@host 1700000000\n\
+         :irc.example.com 353 bot = #chan :@+alice bob!b@host.example @bot\n\
+         :irc.example.com 366 bot #chan :End of /NAMES list\n\
+         :carol!c@host JOIN #chan\n",
+    );
+
+    let channel = state.channel("#chan").unwrap();
+    assert_eq!(
+        channel
+            .members
+            .keys()
+            .map(|nick| nick.as_str())
+            .collect::<Vec<_>>(),
+        vec!["alice", "bob", "bot", "carol"]
+    );
+    assert_eq!(channel.member("Alice"), Some("ov"));
+    let topic = channel.topic.as_ref().unwrap();
+    assert_eq!(topic.text, "Hello world");
+    assert_eq!(topic.setter.as_deref(), Some("alice!a@host"));
+    assert_eq!(topic.time, Some(1_700_000_000));
+    assert_eq!(state.member_prefix("#chan", "alice"), Some('@'));
+    assert_eq!(state.member_prefix("#chan", "bob"), None);
+    assert_eq!(
+        state.user("bob").unwrap().hostname.as_deref(),
+        Some("host.example")
+    );
+    assert_eq!(state.user("carol").unwrap().username.as_deref(), Some("c"));
+}
+
+#[test]
+fn test_leave() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :bot!bot@localhost JOIN #a\n\
+         :bot!bot@localhost JOIN #b\n\
+         :alice!a@host JOIN #a\n\
+         :alice!a@host JOIN #b\n\
+         :bob!b@host JOIN #a\n\
+         :alice!a@host PART #a\n\
+         :bob!b@host NICK robert\n",
+    );
+    assert!(state.user("alice").is_some());
+    assert!(state.channel("#a").unwrap().contains("robert"));
+    assert_eq!(state.user("robert").unwrap().nickname, "robert");
+
+    handle_lines(
+        &mut state,
+        ":alice!a@host QUIT :bye\n\
+         :op!o@host KICK #a robert :out\n",
+    );
+    assert!(state.user("alice").is_none());
+    assert!(state.user("robert").is_none());
+
+    handle_lines(&mut state, ":bot!bot@localhost PART #b\n");
+    assert_eq!(
+        state
+            .channels()
+            .map(|c| c.name.as_str())
+            .collect::<Vec<_>>(),
+        vec!["#a"]
+    );
+    handle_lines(&mut state, ":bot!bot@localhost NICK robot\n");
+    assert_eq!(state.nickname(), "robot");
+}
+
+#[test]
+fn test_modes() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :bot MODE bot :+iw\n\
+         :bot!bot@localhost JOIN #chan\n\
+         :alice!a@host JOIN #chan\n\
+         :irc.example.com 324 bot #chan +ntl 10\n\
+         :op!o@host MODE #chan +ovkb-l alice alice secret *!*@spam\n\
+         :op!o@host TOPIC #chan :New topic\n",
+    );
+    assert_eq!(state.modes().iter().collect::<String>(), "iw");
+    let channel = state.channel("#chan").unwrap();
+    assert_eq!(channel.member("alice"), Some("ov"));
+    assert_eq!(channel.modes.get(&'k'), Some(&Some("secret".into())));
+    assert_eq!(channel.modes.get(&'l'), None);
+    assert_eq!(channel.modes.get(&'n'), Some(&None));
+    assert_eq!(channel.modes.get(&'b'), None);
+    let topic = channel.topic.as_ref().unwrap();
+    assert_eq!(topic.setter.as_deref(), Some("op"));
+
+    handle_lines(&mut state, ":op!o@host MODE #chan -o alice\n");
+    assert_eq!(state.channel("#chan").unwrap().member("alice"), Some("v"));
+}
+
+#[test]
+fn test_isupport() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :irc.example.com 005 bot PREFIX=(ohv)@%+ CHANMODES=b,k,fl,nt :are supported\n\
+         :bot!bot@localhost JOIN #chan\n\
+         :irc.example.com 353 bot = #chan :%alice bot\n\
+         :irc.example.com 366 bot #chan :End of /NAMES list\n\
+         :op!o@host MODE #chan +hf alice 5:10\n",
+    );
+    let channel = state.channel("#chan").unwrap();
+    assert_eq!(channel.member("alice"), Some("h"));
+    assert_eq!(channel.modes.get(&'f'), Some(&Some("5:10".into())));
+    assert_eq!(state.member_prefix("#chan", "alice"), Some('%'));
+}
+
+#[test]
+fn test_casemapping() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 Bot[1] :Welcome\n\
+         :bot{1}!bot@localhost JOIN #Chan[a]\n\
+         :Alice!a@host JOIN #chan{A}\n\
+         :ALICE!a@host NICK Alice^\n",
+    );
+    let channel = state.channel("#CHAN[A]").unwrap();
+    assert_eq!(channel.name.as_str(), "#Chan[a]");
+    assert!(channel.contains("alice~"));
+    assert_eq!(state.user("ALICE~").unwrap().nickname.as_str(), "Alice^");
+
+    handle_lines(
+        &mut state,
+        ":irc.example.com 005 Bot[1] CASEMAPPING=ascii :are supported\n",
+    );
+    assert!(state.channel("#chan{a}").is_none());
+    assert!(state.channel("#CHAN[A]").is_some());
+    assert!(state.user("alice^").is_some());
+    assert!(state.user("alice~").is_none());
+
+    handle_lines(&mut state, ":BOT[1]!bot@localhost PART #chan[a]\n");
+    assert_eq!(state.channels().count(), 0);
+}
+
+#[test]
+fn test_remap_merge() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :irc.example.com 005 bot CASEMAPPING=ascii :are supported\n\
+         :bot!bot@localhost JOIN #a[\n\
+         :bot!bot@localhost JOIN #a{\n\
+         :alice!a@host JOIN #a[,#a{\n\
+         :bob!b@host JOIN #a{\n\
+         :op!o@host MODE #a[ +v alice\n\
+         :op!o@host MODE #a{ +o alice\n\
+         :irc.example.com 005 bot CASEMAPPING=rfc1459 :are supported\n",
+    );
+    assert_eq!(state.channels().count(), 1);
+    let channel = state.channel("#A{").unwrap();
+    assert_eq!(channel.name.as_str(), "#a[");
+    assert_eq!(channel.member("alice"), Some("ov"));
+    assert!(channel.contains("bob"));
+}
+
+#[test]
+fn test_kick_many() {
+    let mut state = State::new();
+    handle_lines(
+        &mut state,
+        ":irc.example.com 001 bot :Welcome\n\
+         :bot!bot@localhost JOIN #a,#b\n\
+         :alice!a@host JOIN #a,#b\n\
+         :bob!b@host JOIN #a,#b\n\
+         :op!o@host KICK #a alice,bob :out\n\
+         :op!o@host KICK #b,#c bob,alice :out\n",
+    );
+    assert_eq!(state.channel("#a").unwrap().members.len(), 1);
+    let channel = state.channel("#b").unwrap();
+    assert!(!channel.contains("bob"));
+    assert!(channel.contains("alice"));
+}
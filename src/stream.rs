@@ -1,8 +1,21 @@
 use {
-    crate::message::{Message, ParseError},
+    crate::{
+        batch::BatchItem,
+        code::Code,
+        command::Command,
+        ctcp::Ctcp,
+        message::{Message, MessageRef, ParseError, ValidationError},
+        query::{
+            motd_from_replies, names_from_replies, BanEntry, ListEntry, Queries, Query, QueryError,
+            WhoEntry, WhoisInfo, DEFAULT_QUERY_TIMEOUT,
+        },
+        split::Splitter,
+        throttle::{RateLimit, ThrottledWriter},
+    },
     encoding::{DecoderTrap, EncoderTrap, EncodingRef},
     futures::{
         executor::{block_on, block_on_stream, BlockingStream},
+        future,
         io::{AllowStdIo, BufReader, Error as AsyncIoError, ReadHalf, WriteHalf},
         lock::Mutex,
         prelude::*,
@@ -15,6 +28,7 @@ use {
         mem,
         pin::Pin,
         sync::Arc,
+        time::Duration,
     },
 };
 
@@ -22,6 +36,10 @@ use {
 pub enum StreamError {
     ParseError(ParseError),
     AsyncIoError(AsyncIoError),
+    /// Server did not answer our `PING` in time. See `Keepalive`.
+    Timeout,
+    /// Failed to write an automatic reply. See `Keepalive`.
+    WriteError(WriteError),
 }
 
 impl From<ParseError> for StreamError {
@@ -36,26 +54,87 @@ impl From<AsyncIoError> for StreamError {
     }
 }
 
+impl From<WriteError> for StreamError {
+    fn from(err: WriteError) -> Self {
+        StreamError::WriteError(err)
+    }
+}
+
 impl fmt::Display for StreamError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             StreamError::ParseError(ref e) => write!(f, "ParseError: {}", e),
             StreamError::AsyncIoError(ref e) => write!(f, "AsyncIoError: {}", e),
+            StreamError::Timeout => write!(f, "Timeout"),
+            StreamError::WriteError(ref e) => write!(f, "WriteError: {}", e),
         }
     }
 }
 
 impl std::error::Error for StreamError {}
 
+#[derive(Debug)]
+pub enum WriteError {
+    IoError(IoError),
+    /// Argument given to a convenience method is empty or contains a space.
+    InvalidArgument {
+        name: &'static str,
+        value: String,
+    },
+    /// Message would not serialize into a single well-formed line.
+    InvalidMessage(ValidationError),
+}
+
+impl From<IoError> for WriteError {
+    fn from(err: IoError) -> Self {
+        WriteError::IoError(err)
+    }
+}
+
+impl From<ValidationError> for WriteError {
+    fn from(err: ValidationError) -> Self {
+        WriteError::InvalidMessage(err)
+    }
+}
+
+impl fmt::Display for WriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            WriteError::IoError(ref e) => write!(f, "IoError: {}", e),
+            WriteError::InvalidArgument { name, ref value } => {
+                write!(f, "InvalidArgument: {} {:?}", name, value)
+            }
+            WriteError::InvalidMessage(ref e) => write!(f, "InvalidMessage: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {}
+
+/// Checks that an argument of a convenience method is a single non-empty word.
+pub(crate) fn check_arg(name: &'static str, value: &str) -> Result<String, WriteError> {
+    if value.is_empty() || value.contains(' ') {
+        Err(WriteError::InvalidArgument {
+            name,
+            value: value.into(),
+        })
+    } else {
+        Ok(value.into())
+    }
+}
+
 pub struct Writer<S> {
     pub encoding: EncodingRef,
     inner: Arc<Mutex<WriteHalf<S>>>,
+    queries: Queries,
+    query_timeout: Duration,
 }
 
 impl<S> Writer<S>
 where
     S: AsyncWrite + Unpin,
 {
+    /// Writes the string as is, without any validation. See `send`.
     pub async fn raw(&self, msg: impl AsRef<str>) -> Result<(), IoError> {
         let bytes = self
             .encoding
@@ -70,6 +149,249 @@ where
         let fut = self.raw(msg);
         block_on(fut)
     }
+
+    /// Sends the message, terminated with CRLF.
+    ///
+    /// The message is validated first, so a line containing CR, LF or NUL is
+    /// never sent. See `Message::validate`.
+    pub async fn send(&self, msg: &Message) -> Result<(), WriteError> {
+        msg.validate()?;
+        self.raw(format!("{}\r\n", msg)).await?;
+        Ok(())
+    }
+
+    pub fn send_wait(&self, msg: &Message) -> Result<(), WriteError> {
+        let fut = self.send(msg);
+        block_on(fut)
+    }
+
+    async fn send_command(&self, command: Command) -> Result<(), WriteError> {
+        self.send(&command.into()).await
+    }
+
+    /// Sends `PRIVMSG <target> :<text>`.
+    pub async fn privmsg(&self, target: &str, text: &str) -> Result<(), WriteError> {
+        self.send_command(Command::Privmsg {
+            target: check_arg("target", target)?,
+            text: text.into(),
+        })
+        .await
+    }
+
+    /// Sends `NOTICE <target> :<text>`.
+    pub async fn notice(&self, target: &str, text: &str) -> Result<(), WriteError> {
+        self.send_command(Command::Notice {
+            target: check_arg("target", target)?,
+            text: text.into(),
+        })
+        .await
+    }
+
+    /// Sends `PRIVMSG <target> :\x01ACTION <text>\x01`, as in `/me`.
+    pub async fn action(&self, target: &str, text: &str) -> Result<(), WriteError> {
+        check_arg("target", target)?;
+        self.send(&Ctcp::action(text).request(target)).await
+    }
+
+    /// Returns a `Splitter` for this writer's encoding.
+    ///
+    /// `prefix_len` is the length of our own `nick!user@host`, as seen by the server.
+    pub fn splitter(&self, prefix_len: usize) -> Splitter {
+        Splitter::new(self.encoding, prefix_len)
+    }
+
+    /// Returns a `ThrottledWriter` queuing messages to respect the rate limit,
+    /// and the driver writing them through this writer.
+    ///
+    /// Messages sent through this writer directly are not throttled: the
+    /// driver itself writes through it, and so do automatic replies like the
+    /// `PONG`s of `Keepalive`, which must not wait behind the queue. Send
+    /// everything else through the `ThrottledWriter` to respect the limit.
+    pub fn throttled(
+        &self,
+        limit: RateLimit,
+    ) -> (
+        ThrottledWriter,
+        impl Future<Output = Result<(), WriteError>>,
+    ) {
+        ThrottledWriter::new(self.clone(), limit)
+    }
+
+    /// Sends `PRIVMSG`s, splitting the text into as many lines as needed.
+    ///
+    /// See `Splitter`.
+    pub async fn privmsg_split(
+        &self,
+        prefix_len: usize,
+        target: &str,
+        text: &str,
+    ) -> Result<(), WriteError> {
+        for line in self
+            .splitter(prefix_len)
+            .split(&Code::Privmsg, target, text)
+        {
+            self.privmsg(target, &line).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `NOTICE`s, splitting the text into as many lines as needed.
+    ///
+    /// See `Splitter`.
+    pub async fn notice_split(
+        &self,
+        prefix_len: usize,
+        target: &str,
+        text: &str,
+    ) -> Result<(), WriteError> {
+        for line in self.splitter(prefix_len).split(&Code::Notice, target, text) {
+            self.notice(target, &line).await?;
+        }
+        Ok(())
+    }
+
+    /// Sends `JOIN <channel> [<key>]`.
+    pub async fn join(&self, channel: &str, key: Option<&str>) -> Result<(), WriteError> {
+        self.send_command(Command::Join {
+            channels: vec![check_arg("channel", channel)?],
+            keys: key
+                .map(|key| check_arg("key", key))
+                .into_iter()
+                .collect::<Result<_, _>>()?,
+        })
+        .await
+    }
+
+    /// Sends `PART <channel> [:<message>]`.
+    pub async fn part(&self, channel: &str, message: Option<&str>) -> Result<(), WriteError> {
+        self.send_command(Command::Part {
+            channels: vec![check_arg("channel", channel)?],
+            message: message.map(Into::into),
+        })
+        .await
+    }
+
+    /// Sends `NICK <nickname>`.
+    pub async fn nick(&self, nickname: &str) -> Result<(), WriteError> {
+        self.send_command(Command::Nick {
+            nickname: check_arg("nickname", nickname)?,
+        })
+        .await
+    }
+
+    /// Sends `QUIT [:<message>]`.
+    pub async fn quit(&self, message: Option<&str>) -> Result<(), WriteError> {
+        self.send_command(Command::Quit {
+            message: message.map(Into::into),
+        })
+        .await
+    }
+
+    /// Sends `PONG :<token>`, replying to `PING :<token>`.
+    pub async fn pong(&self, token: &str) -> Result<(), WriteError> {
+        self.send_command(Command::Pong {
+            server: token.into(),
+            token: None,
+        })
+        .await
+    }
+
+    /// Sets the time to wait for the end of a query, 30 seconds by default.
+    pub fn set_query_timeout(&mut self, timeout: Duration) {
+        self.query_timeout = timeout;
+    }
+
+    /// Sends the message and collects the replies answering it.
+    ///
+    /// Replies are matched by the `IrcStream` this writer comes from, which
+    /// must be polled meanwhile. They are still yielded by the stream.
+    pub async fn query(&self, msg: &Message, query: Query) -> Result<Vec<Message>, QueryError> {
+        let ticket = self.queries.start(query);
+        self.send(msg).await?;
+        ticket
+            .wait(self.query_timeout)
+            .await?
+            .map_err(QueryError::Failed)
+    }
+
+    /// Sends the message with a generated `label` tag, and waits for the
+    /// response: a message, or a batch of messages, with the same label.
+    ///
+    /// Requires the `labeled-response` capability, and `batch` for the
+    /// responses of several messages. A response with no content is an `ACK`.
+    /// As for `query`, the `IrcStream` must be polled meanwhile.
+    pub async fn send_labeled(&self, msg: &Message) -> Result<BatchItem, QueryError> {
+        let (label, ticket) = self.queries.start_labeled();
+        let mut msg = msg.clone();
+        msg.tags.insert("label", label);
+        self.send(&msg).await?;
+        ticket.wait(self.query_timeout).await
+    }
+
+    /// Sends `WHOIS <nickname>` and collects the answer.
+    pub async fn whois(&self, nickname: &str) -> Result<WhoisInfo, QueryError> {
+        let msg = Command::Whois {
+            target: None,
+            masks: vec![check_arg("nickname", nickname)?],
+        }
+        .into();
+        let replies = self.query(&msg, Query::whois(nickname)).await?;
+        Ok(WhoisInfo::from_replies(nickname, replies))
+    }
+
+    /// Sends `WHO <mask>` and collects the answer.
+    pub async fn who(&self, mask: &str) -> Result<Vec<WhoEntry>, QueryError> {
+        let msg = Command::Who {
+            mask: Some(check_arg("mask", mask)?),
+            operators: false,
+        }
+        .into();
+        let replies = self.query(&msg, Query::who(mask)).await?;
+        Ok(WhoEntry::from_replies(&replies))
+    }
+
+    /// Sends `NAMES <channel>` and collects the names, with their membership prefixes.
+    pub async fn names(&self, channel: &str) -> Result<Vec<String>, QueryError> {
+        let msg = Command::Names {
+            channels: vec![check_arg("channel", channel)?],
+            target: None,
+        }
+        .into();
+        let replies = self.query(&msg, Query::names(channel)).await?;
+        Ok(names_from_replies(&replies))
+    }
+
+    /// Sends `LIST [<mask>]` and collects the channels.
+    pub async fn list(&self, mask: Option<&str>) -> Result<Vec<ListEntry>, QueryError> {
+        let msg = Command::List {
+            channels: mask
+                .map(|mask| check_arg("mask", mask))
+                .into_iter()
+                .collect::<Result<_, _>>()?,
+            target: None,
+        }
+        .into();
+        let replies = self.query(&msg, Query::list()).await?;
+        Ok(ListEntry::from_replies(&replies))
+    }
+
+    /// Sends `MOTD` and collects its lines.
+    pub async fn motd(&self) -> Result<Vec<String>, QueryError> {
+        let msg = Command::Motd { target: None }.into();
+        let replies = self.query(&msg, Query::motd()).await?;
+        Ok(motd_from_replies(&replies))
+    }
+
+    /// Sends `MODE <channel> +b` and collects the bans.
+    pub async fn banlist(&self, channel: &str) -> Result<Vec<BanEntry>, QueryError> {
+        let msg = Command::Mode {
+            target: check_arg("channel", channel)?,
+            changes: vec!["+b".into()],
+        }
+        .into();
+        let replies = self.query(&msg, Query::banlist(channel)).await?;
+        Ok(BanEntry::from_replies(&replies))
+    }
 }
 
 impl<S> Clone for Writer<S> {
@@ -77,6 +399,8 @@ impl<S> Clone for Writer<S> {
         Writer {
             encoding: self.encoding,
             inner: self.inner.clone(),
+            queries: self.queries.clone(),
+            query_timeout: self.query_timeout,
         }
     }
 }
@@ -87,6 +411,7 @@ pub struct IrcStream<S> {
     writer: Writer<S>,
     async_buf: Vec<u8>,
     async_read: usize,
+    line: String,
 }
 
 impl<S> IrcStream<S>
@@ -98,6 +423,8 @@ where
         let writer = Writer {
             encoding,
             inner: Arc::new(Mutex::new(write_half)),
+            queries: Queries::default(),
+            query_timeout: DEFAULT_QUERY_TIMEOUT,
         };
 
         IrcStream {
@@ -106,6 +433,7 @@ where
             writer,
             async_buf: Vec::new(),
             async_read: 0,
+            line: String::new(),
         }
     }
 
@@ -114,6 +442,23 @@ where
     }
 }
 
+impl<S> IrcStream<S> {
+    /// Feeds the message to the queries of the writers, if any is waiting.
+    ///
+    /// `RPL_ISUPPORT` is always fed, for the casemapping of the server.
+    fn correlate(&self, msg: &MessageRef<'_>) {
+        if msg.command == "005" || self.writer.queries.is_waiting() {
+            self.writer.queries.handle(&msg.to_owned());
+        }
+    }
+}
+
+impl<S> Drop for IrcStream<S> {
+    fn drop(&mut self) {
+        self.writer.queries.close();
+    }
+}
+
 impl<S> IrcStream<AllowStdIo<S>>
 where
     S: Read + Write + Send,
@@ -149,17 +494,19 @@ fn read_until_internal<R: AsyncBufRead + ?Sized>(
     }
 }
 
-impl<S> Stream for IrcStream<S>
+impl<S> IrcStream<S>
 where
     S: AsyncRead + Unpin,
 {
-    type Item = Result<Message, StreamError>;
-
-    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+    /// Reads the next line into the internal buffer.
+    ///
+    /// Returns `false` when the end of the stream is reached.
+    fn poll_line(&mut self, cx: &mut Context<'_>) -> Poll<Result<bool, AsyncIoError>> {
         let Self {
             ref mut reader,
             ref mut async_buf,
             ref mut async_read,
+            ref mut line,
             encoding,
             ..
         } = *self;
@@ -172,11 +519,70 @@ where
             cx
         ))?;
 
-        if read > 0 {
-            let line = encoding.decode(async_buf, DecoderTrap::Ignore).unwrap();
+        if read == 0 {
+            self.writer.queries.close();
+            Poll::Ready(Ok(false))
+        } else {
+            line.clear();
+            encoding
+                .decode_to(async_buf, DecoderTrap::Ignore, line)
+                .unwrap();
             *async_read = 0;
             async_buf.clear();
-            Poll::Ready(Some(Message::parse(&line).map_err(Into::into)))
+            Poll::Ready(Ok(true))
+        }
+    }
+
+    /// Polls the next message, borrowing it from the internal buffer.
+    ///
+    /// Unlike the `Stream` implementation, this does not allocate owned strings
+    /// for each message. The returned message is valid until the next call.
+    pub fn poll_next_ref(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<MessageRef<'_>, StreamError>>> {
+        if ready!(self.poll_line(cx))? {
+            let msg = MessageRef::parse(&self.line);
+            if let Ok(ref msg) = msg {
+                self.correlate(msg);
+            }
+            Poll::Ready(Some(msg.map_err(Into::into)))
+        } else {
+            Poll::Ready(None)
+        }
+    }
+
+    /// Reads the next message, borrowing it from the internal buffer.
+    ///
+    /// See `poll_next_ref`.
+    pub async fn next_ref(&mut self) -> Option<Result<MessageRef<'_>, StreamError>> {
+        match future::poll_fn(|cx| self.poll_line(cx)).await {
+            Ok(true) => {
+                let msg = MessageRef::parse(&self.line);
+                if let Ok(ref msg) = msg {
+                    self.correlate(msg);
+                }
+                Some(msg.map_err(Into::into))
+            }
+            Ok(false) => None,
+            Err(e) => Some(Err(e.into())),
+        }
+    }
+}
+
+impl<S> Stream for IrcStream<S>
+where
+    S: AsyncRead + Unpin,
+{
+    type Item = Result<Message, StreamError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if ready!(self.poll_line(cx))? {
+            let msg = MessageRef::parse(&self.line).map(|msg| {
+                self.correlate(&msg);
+                msg.to_owned()
+            });
+            Poll::Ready(Some(msg.map_err(Into::into)))
         } else {
             Poll::Ready(None)
         }
@@ -194,3 +600,265 @@ where
         block_on_stream(self)
     }
 }
+
+#[test]
+fn test_next_ref() {
+    use futures::io::Cursor;
+
+    let lines = "PING :a\r\n:bob!bob@bob.com PRIVMSG #chan :hello\r\n";
+    let mut stream = IrcStream::new(Cursor::new(lines.as_bytes().to_vec()), encoding::all::UTF_8);
+    block_on(async {
+        let msg = stream.next_ref().await.unwrap().unwrap();
+        assert_eq!(msg.command, "PING");
+        assert_eq!(msg.args, vec!["a"]);
+        let msg = stream.next_ref().await.unwrap().unwrap();
+        assert_eq!(msg.command, "PRIVMSG");
+        assert_eq!(msg.args, vec!["#chan", "hello"]);
+        assert!(stream.next_ref().await.is_none());
+    });
+}
+
+#[test]
+fn test_stream() {
+    use futures::io::Cursor;
+
+    let lines = "PING :a\r\nPING :b\r\n";
+    let stream = IrcStream::new(Cursor::new(lines.as_bytes().to_vec()), encoding::all::UTF_8);
+    let msgs = stream.into_iter().collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(msgs.len(), 2);
+    assert_eq!(msgs[1].args, vec!["b"]);
+}
+
+#[test]
+fn test_send() {
+    use crate::{code::Code, mock::MockStream};
+
+    let (mock, output) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        let msg = Message::build(Code::Privmsg).arg("#chan").trailing("hi");
+        writer.send(&msg).await.unwrap();
+        writer.privmsg("#chan", "hello world").await.unwrap();
+        writer.notice("bob", ":)").await.unwrap();
+        writer.join("#chan", Some("secret")).await.unwrap();
+        writer.part("#chan", None).await.unwrap();
+        writer.nick("bob").await.unwrap();
+        writer.quit(Some("bye bye")).await.unwrap();
+        writer.pong("irc.example.com").await.unwrap();
+    });
+    assert_eq!(
+        output.get(),
+        "PRIVMSG #chan hi\r\n\
+         PRIVMSG #chan :hello world\r\n\
+         NOTICE bob ::)\r\n\
+         JOIN #chan secret\r\n\
+         PART #chan\r\n\
+         NICK bob\r\n\
+         QUIT :bye bye\r\n\
+         PONG irc.example.com\r\n"
+    );
+}
+
+#[test]
+fn test_invalid_argument() {
+    use crate::mock::MockStream;
+
+    let (mock, output) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        assert!(matches!(
+            writer.privmsg("#a #b", "hi").await,
+            Err(WriteError::InvalidArgument { name: "target", .. })
+        ));
+        assert!(matches!(
+            writer.nick("").await,
+            Err(WriteError::InvalidArgument {
+                name: "nickname",
+                ..
+            })
+        ));
+    });
+    assert_eq!(output.get(), "");
+}
+
+#[test]
+fn test_send_injection() {
+    use crate::mock::MockStream;
+
+    let (mock, output) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        assert!(matches!(
+            writer.privmsg("#chan", "hi\r\nQUIT :pwned").await,
+            Err(WriteError::InvalidMessage(
+                ValidationError::ForbiddenCharacter { index: 1, .. }
+            ))
+        ));
+        assert!(matches!(
+            writer.privmsg(":bob", "hi").await,
+            Err(WriteError::InvalidMessage(ValidationError::InvalidMiddle(
+                0
+            )))
+        ));
+    });
+    assert_eq!(output.get(), "");
+}
+
+#[test]
+fn test_privmsg_split() {
+    use crate::{mock::MockStream, split::MAX_LINE_LEN};
+
+    let (mock, output) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    let prefix = "bob!bob@bob.com";
+    let text = vec!["word"; 300].join(" ");
+    block_on(writer.privmsg_split(prefix.len(), "#chan", &text)).unwrap();
+    let output = output.get();
+    let lines = output.split_terminator("\r\n").collect::<Vec<_>>();
+    assert_eq!(lines.len(), 4);
+    for line in lines {
+        assert!(line.starts_with("PRIVMSG #chan :word"));
+        assert!(format!(":{} {}\r\n", prefix, line).len() <= MAX_LINE_LEN);
+    }
+}
+
+#[test]
+fn test_query() {
+    use crate::mock::MockStream;
+
+    let (mock, output) = MockStream::new(
+        ":irc.example.com 311 me bob ~bob bob.com * :Bob Bobson\r\n\
+         PING :irc.example.com\r\n\
+         :irc.example.com 318 me bob :End of /WHOIS list\r\n\
+         :irc.example.com 401 me ghost :No such nick/channel\r\n\
+         :irc.example.com 318 me ghost :End of /WHOIS list\r\n",
+    );
+    let mut stream = IrcStream::new(mock.hang(), encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        // Both are sent before any reply is read.
+        let queries = future::join(writer.whois("bob"), writer.whois("ghost"));
+        let messages = async {
+            let mut codes = Vec::new();
+            while let Some(msg) = stream.next().await {
+                codes.push(msg.unwrap().code);
+                if codes.len() == 5 {
+                    break;
+                }
+            }
+            codes
+        };
+        let ((info, err), codes) = future::join(queries, messages).await;
+        let (info, err) = (info.unwrap(), err.unwrap_err());
+        assert_eq!(info.realname.as_deref(), Some("Bob Bobson"));
+        assert!(matches!(err, QueryError::Failed(ref msg) if msg.code == Code::ErrNosuchnick));
+        // Replies are still yielded.
+        assert_eq!(codes.len(), 5);
+        assert_eq!(codes[1], Code::Ping);
+    });
+    assert_eq!(output.get(), "WHOIS bob\r\nWHOIS ghost\r\n");
+}
+
+#[test]
+fn test_query_casemapping() {
+    use crate::mock::MockStream;
+
+    let (mock, _) = MockStream::new(
+        ":irc.example.com 005 me CASEMAPPING=ascii :are supported by this server\r\n\
+         :irc.example.com 311 me bob{} ~bob bob.com * :Bob Bobson\r\n\
+         :irc.example.com 318 me bob{} :End of /WHOIS list\r\n\
+         :irc.example.com 318 me BOB[] :End of /WHOIS list\r\n",
+    );
+    let mut stream = IrcStream::new(mock.hang(), encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        // Read while no query is waiting.
+        assert!(stream.next().await.is_some());
+        let messages = async {
+            for _ in 0..3 {
+                stream.next().await.unwrap().unwrap();
+            }
+        };
+        let (info, _) = future::join(writer.whois("Bob[]"), messages).await;
+        // `bob{}` is another nickname with `ascii`.
+        assert!(info.unwrap().replies.is_empty());
+    });
+    assert!(!writer.queries.is_waiting());
+}
+
+#[test]
+fn test_query_timeout() {
+    use crate::mock::MockStream;
+
+    let (mock, _) = MockStream::new("");
+    let mut stream = IrcStream::new(mock.hang(), encoding::all::UTF_8);
+    let mut writer = stream.writer();
+    writer.set_query_timeout(Duration::from_millis(10));
+    block_on(async {
+        let query = writer.motd();
+        let messages = stream.next();
+        match future::select(Box::pin(query), messages).await {
+            future::Either::Left((result, _)) => {
+                assert!(matches!(result, Err(QueryError::Timeout)))
+            }
+            future::Either::Right(_) => panic!("stream ended"),
+        }
+    });
+
+    let (mock, _) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let writer = stream.writer();
+    drop(stream);
+    assert!(matches!(block_on(writer.motd()), Err(QueryError::Closed)));
+}
+
+#[test]
+fn test_send_labeled() {
+    use crate::mock::MockStream;
+
+    let (mock, output) = MockStream::new(
+        "@label=yaircc1 :irc.example.com BATCH +b labeled-response\r\n\
+         @batch=b :irc.example.com 311 me bob ~bob bob.com * :Bob\r\n\
+         @batch=b :irc.example.com 318 me bob :End of /WHOIS list\r\n\
+         @label=yaircc0 :me!me@host PRIVMSG #chan :hi\r\n\
+         :irc.example.com BATCH -b\r\n\
+         @label=yaircc2 :irc.example.com ACK\r\n",
+    );
+    let mut stream = IrcStream::new(mock.hang(), encoding::all::UTF_8);
+    let writer = stream.writer();
+    block_on(async {
+        let privmsg = Message::build(Code::Privmsg).arg("#chan").trailing("hi");
+        let whois = Message::build(Code::Whois).arg("bob").finish();
+        let nick = Message::build(Code::Nick).arg("me").finish();
+        let sends = future::join3(
+            writer.send_labeled(&privmsg),
+            writer.send_labeled(&whois),
+            writer.send_labeled(&nick),
+        );
+        let messages = async {
+            for _ in 0..6 {
+                stream.next().await.unwrap().unwrap();
+            }
+        };
+        let ((echo, batch, ack), ()) = future::join(sends, messages).await;
+        assert!(matches!(echo.unwrap(), BatchItem::Message(ref msg) if msg.code == Code::Privmsg));
+        match batch.unwrap() {
+            BatchItem::Batch(batch) => {
+                assert_eq!(batch.kind, "labeled-response");
+                assert_eq!(batch.messages().len(), 2);
+            }
+            item => panic!("unexpected {:?}", item),
+        }
+        assert!(matches!(ack.unwrap(), BatchItem::Message(ref msg) if msg.code == Code::Ack));
+    });
+    assert_eq!(
+        output.get(),
+        "@label=yaircc0 PRIVMSG #chan hi\r\n\
+         @label=yaircc1 WHOIS bob\r\n\
+         @label=yaircc2 NICK me\r\n"
+    );
+}
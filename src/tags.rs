@@ -0,0 +1,226 @@
+//! IRCv3 message tags.
+
+use std::{fmt, iter::FromIterator, slice};
+
+/// Ordered collection of IRCv3 message tags.
+///
+/// Keys are stored verbatim, so client-only tags (`+example`) and vendor
+/// prefixed tags (`example.com/tag`) keep their full name. A tag without a
+/// value is stored with an empty value, as the specification treats both the
+/// same way.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Tags(Vec<(String, String)>);
+
+impl Tags {
+    /// Creates an empty tag set.
+    pub fn new() -> Self {
+        Tags(Vec::new())
+    }
+
+    /// Parses the raw tag block, without the leading `@`.
+    ///
+    /// Values are unescaped. When a key appears more than once, the last value wins.
+    pub fn parse(raw: &str) -> Self {
+        let mut tags = Tags::new();
+        for tag in raw.split(';').filter(|tag| !tag.is_empty()) {
+            match tag.find('=') {
+                None => tags.insert(tag, ""),
+                Some(idx) => tags.insert(&tag[..idx], unescape(&tag[idx + 1..])),
+            }
+        }
+        tags
+    }
+
+    /// Returns the value of the tag, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Checks if the tag is present.
+    pub fn contains(&self, key: &str) -> bool {
+        self.get(key).is_some()
+    }
+
+    /// Sets the value of the tag, keeping its position if it already exists.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) {
+        let key = key.into();
+        let value = value.into();
+        match self.0.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key, value)),
+        }
+    }
+
+    /// Removes the tag and returns its value, if present.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        let idx = self.0.iter().position(|(k, _)| k == key)?;
+        Some(self.0.remove(idx).1)
+    }
+
+    /// Iterates over the tags in order.
+    pub fn iter(&self) -> Iter<'_> {
+        Iter(self.0.iter())
+    }
+
+    /// Number of tags.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks if there are no tags.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Tags
+where
+    K: Into<String>,
+    V: Into<String>,
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut tags = Tags::new();
+        for (key, value) in iter {
+            tags.insert(key, value);
+        }
+        tags
+    }
+}
+
+impl<'a> IntoIterator for &'a Tags {
+    type Item = (&'a str, &'a str);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over the tags of a `Tags`.
+#[derive(Clone, Debug)]
+pub struct Iter<'a>(slice::Iter<'a, (String, String)>);
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a str, &'a str);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+}
+
+/// Checks if the key is a client-only tag, i.e. starts with `+`.
+pub fn is_client_only(key: &str) -> bool {
+    key.starts_with('+')
+}
+
+/// Returns the vendor prefix of the key, if any.
+///
+/// `+example.com/foo` has the vendor `example.com`.
+pub fn vendor(key: &str) -> Option<&str> {
+    let key = key.trim_start_matches('+');
+    key.find('/').map(|idx| &key[..idx])
+}
+
+/// Unescapes a tag value as described by the IRCv3 message-tags specification.
+pub fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some(':') => unescaped.push(';'),
+            Some('s') => unescaped.push(' '),
+            Some('\\') => unescaped.push('\\'),
+            Some('r') => unescaped.push('\r'),
+            Some('n') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Escapes a tag value as described by the IRCv3 message-tags specification.
+pub fn escape(value: &str) -> Escaped<'_> {
+    Escaped(value)
+}
+
+/// Tag value displayed in its escaped form. See `escape`.
+#[derive(Clone, Copy, Debug)]
+pub struct Escaped<'a>(&'a str);
+
+impl fmt::Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut rest = self.0;
+        while let Some(idx) = rest.find(&[';', ' ', '\\', '\r', '\n'][..]) {
+            f.write_str(&rest[..idx])?;
+            f.write_str(match rest.as_bytes()[idx] {
+                b';' => "\\:",
+                b' ' => "\\s",
+                b'\\' => "\\\\",
+                b'\r' => "\\r",
+                _ => "\\n",
+            })?;
+            rest = &rest[idx + 1..];
+        }
+        f.write_str(rest)
+    }
+}
+
+impl fmt::Display for Tags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            f.write_str(key)?;
+            if !value.is_empty() {
+                write!(f, "={}", escape(value))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_unescape() {
+    assert_eq!(
+        unescape("a\\:b\\sc\\\\d\\re\\nf"),
+        "a;b c\\d\re\nf".to_string()
+    );
+    assert_eq!(unescape("\\b\\"), "b".to_string());
+}
+
+#[test]
+fn test_escape() {
+    assert_eq!(
+        escape("a;b c\\d\re\nf").to_string(),
+        "a\\:b\\sc\\\\d\\re\\nf"
+    );
+}
+
+#[test]
+fn test_display() {
+    let tags = Tags::parse("a=1;+b.c/d;e=x\\sy");
+    assert_eq!(tags.to_string(), "a=1;+b.c/d;e=x\\sy");
+}
+
+#[test]
+fn test_duplicate_key() {
+    let tags = Tags::parse("a=1;b;a=2");
+    assert_eq!(tags.iter().collect::<Vec<_>>(), vec![("a", "2"), ("b", "")]);
+}
+
+#[test]
+fn test_vendor() {
+    assert!(is_client_only("+example.com/foo"));
+    assert!(!is_client_only("example.com/foo"));
+    assert_eq!(vendor("+example.com/foo"), Some("example.com"));
+    assert_eq!(vendor("time"), None);
+}
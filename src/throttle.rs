@@ -0,0 +1,414 @@
+//! Outgoing flood control with a token bucket.
+
+use {
+    crate::{
+        code::Code,
+        command::Command,
+        message::Message,
+        stream::{check_arg, WriteError, Writer},
+    },
+    futures::{
+        channel::mpsc::{self, UnboundedReceiver, UnboundedSender},
+        future::{self, Either},
+        prelude::*,
+    },
+    futures_timer::Delay,
+    std::{
+        collections::VecDeque,
+        io::{Error as IoError, ErrorKind},
+        time::{Duration, Instant},
+    },
+};
+
+/// Configuration of the token bucket.
+///
+/// A message costs one token, plus one token per `bytes_per_token` bytes of
+/// the line. The defaults follow the penalty of ircu-derived servers: two
+/// seconds per message plus one second per 120 bytes, with ten seconds of
+/// allowance.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct RateLimit {
+    /// Number of tokens available at once.
+    pub burst: u32,
+    /// Time to get a token back.
+    pub refill: Duration,
+    /// Length of the line costing one more token.
+    pub bytes_per_token: usize,
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        RateLimit {
+            burst: 5,
+            refill: Duration::from_secs(2),
+            bytes_per_token: 240,
+        }
+    }
+}
+
+/// Token bucket.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    last: Option<Instant>,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket, refilled at the given rate.
+    pub fn new(limit: RateLimit) -> Self {
+        TokenBucket {
+            limit,
+            tokens: f64::from(limit.burst),
+            last: None,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if let Some(last) = self.last {
+            let elapsed = now.saturating_duration_since(last);
+            let tokens = elapsed.as_secs_f64() / self.limit.refill.as_secs_f64();
+            self.tokens = (self.tokens + tokens).min(f64::from(self.limit.burst));
+        }
+        self.last = Some(now);
+    }
+
+    /// Cost of sending the line.
+    pub fn cost(&self, line_len: usize) -> f64 {
+        1.0 + line_len as f64 / self.limit.bytes_per_token.max(1) as f64
+    }
+
+    /// Time to wait before `cost` tokens are available.
+    pub fn wait(&mut self, cost: f64, now: Instant) -> Duration {
+        self.refill(now);
+        // A message costing more than the burst is sent with a full bucket.
+        let missing = cost.min(f64::from(self.limit.burst)) - self.tokens;
+        if missing <= 0.0 {
+            Duration::from_secs(0)
+        } else {
+            self.limit.refill.mul_f64(missing)
+        }
+    }
+
+    /// Takes `cost` tokens, going below zero if needed.
+    pub fn take(&mut self, cost: f64, now: Instant) {
+        self.refill(now);
+        self.tokens -= cost;
+    }
+}
+
+/// Checks if the message bypasses the queue.
+fn is_urgent(msg: &Message) -> bool {
+    matches!(msg.code, Code::Pong | Code::Ping | Code::Quit)
+}
+
+/// Send queue, sans I/O.
+///
+/// `PING`, `PONG` and `QUIT` bypass the queue. Other messages are queued per
+/// target, and targets take turns so that a long reply to one channel does
+/// not delay the others.
+#[derive(Clone, Debug)]
+pub struct Throttle {
+    bucket: TokenBucket,
+    urgent: VecDeque<Message>,
+    targets: VecDeque<(String, VecDeque<Message>)>,
+}
+
+impl Throttle {
+    /// Creates an empty queue, sending at the given rate.
+    pub fn new(limit: RateLimit) -> Self {
+        Throttle {
+            bucket: TokenBucket::new(limit),
+            urgent: VecDeque::new(),
+            targets: VecDeque::new(),
+        }
+    }
+
+    /// Checks if nothing is queued.
+    pub fn is_empty(&self) -> bool {
+        self.urgent.is_empty() && self.targets.is_empty()
+    }
+
+    /// Queues the message.
+    pub fn push(&mut self, msg: Message) {
+        if is_urgent(&msg) {
+            self.urgent.push_back(msg);
+            return;
+        }
+        let target = match msg.code {
+            Code::Privmsg | Code::Notice | Code::Join | Code::Part | Code::Mode | Code::Kick => {
+                msg.args.first().cloned().unwrap_or_default()
+            }
+            _ => String::new(),
+        };
+        match self.targets.iter_mut().find(|(t, _)| *t == target) {
+            Some((_, queue)) => queue.push_back(msg),
+            None => self.targets.push_back((target, vec![msg].into())),
+        }
+    }
+
+    /// Pops the next message to send now.
+    ///
+    /// Otherwise, returns how long to wait, or `None` if nothing is queued.
+    pub fn pop(&mut self, now: Instant) -> Result<Message, Option<Duration>> {
+        if let Some(msg) = self.urgent.pop_front() {
+            let cost = self.bucket.cost(msg.to_string().len() + 2);
+            self.bucket.take(cost, now);
+            return Ok(msg);
+        }
+
+        let cost = match self.targets.front() {
+            Some((_, queue)) => self.bucket.cost(queue[0].to_string().len() + 2),
+            None => return Err(None),
+        };
+        let wait = self.bucket.wait(cost, now);
+        if wait > Duration::from_secs(0) {
+            return Err(Some(wait));
+        }
+        self.bucket.take(cost, now);
+
+        let (target, mut queue) = self.targets.pop_front().unwrap();
+        let msg = queue.pop_front().unwrap();
+        if !queue.is_empty() {
+            self.targets.push_back((target, queue));
+        }
+        Ok(msg)
+    }
+}
+
+/// Writer queuing messages to respect a `RateLimit`.
+///
+/// Messages are written by the driver returned along with it, which must be
+/// spawned or polled.
+pub struct ThrottledWriter {
+    sender: UnboundedSender<Message>,
+}
+
+impl Clone for ThrottledWriter {
+    fn clone(&self) -> Self {
+        ThrottledWriter {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl ThrottledWriter {
+    /// Creates the writer and its driver, writing through `writer`.
+    ///
+    /// The driver completes once every `ThrottledWriter` is dropped and the
+    /// queue is empty, or when writing fails.
+    pub fn new<S>(
+        writer: Writer<S>,
+        limit: RateLimit,
+    ) -> (Self, impl Future<Output = Result<(), WriteError>>)
+    where
+        S: AsyncWrite + Unpin,
+    {
+        let (sender, receiver) = mpsc::unbounded();
+        (
+            ThrottledWriter { sender },
+            drive(writer, receiver, Throttle::new(limit)),
+        )
+    }
+
+    /// Queues the message. It is validated first, like `Writer::send`.
+    pub fn send(&self, msg: Message) -> Result<(), WriteError> {
+        msg.validate()?;
+        self.sender.unbounded_send(msg).map_err(|_| {
+            IoError::new(ErrorKind::BrokenPipe, "throttle driver is not running").into()
+        })
+    }
+
+    /// Queues `PRIVMSG <target> :<text>`.
+    pub fn privmsg(&self, target: &str, text: &str) -> Result<(), WriteError> {
+        self.send(
+            Command::Privmsg {
+                target: check_arg("target", target)?,
+                text: text.into(),
+            }
+            .into(),
+        )
+    }
+
+    /// Queues `NOTICE <target> :<text>`.
+    pub fn notice(&self, target: &str, text: &str) -> Result<(), WriteError> {
+        self.send(
+            Command::Notice {
+                target: check_arg("target", target)?,
+                text: text.into(),
+            }
+            .into(),
+        )
+    }
+}
+
+async fn drive<S>(
+    writer: Writer<S>,
+    mut receiver: UnboundedReceiver<Message>,
+    mut throttle: Throttle,
+) -> Result<(), WriteError>
+where
+    S: AsyncWrite + Unpin,
+{
+    let mut closed = false;
+    loop {
+        while !closed {
+            match receiver.next().now_or_never() {
+                Some(Some(msg)) => throttle.push(msg),
+                Some(None) => closed = true,
+                None => break,
+            }
+        }
+
+        let wait = match throttle.pop(Instant::now()) {
+            Ok(msg) => {
+                writer.send(&msg).await?;
+                continue;
+            }
+            Err(None) if closed => return Ok(()),
+            Err(wait) => wait,
+        };
+        let delay = match wait {
+            Some(wait) => Either::Left(Delay::new(wait)),
+            None => Either::Right(future::pending()),
+        };
+        if closed {
+            delay.await;
+        } else if let Either::Left((Some(msg), _)) = future::select(receiver.next(), delay).await {
+            throttle.push(msg);
+        }
+    }
+}
+
+#[cfg(test)]
+fn privmsg(target: &str, text: &str) -> Message {
+    Command::Privmsg {
+        target: target.into(),
+        text: text.into(),
+    }
+    .into()
+}
+
+#[test]
+fn test_bucket() {
+    let limit = RateLimit {
+        burst: 2,
+        refill: Duration::from_secs(2),
+        bytes_per_token: 1000,
+    };
+    let mut bucket = TokenBucket::new(limit);
+    let now = Instant::now();
+    assert_eq!(bucket.wait(1.0, now), Duration::from_secs(0));
+    bucket.take(1.0, now);
+    bucket.take(1.0, now);
+    assert_eq!(bucket.wait(1.0, now), Duration::from_secs(2));
+    assert_eq!(
+        bucket.wait(1.0, now + Duration::from_secs(1)),
+        Duration::from_secs(1)
+    );
+    assert_eq!(
+        bucket.wait(1.0, now + Duration::from_secs(2)),
+        Duration::from_secs(0)
+    );
+    // Never more than the burst.
+    assert_eq!(
+        bucket.wait(2.0, now + Duration::from_secs(60)),
+        Duration::from_secs(0)
+    );
+    bucket.take(2.0, now + Duration::from_secs(60));
+    assert_eq!(
+        bucket.wait(1.0, now + Duration::from_secs(60)),
+        Duration::from_secs(2)
+    );
+}
+
+#[test]
+fn test_priority_and_fairness() {
+    let limit = RateLimit {
+        burst: 10,
+        refill: Duration::from_secs(1),
+        bytes_per_token: 10000,
+    };
+    let mut throttle = Throttle::new(limit);
+    for i in 0..3 {
+        throttle.push(privmsg("#a", &i.to_string()));
+    }
+    throttle.push(privmsg("#b", "0"));
+    throttle.push(Command::Quit { message: None }.into());
+
+    let now = Instant::now();
+    let order = (0..5)
+        .map(|_| throttle.pop(now).unwrap().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        order,
+        vec![
+            "QUIT",
+            "PRIVMSG #a 0",
+            "PRIVMSG #b 0",
+            "PRIVMSG #a 1",
+            "PRIVMSG #a 2"
+        ]
+    );
+    assert_eq!(throttle.pop(now), Err(None));
+    assert!(throttle.is_empty());
+}
+
+#[test]
+fn test_throttle_wait() {
+    let limit = RateLimit {
+        burst: 1,
+        refill: Duration::from_secs(2),
+        bytes_per_token: 10000,
+    };
+    let mut throttle = Throttle::new(limit);
+    throttle.push(privmsg("#a", "0"));
+    throttle.push(privmsg("#a", "1"));
+    let now = Instant::now();
+    assert!(throttle.pop(now).is_ok());
+    assert!(matches!(throttle.pop(now), Err(Some(_))));
+    throttle.push(
+        Command::Pong {
+            server: "irc.example.com".into(),
+            token: None,
+        }
+        .into(),
+    );
+    assert_eq!(
+        throttle.pop(now).unwrap().to_string(),
+        "PONG irc.example.com"
+    );
+    assert!(throttle.pop(now + Duration::from_secs(6)).is_ok());
+}
+
+#[test]
+fn test_driver() {
+    use {
+        crate::{mock::MockStream, stream::IrcStream},
+        futures::executor::block_on,
+    };
+
+    let (mock, output) = MockStream::new("");
+    let stream = IrcStream::new(mock, encoding::all::UTF_8);
+    let limit = RateLimit {
+        burst: 1,
+        refill: Duration::from_millis(10),
+        bytes_per_token: 10000,
+    };
+    let (writer, driver) = stream.writer().throttled(limit);
+    writer.privmsg("#a", "0").unwrap();
+    writer.privmsg("#a", "1").unwrap();
+    writer.send(Command::Quit { message: None }.into()).unwrap();
+    assert!(writer.privmsg("#a", "a\r\nQUIT").is_err());
+    assert!(matches!(
+        writer.privmsg("#a QUIT", "a"),
+        Err(WriteError::InvalidArgument { name: "target", .. })
+    ));
+    assert!(writer.notice("", "a").is_err());
+    drop(writer);
+
+    let start = Instant::now();
+    block_on(driver).unwrap();
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(output.get(), "QUIT\r\nPRIVMSG #a 0\r\nPRIVMSG #a 1\r\n");
+}